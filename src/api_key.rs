@@ -0,0 +1,63 @@
+// Resolving the API key from the environment or the config
+use crate::config::Config;
+use std::fs;
+
+// Environment variable holding the API key; takes precedence over the config
+pub const API_KEY_ENV: &str = "OPENROUTER_API_KEY";
+
+// Find the API key: OPENROUTER_API_KEY, then `api_key_file`, then inline `api_key`
+pub fn resolve_api_key(config: &Config) -> Result<String, String> {
+    resolve_api_key_with(std::env::var(API_KEY_ENV).ok(), config)
+}
+
+// Where resolve_api_key takes the key from, for error messages about a rejected key
+pub fn api_key_source(config: &Config) -> String {
+    api_key_source_with(std::env::var(API_KEY_ENV).ok(), config)
+}
+
+// Same as api_key_source, with the environment variable's value passed in
+pub fn api_key_source_with(env_key: Option<String>, config: &Config) -> String {
+    let is_set = |key: Option<&str>| key.is_some_and(|key| !key.trim().is_empty());
+    if is_set(env_key.as_deref()) {
+        format!("the {} environment variable", API_KEY_ENV)
+    } else if let Some(path) = &config.api_key_file {
+        format!("the file {:?} ('api_key_file')", path)
+    } else if is_set(config.api_key.as_deref()) {
+        "'api_key' in the config".to_string()
+    } else {
+        format!(
+            "nowhere: set {}, or 'api_key_file' or 'api_key' in the config",
+            API_KEY_ENV
+        )
+    }
+}
+
+// Same as resolve_api_key, with the environment variable's value passed in
+pub fn resolve_api_key_with(env_key: Option<String>, config: &Config) -> Result<String, String> {
+    // Blank values count as unset
+    let non_empty = |key: &str| {
+        let key = key.trim();
+        (!key.is_empty()).then(|| key.to_string())
+    };
+
+    if let Some(key) = env_key.as_deref().and_then(non_empty) {
+        return Ok(key);
+    }
+
+    if let Some(path) = &config.api_key_file {
+        // A configured but unusable key file is an error rather than silently skipped
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Error: Could not read API key file {:?}: {}", path, e))?;
+        return non_empty(&contents)
+            .ok_or_else(|| format!("Error: API key file {:?} is empty.", path));
+    }
+
+    if let Some(key) = config.api_key.as_deref().and_then(non_empty) {
+        return Ok(key);
+    }
+
+    Err(format!(
+        "Error: No API key found. Set the {} environment variable, or 'api_key_file' or 'api_key' in the config.",
+        API_KEY_ENV
+    ))
+}
@@ -0,0 +1,185 @@
+// State shared by the window's handlers, bundled so each handler clones a single Rc<AppState>
+// instead of one Rc<RefCell<...>> per value
+use crate::config::Config;
+use crate::translation::RateLimiter;
+use gtk::prelude::*;
+use gtk::{glib, ToggleButton};
+use lingua::Language;
+use std::cell::{Cell, Ref, RefCell};
+use std::rc::Rc;
+use std::time::Duration;
+
+// Language buttons in display order, each with the language it translates into
+pub type LanguageButtonRc = Rc<RefCell<ToggleButton>>;
+pub type LanguageButtonsVec = Vec<(Language, LanguageButtonRc)>;
+
+pub struct AppState {
+    // Read-only while the window is open
+    config: Config,
+    // Clipboard text, or the edited text in live preview
+    original_text: RefCell<Option<String>>,
+    api_key: RefCell<Option<String>>,
+    // Detected (or forced) language of the original text, None if unknown
+    source_language: Cell<Option<Language>>,
+    // Language the output is translated into: chosen by the startup flow, then by every pick.
+    // Not always the last language in the settings, a pinned target isn't saved there.
+    target_language: Cell<Option<Language>>,
+    language_buttons: RefCell<LanguageButtonsVec>,
+    // Shared by every translation request so rapid language switching stays under the API cap
+    rate_limiter: RateLimiter,
+    // All rows as one text, for Copy & Close in multi-target mode
+    multi_text: RefCell<Option<String>>,
+    // Set when the startup translation was skipped as the text is already in the target
+    // language; clicking the active button then translates anyway
+    skipped_same_language: Cell<bool>,
+    // Set once the settings were written on exit, so a signal followed by the window
+    // closing doesn't write them twice
+    exit_state_flushed: Cell<bool>,
+    // Timer closing the window after the translation (auto_close_ms), if one is pending
+    auto_close: RefCell<Option<glib::SourceId>>,
+    // Set by the first click in the window; the window then stays open
+    user_interacted: Cell<bool>,
+    // Last language from before the target was switched automatically, restored by Undo
+    switched_from: Cell<Option<Language>>,
+}
+
+impl AppState {
+    pub fn new(config: Config) -> Self {
+        let rate_limiter = RateLimiter::per_minute(config.requests_per_minute);
+        AppState {
+            config,
+            original_text: RefCell::new(None),
+            api_key: RefCell::new(None),
+            source_language: Cell::new(None),
+            target_language: Cell::new(None),
+            language_buttons: RefCell::new(Vec::new()),
+            rate_limiter,
+            multi_text: RefCell::new(None),
+            skipped_same_language: Cell::new(false),
+            exit_state_flushed: Cell::new(false),
+            auto_close: RefCell::new(None),
+            user_interacted: Cell::new(false),
+            switched_from: Cell::new(None),
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn original_text(&self) -> Option<String> {
+        self.original_text.borrow().clone()
+    }
+
+    pub fn set_original_text(&self, text: Option<String>) {
+        *self.original_text.borrow_mut() = text;
+    }
+
+    pub fn api_key(&self) -> Option<String> {
+        self.api_key.borrow().clone()
+    }
+
+    pub fn set_api_key(&self, key: Option<String>) {
+        *self.api_key.borrow_mut() = key;
+    }
+
+    pub fn source_language(&self) -> Option<Language> {
+        self.source_language.get()
+    }
+
+    pub fn set_source_language(&self, language: Option<Language>) {
+        self.source_language.set(language);
+    }
+
+    pub fn target_language(&self) -> Option<Language> {
+        self.target_language.get()
+    }
+
+    pub fn set_target_language(&self, language: Option<Language>) {
+        self.target_language.set(language);
+    }
+
+    pub fn language_buttons(&self) -> Ref<'_, LanguageButtonsVec> {
+        self.language_buttons.borrow()
+    }
+
+    pub fn set_language_buttons(&self, buttons: LanguageButtonsVec) {
+        *self.language_buttons.borrow_mut() = buttons;
+    }
+
+    pub fn rate_limiter(&self) -> RateLimiter {
+        self.rate_limiter.clone()
+    }
+
+    pub fn multi_text(&self) -> Option<String> {
+        self.multi_text.borrow().clone()
+    }
+
+    pub fn set_multi_text(&self, text: Option<String>) {
+        *self.multi_text.borrow_mut() = text;
+    }
+
+    pub fn set_skipped_same_language(&self, skipped: bool) {
+        self.skipped_same_language.set(skipped);
+    }
+
+    // Whether the startup translation was skipped, clearing the flag
+    pub fn take_skipped_same_language(&self) -> bool {
+        self.skipped_same_language.replace(false)
+    }
+
+    // Language of the active button, None if no button is active (e.g. multi-target mode)
+    pub fn active_language(&self) -> Option<Language> {
+        self.language_buttons()
+            .iter()
+            .find(|(_, button)| button.borrow().is_active())
+            .map(|(lang, _)| *lang)
+    }
+
+    // Whether the exit state still has to be written, marking it as written
+    pub fn begin_exit_flush(&self) -> bool {
+        !self.exit_state_flushed.replace(true)
+    }
+
+    // How long after a translation the window closes, None if it stays open: auto_close_ms
+    // is unset or 0, the translation failed or the user already clicked in the window
+    pub fn auto_close_delay(&self, succeeded: bool) -> Option<Duration> {
+        let delay_ms = self.config.auto_close_ms.filter(|&ms| ms > 0)?;
+        (succeeded && !self.user_interacted.get()).then(|| Duration::from_millis(delay_ms))
+    }
+
+    // Keep the timer so a click can stop it, replacing a pending one
+    pub fn set_auto_close(&self, source: glib::SourceId) {
+        if let Some(previous) = self.auto_close.replace(Some(source)) {
+            previous.remove();
+        }
+    }
+
+    // Forget the timer once it fired; glib has removed its source already
+    pub fn clear_auto_close(&self) {
+        self.auto_close.take();
+    }
+
+    // The user clicked in the window: stop a pending auto-close and don't start new ones
+    pub fn cancel_auto_close(&self) {
+        self.user_interacted.set(true);
+        if let Some(source) = self.auto_close.take() {
+            source.remove();
+        }
+    }
+
+    // Note an automatic switch of the target from `previous` to `new`. Undo goes back to the
+    // language from before the first switch, so later ones keep it. Returns the language Undo
+    // restores, None if the switches led back to it.
+    pub fn record_auto_switch(&self, previous: Language, new: Language) -> Option<Language> {
+        let original = self.switched_from.get().unwrap_or(previous);
+        let restore = (original != new).then_some(original);
+        self.switched_from.set(restore);
+        restore
+    }
+
+    // The language Undo restores, forgetting it so the switch is undone only once
+    pub fn take_auto_switch(&self) -> Option<Language> {
+        self.switched_from.take()
+    }
+}
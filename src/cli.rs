@@ -0,0 +1,79 @@
+// Command-line handling for the binary, kept here so it can be tested without GTK
+
+// What the binary should do, from its command-line arguments
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Gui,           // Default: open the window
+    Silent,        // Translate the clipboard in place without a window
+    Version,       // Print the version and exit
+    Help,          // Print the usage summary and exit
+    ListLanguages, // Print the language codes accepted in the config and exit
+    SelfTest,      // Translate a fixed word to check the API key and model, then exit
+    ResetLanguage, // Forget the remembered target language and exit
+    Trigger,       // Make a running instance translate the clipboard again, or open the window
+}
+
+// Pick the command from the arguments (without the program name).
+// --help wins over --version, then --list-languages, --reset-language, --selftest, --silent
+// and --trigger. Other arguments are left to GTK.
+pub fn parse_args<I, S>(args: I) -> Command
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut command = Command::Gui;
+    for arg in args {
+        match arg.as_ref() {
+            "--help" | "-h" => return Command::Help,
+            "--version" | "-V" => command = Command::Version,
+            "--list-languages" if command != Command::Version => command = Command::ListLanguages,
+            "--reset-language"
+                if matches!(
+                    command,
+                    Command::Gui | Command::Silent | Command::SelfTest | Command::Trigger
+                ) =>
+            {
+                command = Command::ResetLanguage
+            }
+            "--selftest"
+                if matches!(command, Command::Gui | Command::Silent | Command::Trigger) =>
+            {
+                command = Command::SelfTest
+            }
+            "--silent" if matches!(command, Command::Gui | Command::Trigger) => {
+                command = Command::Silent
+            }
+            "--trigger" if command == Command::Gui => command = Command::Trigger,
+            _ => {}
+        }
+    }
+    command
+}
+
+// Output of --version, e.g. "translator 0.1.0"
+pub fn version_text() -> String {
+    format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+}
+
+// Output of --help
+pub fn help_text() -> String {
+    format!(
+        "{}\n\
+         Translates the clipboard text with an OpenAI-compatible API.\n\
+         \n\
+         Usage: {} [OPTIONS]\n\
+         \n\
+         Without options a window opens with the translation of the clipboard.\n\
+         \n\
+         Options:\n\
+         \x20 --silent          Translate the clipboard in place without a window\n\
+         \x20 --list-languages  Print the language codes for the config and exit\n\
+         \x20 --reset-language  Forget the remembered target language and exit\n\
+         \x20 --selftest        Check the API key and model with a test translation\n\
+         \x20 --trigger         Translate the clipboard in the running window, if any\n\
+         \x20 -V, --version     Print the version and exit\n\
+         \x20 -h, --help        Print this help and exit",
+        version_text(),
+        env!("CARGO_PKG_NAME")
+    )
+}
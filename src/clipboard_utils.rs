@@ -1,4 +1,5 @@
-use gtk::gdk;
+use gtk::prelude::*;
+use gtk::{gdk, gio, glib};
 
 pub struct ClipboardError {
     pub message: String,
@@ -24,10 +25,12 @@ impl std::fmt::Debug for ClipboardError {
 
 impl std::error::Error for ClipboardError {}
 
-pub async fn read_clipboard_text(clipboard: &gdk::Clipboard) -> Result<String, ClipboardError> {
-    let text_future = clipboard.read_text_future();
-    match text_future.await {
-        Ok(Some(text)) => Ok(text.to_string()),
+pub async fn read_clipboard_text(
+    clipboard: &gdk::Clipboard,
+    accept_html: bool,
+) -> Result<String, ClipboardError> {
+    match read_clipboard_contents(clipboard, accept_html).await {
+        Ok(Some(text)) => Ok(text),
         Ok(None) => Err(ClipboardError::from("Clipboard text is empty.".to_string())),
         Err(e) => Err(ClipboardError::from(format!(
             "Failed to read from clipboard: {}",
@@ -36,6 +39,234 @@ pub async fn read_clipboard_text(clipboard: &gdk::Clipboard) -> Result<String, C
     }
 }
 
+// Text on the clipboard, or None if there is none.
+// With `accept_html`, contents that are only available as HTML or whose plain text is empty
+// (e.g. copied from a browser on some compositors) are converted from HTML instead.
+pub async fn read_clipboard_contents(
+    clipboard: &gdk::Clipboard,
+    accept_html: bool,
+) -> Result<Option<String>, ClipboardError> {
+    let text = clipboard
+        .read_text_future()
+        .await
+        .map_err(|e| ClipboardError::from(e.to_string()))?
+        .map(|text| text.to_string());
+    if !accept_html || text.as_deref().is_some_and(|text| !text.trim().is_empty()) {
+        return Ok(text);
+    }
+    match read_clipboard_html(clipboard).await? {
+        Some(html_text) => {
+            println!("Clipboard has no plain text, using its HTML contents");
+            Ok(Some(html_text))
+        }
+        None => Ok(text),
+    }
+}
+
+// The clipboard's text/html contents converted to plain text, None if there are none
+async fn read_clipboard_html(clipboard: &gdk::Clipboard) -> Result<Option<String>, ClipboardError> {
+    if !clipboard.formats().contain_mime_type(HTML_MIME_TYPE) {
+        return Ok(None);
+    }
+    let (stream, _mime_type) = clipboard
+        .read_future(&[HTML_MIME_TYPE], glib::Priority::DEFAULT)
+        .await
+        .map_err(|e| ClipboardError::from(e.to_string()))?;
+    let buffer = gio::MemoryOutputStream::new_resizable();
+    buffer
+        .splice_future(
+            &stream,
+            gio::OutputStreamSpliceFlags::CLOSE_SOURCE | gio::OutputStreamSpliceFlags::CLOSE_TARGET,
+            glib::Priority::DEFAULT,
+        )
+        .await
+        .map_err(|e| ClipboardError::from(e.to_string()))?;
+
+    let text = html_to_text(&decode_html_bytes(&buffer.steal_as_bytes()));
+    Ok(Some(text).filter(|text| !text.trim().is_empty()))
+}
+
+const HTML_MIME_TYPE: &str = "text/html";
+
+// Mime type KDE's clipboard manager (and others following it) checks to keep passwords
+// out of its history
+const PASSWORD_HINT_MIME_TYPE: &str = "x-kde-passwordManagerHint";
+
+// Put text on the clipboard. With `private_mode` it is offered together with the password
+// hint, so clipboard managers that honor it don't record it in their history.
+pub fn write_clipboard_text(clipboard: &gdk::Clipboard, text: &str, private_mode: bool) {
+    if !private_mode {
+        clipboard.set_text(text);
+        return;
+    }
+    let provider = gdk::ContentProvider::new_union(&[
+        gdk::ContentProvider::for_value(&text.to_value()),
+        gdk::ContentProvider::for_bytes(
+            PASSWORD_HINT_MIME_TYPE,
+            &glib::Bytes::from_static(b"secret"),
+        ),
+    ]);
+    if let Err(e) = clipboard.set_content(Some(&provider)) {
+        eprintln!("Failed to set clipboard contents: {}", e);
+    }
+}
+
+// Some browsers offer text/html as UTF-16 (with a byte order mark), others as UTF-8
+fn decode_html_bytes(bytes: &[u8]) -> String {
+    match bytes {
+        [0xFF, 0xFE, rest @ ..] => {
+            let units: Vec<u16> = rest
+                .chunks_exact(2)
+                .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+// --- HTML to plain text ---
+
+// Tags that start and end a paragraph, so their text is set off by blank lines
+const PARAGRAPH_TAGS: &[&str] = &[
+    "article",
+    "blockquote",
+    "div",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "ul",
+];
+
+// Tags that start a new line
+const LINE_TAGS: &[&str] = &["br", "dd", "dt", "li", "tr"];
+
+// Tags whose contents are not text
+const SKIPPED_TAGS: &[&str] = &["head", "script", "style", "template"];
+
+// Decode a character reference (the part between '&' and ';'), None if unknown
+fn decode_entity(entity: &str) -> Option<char> {
+    match entity {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some(' '),
+        _ => {
+            let number = entity.strip_prefix('#')?;
+            let code = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+// Append text between tags, decoding character references. Line breaks in the source are
+// just whitespace in HTML.
+fn push_html_text(output: &mut String, text: &str) {
+    let text = text.replace(['\r', '\n'], " ");
+    let mut rest = text.as_str();
+    while let Some(start) = rest.find('&') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let decoded = after
+            .find(';')
+            .filter(|&end| end <= 10) // Longer isn't an entity we know
+            .and_then(|end| decode_entity(&after[..end]).map(|c| (c, end)));
+        match decoded {
+            Some((c, end)) => {
+                output.push(c);
+                rest = &after[end + 1..];
+            }
+            None => {
+                output.push('&');
+                rest = after;
+            }
+        }
+    }
+    output.push_str(rest);
+}
+
+// Convert HTML to plain text: tags are removed, paragraphs and line breaks kept,
+// whitespace collapsed as a browser would and character references decoded
+pub fn html_to_text(html: &str) -> String {
+    let mut output = String::new();
+    let mut skipping: Option<String> = None; // Inside <script> etc. until its closing tag
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        if skipping.is_none() {
+            push_html_text(&mut output, &rest[..start]);
+        }
+        let after = &rest[start + 1..];
+
+        // Comments may contain '>'
+        if let Some(comment) = after.strip_prefix("!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let Some(end) = after.find('>') else {
+            rest = after; // Not closed: not a tag after all
+            if skipping.is_none() {
+                output.push('<');
+            }
+            continue;
+        };
+        let tag = &after[..end];
+        rest = &after[end + 1..];
+
+        let closing = tag.starts_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase();
+
+        if let Some(skipped) = &skipping {
+            if closing && name == *skipped {
+                skipping = None;
+            }
+        } else if SKIPPED_TAGS.contains(&name.as_str()) {
+            if !closing && !tag.ends_with('/') {
+                skipping = Some(name);
+            }
+        } else if PARAGRAPH_TAGS.contains(&name.as_str()) {
+            output.push_str("\n\n");
+        } else if LINE_TAGS.contains(&name.as_str()) && !closing {
+            output.push('\n');
+        }
+    }
+    if skipping.is_none() {
+        push_html_text(&mut output, rest);
+    }
+
+    // Collapse whitespace within lines and keep at most one blank line between paragraphs
+    let mut lines: Vec<String> = Vec::new();
+    for line in output.split('\n') {
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        let previous_blank = lines.last().map_or(true, |last| last.is_empty());
+        if !line.is_empty() || !previous_blank {
+            lines.push(line);
+        }
+    }
+    while lines.last().is_some_and(|last| last.is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -53,6 +284,50 @@ mod tests {
         assert_eq!(format!("{:?}", error), "ClipboardError(Test error)");
     }
 
+    #[test]
+    fn test_html_to_text_strips_tags() {
+        assert_eq!(
+            html_to_text("<meta charset=\"utf-8\"><b>Hello</b>, <a href=\"x\">world</a>!"),
+            "Hello, world!"
+        );
+        // Paragraphs are separated by a blank line, list items and <br> start new lines
+        assert_eq!(
+            html_to_text(
+                "<p>First\n  paragraph</p><p>Second<br>line</p><ul><li>one</li><li>two</li></ul>"
+            ),
+            "First paragraph\n\nSecond\nline\n\none\ntwo"
+        );
+        // Scripts, styles and comments are dropped
+        assert_eq!(
+            html_to_text(
+                "<style>p { color: red; }</style><!-- a > b -->Text<script>if (a < b) {}</script>"
+            ),
+            "Text"
+        );
+    }
+
+    #[test]
+    fn test_html_to_text_decodes_entities() {
+        assert_eq!(
+            html_to_text(
+                "Fish &amp; chips &lt;3 &quot;caf&#233;&quot; &#x263A;&nbsp;&apos;ok&apos;"
+            ),
+            "Fish & chips <3 \"café\" ☺ 'ok'"
+        );
+        // Unknown entities and a lone ampersand are kept as they are
+        assert_eq!(html_to_text("AT&T &bogus; 1 < 2"), "AT&T &bogus; 1 < 2");
+    }
+
+    #[test]
+    fn test_decode_html_bytes() {
+        assert_eq!(decode_html_bytes("<b>é</b>".as_bytes()), "<b>é</b>");
+        let utf16: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain("<b>é</b>".encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        assert_eq!(decode_html_bytes(&utf16), "<b>é</b>");
+    }
+
     #[test]
     fn test_clipboard_error_trait() {
         let error = ClipboardError::from("Test error".to_string());
@@ -1,20 +1,96 @@
 // Use lingua::Language directly
 use lingua::{IsoCode639_1, Language};
 use serde::{Deserialize, Deserializer, Serialize, Serializer}; // Import necessary serde traits
+use std::collections::HashMap;
 use std::fs;
 use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::time::{SystemTime, UNIX_EPOCH}; // For timestamp in backup filename
 
-const CONFIG_DIR: &str = "translator";
+use crate::paths;
+use crate::ui_strings;
+
 const CONFIG_FILE: &str = "config.toml";
+// Config files looked for, in order of preference; config.toml is created if none exists
+const CONFIG_FILES: &[&str] = &[CONFIG_FILE, "config.json", "config.yaml", "config.yml"];
+// Per-machine overrides, read after the config file; only the fields it sets replace the
+// config file's values
+pub const LOCAL_CONFIG_FILE: &str = "config.local.toml";
+// Invalid config files are renamed to their file name, this suffix and the Unix timestamp of
+// the failed load, e.g. config.json.invalid_1700000000
+const BACKUP_SUFFIX: &str = ".invalid_";
+
+// Allowed range for font_scale
+pub const MIN_FONT_SCALE: f64 = 0.5;
+pub const MAX_FONT_SCALE: f64 = 3.0;
+
+// Accepted values for register
+pub const REGISTERS: &[&str] = &["formal", "informal", "neutral"];
+
+// Accepted values for mode
+pub const MODES: &[&str] = &["translate", "proofread"];
+
+// Accepted values for button_label_case
+pub const BUTTON_LABEL_CASES: &[&str] = &["upper", "lower", "title"];
 
 // --- Serde helper module for lingua::Language ---
-mod language_serde {
+// Shared with settings.rs so every file stores languages the same way
+pub(crate) mod language_serde {
     use super::*; // Import items from parent module (Language, etc.)
     use serde::de::Error; // Import serde error type
 
+    // Parse an ISO 639-1 code, falling back to a language name in any case (for backward
+    // compatibility)
+    pub fn parse_language(code: &str) -> Option<Language> {
+        let code = code.trim();
+        if let Ok(iso_code) = IsoCode639_1::from_str(&code.to_uppercase()) {
+            // Convert from IsoCode639_1 to Language
+            return Some(Language::from_iso_code_639_1(&iso_code));
+        }
+        Language::all()
+            .into_iter()
+            .find(|language| language.to_string().eq_ignore_ascii_case(code))
+    }
+
+    // Error message for a value that is neither a code nor a name, with the closest
+    // language name as a suggestion if there is one near enough
+    fn invalid_language(message: &str, code: &str) -> String {
+        match closest_language_name(code) {
+            Some(name) => format!("{}: {} (did you mean {}?)", message, code, name),
+            None => format!("{}: {}", message, code),
+        }
+    }
+
+    // Language name with the smallest edit distance to the input, ignoring case. Names more
+    // than a third of the input's length away (at least 2 edits) aren't suggested.
+    fn closest_language_name(input: &str) -> Option<String> {
+        let input = input.trim().to_lowercase();
+        let max_distance = (input.chars().count() / 3).max(2);
+        Language::all()
+            .into_iter()
+            .map(|language| language.to_string())
+            .map(|name| (levenshtein(&input, &name.to_lowercase()), name))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .min()
+            .map(|(_, name)| name)
+    }
+
+    // Number of single-character insertions, deletions and substitutions between a and b
+    fn levenshtein(a: &str, b: &str) -> usize {
+        let b: Vec<char> = b.chars().collect();
+        let mut previous: Vec<usize> = (0..=b.len()).collect();
+        for (i, a_char) in a.chars().enumerate() {
+            let mut current = vec![i + 1; b.len() + 1];
+            for (j, b_char) in b.iter().enumerate() {
+                let substitution = previous[j] + usize::from(a_char != *b_char);
+                current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+            }
+            previous = current;
+        }
+        previous[b.len()]
+    }
+
     // Serialize a single Language to its ISO code
     pub fn serialize<S>(lang: &Language, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -30,18 +106,31 @@ mod language_serde {
     where
         D: Deserializer<'de>,
     {
-        use lingua::IsoCode639_1;
         let code = String::deserialize(deserializer)?;
+        parse_language(&code).ok_or_else(|| {
+            D::Error::custom(invalid_language("invalid language code or name", &code))
+        })
+    }
 
-        // Try to parse as ISO code first
-        if let Ok(iso_code) = IsoCode639_1::from_str(&code.to_uppercase()) {
-            // Convert from IsoCode639_1 to Language
-            return Ok(Language::from_iso_code_639_1(&iso_code));
+    // --- Helpers for Option<Language> ---
+
+    // Serialize Option<Language> to an ISO code (use with skip_serializing_if = "Option::is_none")
+    pub fn serialize_option<S>(lang: &Option<Language>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match lang {
+            Some(lang) => serialize(lang, serializer),
+            None => serializer.serialize_none(),
         }
+    }
 
-        // If that fails, try to parse as language name (for backward compatibility)
-        Language::from_str(&code)
-            .map_err(|_| D::Error::custom(format!("invalid language code or name: {}", code)))
+    // Deserialize Option<Language> from an ISO code (use with #[serde(default)])
+    pub fn deserialize_option<'de, D>(deserializer: D) -> Result<Option<Language>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserialize(deserializer).map(Some)
     }
 
     // --- Helpers for Vec<Language> ---
@@ -67,41 +156,352 @@ mod language_serde {
     where
         D: Deserializer<'de>,
     {
-        use lingua::IsoCode639_1;
         let codes: Vec<String> = Vec::deserialize(deserializer)?;
         codes
             .into_iter()
             .map(|code| {
-                // Try to parse as ISO code first
-                if let Ok(iso_code) = IsoCode639_1::from_str(&code.to_uppercase()) {
-                    return Ok(Language::from_iso_code_639_1(&iso_code));
-                }
-
-                // If that fails, try to parse as language name (for backward compatibility)
-                Language::from_str(&code).map_err(|_| {
-                    D::Error::custom(format!("invalid language code or name in list: {}", code))
+                parse_language(&code).ok_or_else(|| {
+                    D::Error::custom(invalid_language(
+                        "invalid language code or name in list",
+                        &code,
+                    ))
                 })
             })
             .collect() // Collect results into Result<Vec<Language>, D::Error>
     }
 }
 
-// Derive Serialize, Deserialize, Debug, and Clone for the Config struct
-#[derive(Serialize, Deserialize, Debug, Clone)]
+// (ISO 639-1 code, name) of every language that can be used in the config, sorted by code.
+// Only codes that read back as the same language are listed, so each one is valid in
+// all_target_languages, primary_language etc.
+pub fn supported_language_codes() -> Vec<(String, String)> {
+    let mut languages: Vec<(String, String)> = Language::all()
+        .into_iter()
+        .filter_map(|language| {
+            let code = language.iso_code_639_1().to_string().to_uppercase();
+            (language_serde::parse_language(&code) == Some(language))
+                .then(|| (code, language.to_string()))
+        })
+        .collect();
+    languages.sort();
+    languages
+}
+
+// Derive Serialize, Deserialize, Debug, Clone and PartialEq for the Config struct
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Config {
     pub api_url: String,
     pub model_version: String,
+    // API key stored in the config; OPENROUTER_API_KEY and api_key_file take precedence
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    // File containing the API key; OPENROUTER_API_KEY takes precedence
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key_file: Option<PathBuf>,
     // Use lingua::Language with serde helpers
     #[serde(with = "language_serde")] // Use the helper module for single Language
     pub primary_language: Language,
     #[serde(with = "language_serde")] // Use the helper module for single Language
     pub secondary_language: Language,
-    // List of available target languages for the UI
+    // List of available target languages for the UI, in button order. Saving keeps this order:
+    // duplicates are dropped (first one kept) and a missing primary/secondary language is
+    // appended at the end, primary first, so saving again changes nothing.
     #[serde(default = "default_all_target_languages")] // Use default if missing in file
     #[serde(serialize_with = "language_serde::serialize_vec")] // Use specific vec serializer
     #[serde(deserialize_with = "language_serde::deserialize_vec")]
     // Use specific vec deserializer
     pub all_target_languages: Vec<Language>,
+    // Order the language buttons by how often each language was picked (counts in usage.toml)
+    #[serde(default)]
+    pub sort_buttons_by_usage: bool,
+    // Case of the ISO codes on the language buttons: "upper" (EN), "lower" (en) or "title" (En)
+    #[serde(default = "default_button_label_case")]
+    pub button_label_case: String,
+    // Detect the source language at startup (false = treat the source as unknown, or as
+    // forced_source_language if set)
+    #[serde(default = "default_detection_enabled")]
+    pub detection_enabled: bool,
+    // After detecting on the start of the text, detect again on the whole text in the
+    // background and switch the target if that changes it
+    #[serde(default)]
+    pub two_phase_detection: bool,
+    // Added to the primary language's detection confidence (0.0..=1.0), so near-ties on
+    // short text go to the primary language
+    #[serde(default)]
+    pub detection_primary_bias: f64,
+    // Most languages the detector is built with (0 = no limit); each one costs load time
+    // and memory, so beyond this only the primary, secondary and first others are kept
+    #[serde(default = "default_max_detection_languages")]
+    pub max_detection_languages: usize,
+    // Fall back to the clipboard's HTML contents (converted to plain text) when it has no plain text
+    #[serde(default)]
+    pub accept_html_clipboard: bool,
+    // When the clipboard holds only a link, fetch the page and translate its text instead
+    #[serde(default)]
+    pub translate_urls: bool,
+    // Pick the target language from the detected source at startup (false = always use the last one)
+    #[serde(default = "default_auto_select_target")]
+    pub auto_select_target: bool,
+    // Maximum number of API requests per minute (0 disables rate limiting)
+    #[serde(default = "default_requests_per_minute")]
+    pub requests_per_minute: u32,
+    // Show the translation in an editable text box instead of a read-only label
+    #[serde(default)]
+    pub editable_output: bool,
+    // Show the source text in an editable box and retranslate while typing
+    #[serde(default)]
+    pub live_preview: bool,
+    // Longer input is split into chunks on paragraph boundaries (0 disables chunking)
+    #[serde(default = "default_max_input_chars")]
+    pub max_input_chars: usize,
+    // Larger clipboard text is refused instead of being sent to the API (0 disables the check)
+    #[serde(default = "default_max_clipboard_bytes")]
+    pub max_clipboard_bytes: usize,
+    // Maximum length of the model's reply per request, in tokens
+    #[serde(default = "default_max_tokens")]
+    pub max_tokens: u16,
+    // Retry a truncated translation once with twice the max_tokens
+    #[serde(default)]
+    pub auto_extend_on_truncation: bool,
+    // Send the request again when the reply is empty or only whitespace, up to max_retries times
+    #[serde(default)]
+    pub retry_on_empty: bool,
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    // Size max_tokens from the input length instead of using the fixed value
+    #[serde(default)]
+    pub auto_max_tokens: bool,
+    // Upper limit for max_tokens sized by auto_max_tokens
+    #[serde(default = "default_max_tokens_ceiling")]
+    pub max_tokens_ceiling: u16,
+    // Time limit for the whole startup flow: clipboard, detection, translation (0 disables it)
+    #[serde(default = "default_startup_timeout_ms")]
+    pub startup_timeout_ms: u64,
+    // Join lines broken mid-paragraph (e.g. text copied from PDFs) before translating
+    #[serde(default)]
+    pub normalize_newlines: bool,
+    // Capitalize the translation's first letter when the original starts with a capital
+    #[serde(default)]
+    pub fix_capitalization: bool,
+    // Remove a lead-in like "Here is the translation:" that some models put before the result
+    #[serde(default)]
+    pub strip_preamble: bool,
+    // Show the prompt that would be sent instead of calling the API
+    #[serde(default)]
+    pub dry_run: bool,
+    // Grey out the button of the detected source language, translating into it is pointless
+    #[serde(default)]
+    pub exclude_source_button: bool,
+    // Grey out every language button but the one of pinned_target_language
+    #[serde(default)]
+    pub lock_pinned_target: bool,
+    // Language buttons toggle independently and every active language is shown in its own row
+    #[serde(default)]
+    pub multi_target: bool,
+    // Translate with each of these models and show the results in rows, for comparing models
+    // (empty = only model_version). Ignored with multi_target.
+    #[serde(default)]
+    pub compare_models: Vec<String>,
+    // Send a desktop notification when a translation finishes while the window is in the background
+    #[serde(default)]
+    pub notify_on_complete: bool,
+    // Close the window after copying the translation (false = "Copy" keeps it open)
+    #[serde(default = "default_close_after_copy")]
+    pub close_after_copy: bool,
+    // Keep the clipboard text in memory only: no text in logs or notifications, and copied
+    // text is marked as secret so clipboard managers leave it out of their history
+    #[serde(default)]
+    pub private_mode: bool,
+    // Keep a copy of a config file that fails to parse before replacing it with the defaults
+    #[serde(default = "default_backup_invalid_config")]
+    pub backup_invalid_config: bool,
+    // Number of invalid config backups to keep, oldest are deleted first (0 keeps all)
+    #[serde(default = "default_max_config_backups")]
+    pub max_config_backups: usize,
+    // Scale factor for label, button and text box fonts, e.g. 1.5 for larger text (0.5-3.0)
+    #[serde(default = "default_font_scale")]
+    pub font_scale: f64,
+    // Width limit of the translation in characters; longer lines wrap, so the window grows
+    // in height rather than width (0 = no limit)
+    #[serde(default = "default_label_max_width_chars")]
+    pub label_max_width_chars: u32,
+    // Form of address in translations: "formal" (Sie/vous), "informal" (du/tu) or "neutral"
+    #[serde(default = "default_register")]
+    pub register: String,
+    // "translate", or "proofread" to correct grammar and style in the text's own language
+    #[serde(default = "default_mode")]
+    pub mode: String,
+    // Show the model earlier translations from this run that share words with the text, so
+    // terms are translated consistently
+    #[serde(default)]
+    pub use_translation_memory: bool,
+    // Tell the model the detected source language, e.g. to tell apart similar languages
+    #[serde(default)]
+    pub hint_source_language: bool,
+    // Show a collapsible panel with the finish reason and token usage of the last response
+    #[serde(default)]
+    pub debug_panel: bool,
+    // Return fake translations without calling the API (no API key needed), for demos
+    #[serde(default)]
+    pub mock: bool,
+    // Ask the model for {"translation": "..."} JSON (response_format) instead of plain text
+    #[serde(default)]
+    pub json_response: bool,
+    // The model stops writing at any of these, e.g. "\n\nNote:" before an explanation it
+    // would append (empty = the parameter isn't sent)
+    #[serde(default)]
+    pub stop_sequences: Vec<String>,
+    // Complete system prompts for specific target languages, keyed by ISO code.
+    // "{target_language}" is replaced with the language name.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub per_language_prompt: HashMap<String, String>,
+    // Output limits of specific models, keyed by model_version; max_tokens is lowered to them
+    #[serde(default)]
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub model_max_tokens: HashMap<String, u16>,
+    // Model for specific language pairs, keyed by "SRC-DST" ISO codes, e.g. "ZH-EN"; other
+    // pairs and undetected sources use model_version
+    #[serde(default)]
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub model_for_pair: HashMap<String, String>,
+    // Replacements for the texts shown while waiting, keyed by ui_strings key
+    #[serde(default)]
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub ui_strings: HashMap<String, String>,
+    // Target used when the source language can't be detected or isn't configured (None = primary)
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "language_serde::serialize_option")]
+    #[serde(deserialize_with = "language_serde::deserialize_option")]
+    pub fallback_target_language: Option<Language>,
+    // Always translate into this language, overriding the automatic and remembered choice
+    // (None = choose automatically)
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "language_serde::serialize_option")]
+    #[serde(deserialize_with = "language_serde::deserialize_option")]
+    pub pinned_target_language: Option<Language>,
+    // Source language to assume instead of detecting it (None = detect)
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "language_serde::serialize_option")]
+    #[serde(deserialize_with = "language_serde::deserialize_option")]
+    pub forced_source_language: Option<Language>,
+    // Proxy for API requests, e.g. "http://proxy.example.com:8080" (None = use HTTPS_PROXY)
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy_url: Option<String>,
+    // User-Agent header for API requests (None = "translator/<version>")
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    // Field the texts are from, e.g. "medical", added to the system prompt (None = no hint)
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domain_hint: Option<String>,
+    // Text sent before and after the clipboard text as context only, e.g. the surrounding
+    // sentences of a fragment; the model translates just the clipboard text (None = no context)
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context_prefix: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context_suffix: Option<String>,
+    // OpenRouter provider routing preferences, sent as the request's "provider" object,
+    // e.g. { order = ["openai"], allow_fallbacks = false } (None = OpenRouter's default)
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider_routing: Option<serde_json::Value>,
+    // Close the window this many milliseconds after the startup translation is shown, unless
+    // the user clicks in it first (None = stay open)
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_close_ms: Option<u64>,
+    // Sent as the request's seed so repeated requests give the same reply where the model
+    // supports it, e.g. to compare prompt changes (None = not sent)
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    // Longest text sent for translation, in characters; longer text is cut at a word boundary
+    // and the translation marked as truncated (None or 0 = no limit)
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub translation_max_chars: Option<usize>,
+}
+
+fn default_backup_invalid_config() -> bool {
+    true
+}
+
+fn default_max_config_backups() -> usize {
+    5
+}
+
+fn default_max_retries() -> u32 {
+    1
+}
+
+fn default_font_scale() -> f64 {
+    1.0
+}
+
+fn default_label_max_width_chars() -> u32 {
+    60
+}
+
+fn default_register() -> String {
+    "neutral".to_string()
+}
+
+fn default_button_label_case() -> String {
+    "upper".to_string()
+}
+
+fn default_mode() -> String {
+    "translate".to_string()
+}
+
+fn default_max_detection_languages() -> usize {
+    15
+}
+
+fn default_detection_enabled() -> bool {
+    true
+}
+
+fn default_close_after_copy() -> bool {
+    true
+}
+
+fn default_auto_select_target() -> bool {
+    true
+}
+
+fn default_requests_per_minute() -> u32 {
+    20
+}
+
+fn default_max_input_chars() -> usize {
+    8000
+}
+
+fn default_max_clipboard_bytes() -> usize {
+    1_000_000
+}
+
+fn default_max_tokens() -> u16 {
+    1024
+}
+
+fn default_max_tokens_ceiling() -> u16 {
+    4096
+}
+
+fn default_startup_timeout_ms() -> u64 {
+    60000
 }
 
 // Function to provide default value for all_target_languages
@@ -125,27 +525,812 @@ impl Default for Config {
         Config {
             api_url: "https://openrouter.ai/api/v1".to_string(),
             model_version: "openai/gpt-4o".to_string(),
+            api_key: None,
+            api_key_file: None,
             primary_language: primary,
             secondary_language: secondary,
             all_target_languages: default_all_target_languages(),
+            sort_buttons_by_usage: false,
+            button_label_case: default_button_label_case(),
+            detection_enabled: default_detection_enabled(),
+            two_phase_detection: false,
+            detection_primary_bias: 0.0,
+            max_detection_languages: default_max_detection_languages(),
+            accept_html_clipboard: false,
+            translate_urls: false,
+            auto_select_target: default_auto_select_target(),
+            requests_per_minute: default_requests_per_minute(),
+            editable_output: false,
+            live_preview: false,
+            max_input_chars: default_max_input_chars(),
+            max_clipboard_bytes: default_max_clipboard_bytes(),
+            max_tokens: default_max_tokens(),
+            auto_extend_on_truncation: false,
+            retry_on_empty: false,
+            max_retries: default_max_retries(),
+            auto_max_tokens: false,
+            max_tokens_ceiling: default_max_tokens_ceiling(),
+            startup_timeout_ms: default_startup_timeout_ms(),
+            normalize_newlines: false,
+            fix_capitalization: false,
+            strip_preamble: false,
+            dry_run: false,
+            exclude_source_button: false,
+            lock_pinned_target: false,
+            multi_target: false,
+            compare_models: Vec::new(),
+            notify_on_complete: false,
+            close_after_copy: default_close_after_copy(),
+            private_mode: false,
+            backup_invalid_config: default_backup_invalid_config(),
+            max_config_backups: default_max_config_backups(),
+            font_scale: default_font_scale(),
+            label_max_width_chars: default_label_max_width_chars(),
+            register: default_register(),
+            mode: default_mode(),
+            use_translation_memory: false,
+            hint_source_language: false,
+            debug_panel: false,
+            mock: false,
+            json_response: false,
+            stop_sequences: Vec::new(),
+            per_language_prompt: HashMap::new(),
+            model_max_tokens: HashMap::new(),
+            model_for_pair: HashMap::new(),
+            ui_strings: HashMap::new(),
+            fallback_target_language: None,
+            pinned_target_language: None,
+            forced_source_language: None,
+            proxy_url: None,
+            user_agent: None,
+            domain_hint: None,
+            context_prefix: None,
+            context_suffix: None,
+            provider_routing: None,
+            auto_close_ms: None,
+            seed: None,
+            translation_max_chars: None,
+        }
+    }
+}
+
+// --- Example config for first runs ---
+
+// One-line descriptions written above each field of the example config
+const FIELD_DESCRIPTIONS: &[(&str, &str)] = &[
+    ("api_url", "OpenAI-compatible API endpoint"),
+    ("model_version", "Model used for translations"),
+    ("primary_language", "Your main language (ISO 639-1 code)"),
+    ("secondary_language", "Your second most used language"),
+    (
+        "all_target_languages",
+        "Languages shown as buttons in the window",
+    ),
+    (
+        "sort_buttons_by_usage",
+        "Order the language buttons by how often you picked them",
+    ),
+    (
+        "button_label_case",
+        "Case of the language codes on the buttons: \"upper\", \"lower\" or \"title\"",
+    ),
+    ("detection_enabled", "Detect the source language at startup"),
+    (
+        "two_phase_detection",
+        "Detect again on the whole text and switch the target if that changes it",
+    ),
+    (
+        "detection_primary_bias",
+        "Added to the primary language's detection confidence to settle near-ties (0.0-1.0)",
+    ),
+    (
+        "max_detection_languages",
+        "Most languages the detector is built with, to limit load time and memory (0 = no limit)",
+    ),
+    (
+        "accept_html_clipboard",
+        "Use the clipboard's HTML contents when it has no plain text",
+    ),
+    (
+        "translate_urls",
+        "Translate the text of the page when the clipboard holds only a link",
+    ),
+    (
+        "auto_select_target",
+        "Choose the target language from the detected source language at startup",
+    ),
+    (
+        "requests_per_minute",
+        "Maximum API requests per minute (0 disables the limit)",
+    ),
+    (
+        "editable_output",
+        "Show the translation in an editable text box",
+    ),
+    (
+        "live_preview",
+        "Show the source text in an editable box and retranslate while typing",
+    ),
+    (
+        "max_input_chars",
+        "Longer text is translated in parts (0 disables splitting)",
+    ),
+    (
+        "max_clipboard_bytes",
+        "Larger clipboard text is not translated (0 disables the check)",
+    ),
+    ("max_tokens", "Maximum length of each reply in tokens"),
+    (
+        "auto_extend_on_truncation",
+        "Retry a truncated translation once with twice the max_tokens",
+    ),
+    (
+        "retry_on_empty",
+        "Send the request again when the reply is empty or only whitespace",
+    ),
+    (
+        "max_retries",
+        "How many times retry_on_empty sends the request again",
+    ),
+    (
+        "auto_max_tokens",
+        "Size max_tokens from the length of the text instead of using the fixed value",
+    ),
+    (
+        "max_tokens_ceiling",
+        "Upper limit for max_tokens when auto_max_tokens is on",
+    ),
+    (
+        "startup_timeout_ms",
+        "Time limit for reading, detecting and translating at startup (0 disables it)",
+    ),
+    (
+        "normalize_newlines",
+        "Join lines broken in the middle of a paragraph before translating",
+    ),
+    (
+        "fix_capitalization",
+        "Capitalize the translation when the original starts with a capital letter",
+    ),
+    (
+        "strip_preamble",
+        "Remove a lead-in like \"Here is the translation:\" before the translation",
+    ),
+    (
+        "dry_run",
+        "Show the prompt that would be sent instead of translating",
+    ),
+    (
+        "exclude_source_button",
+        "Grey out the button of the detected source language",
+    ),
+    (
+        "lock_pinned_target",
+        "Grey out every language button but the one of pinned_target_language",
+    ),
+    (
+        "multi_target",
+        "Let several language buttons be active and show a translation for each",
+    ),
+    (
+        "compare_models",
+        "Translate with each of these models and show the results side by side",
+    ),
+    (
+        "notify_on_complete",
+        "Notify when a translation finishes while the window is in the background",
+    ),
+    (
+        "close_after_copy",
+        "Close the window after copying the translation",
+    ),
+    (
+        "private_mode",
+        "Never write the clipboard text to logs, notifications or clipboard history",
+    ),
+    (
+        "backup_invalid_config",
+        "Keep a copy of a config file that fails to parse before replacing it",
+    ),
+    (
+        "max_config_backups",
+        "Number of invalid config backups to keep (0 keeps all)",
+    ),
+    ("font_scale", "Scale factor for the text size (0.5 to 3.0)"),
+    (
+        "label_max_width_chars",
+        "Wrap the translation at this many characters (0 = no limit)",
+    ),
+    (
+        "register",
+        "Form of address: \"formal\", \"informal\" or \"neutral\"",
+    ),
+    (
+        "mode",
+        "\"translate\", or \"proofread\" to correct the text in its own language",
+    ),
+    (
+        "use_translation_memory",
+        "Show the model related earlier translations from this session to keep terms consistent",
+    ),
+    (
+        "hint_source_language",
+        "Tell the model the detected language of the text",
+    ),
+    (
+        "debug_panel",
+        "Show the finish reason and token usage of the last response",
+    ),
+    ("mock", "Return fake translations without calling the API"),
+    (
+        "json_response",
+        "Ask the model for JSON output and read its 'translation' field",
+    ),
+    (
+        "stop_sequences",
+        "Texts at which the model stops, e.g. \"\\n\\nNote:\" (empty = none)",
+    ),
+];
+
+// Fields without a default value, written commented out: (name, example value, description)
+const OPTIONAL_FIELD_EXAMPLES: &[(&str, &str, &str)] = &[
+    (
+        "api_key_file",
+        "\"/path/to/api_key\"",
+        "File containing the API key, used when OPENROUTER_API_KEY is not set",
+    ),
+    (
+        "api_key",
+        "\"your_api_key_here\"",
+        "The API key itself, used when neither OPENROUTER_API_KEY nor api_key_file is set",
+    ),
+    (
+        "fallback_target_language",
+        "\"EN\"",
+        "Target for undetected or unconfigured source languages (default: primary_language)",
+    ),
+    (
+        "pinned_target_language",
+        "\"PL\"",
+        "Always translate into this language, whatever the source language",
+    ),
+    (
+        "forced_source_language",
+        "\"DE\"",
+        "Treat the clipboard text as this language instead of detecting it",
+    ),
+    (
+        "proxy_url",
+        "\"http://proxy.example.com:8080\"",
+        "Proxy for API requests (default: the HTTPS_PROXY environment variable)",
+    ),
+    (
+        "user_agent",
+        "\"translator (my-gateway-tag)\"",
+        "User-Agent header for API requests (default: translator/<version>)",
+    ),
+    (
+        "domain_hint",
+        "\"software\"",
+        "Field the texts are from, so the model picks the right terminology",
+    ),
+    (
+        "context_prefix",
+        "\"The previous paragraph\"",
+        "Context sent before the clipboard text; only the clipboard text is translated",
+    ),
+    (
+        "context_suffix",
+        "\"The next paragraph\"",
+        "Context sent after the clipboard text; only the clipboard text is translated",
+    ),
+    (
+        "provider_routing",
+        "{ order = [\"openai\", \"azure\"], allow_fallbacks = false }",
+        "OpenRouter provider preferences, sent as the request's \"provider\" object",
+    ),
+    (
+        "auto_close_ms",
+        "5000",
+        "Close the window this long after the translation is shown, unless you click in it",
+    ),
+    (
+        "seed",
+        "42",
+        "Ask for reproducible replies, for models that support it",
+    ),
+    (
+        "translation_max_chars",
+        "2000",
+        "Translate only the first 2000 characters of longer text",
+    ),
+];
+
+// --- Configuration Validation ---
+
+// Issues found in a Config. Returned by Config::validate so callers decide how to surface them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigWarning {
+    EmptyTargets,
+    PrimaryNotInTargets(Language),
+    SecondaryNotInTargets(Language),
+    PinnedNotInTargets(Language),
+    InvalidApiUrl(String),
+    EmptyModelVersion,
+    SameLanguages(Language),
+    UnknownRegister(String),
+    UnknownMode(String),
+    UnknownButtonLabelCase(String),
+    UnknownUiString(String),
+    ProviderRoutingNotObject,
+}
+
+impl std::fmt::Display for ConfigWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigWarning::EmptyTargets => write!(f, "'all_target_languages' is empty"),
+            ConfigWarning::PrimaryNotInTargets(lang) => write!(
+                f,
+                "Primary language '{:?}' is not in 'all_target_languages'",
+                lang
+            ),
+            ConfigWarning::SecondaryNotInTargets(lang) => write!(
+                f,
+                "Secondary language '{:?}' is not in 'all_target_languages'",
+                lang
+            ),
+            ConfigWarning::PinnedNotInTargets(lang) => write!(
+                f,
+                "Pinned target language '{:?}' is not in 'all_target_languages', it has no button",
+                lang
+            ),
+            ConfigWarning::InvalidApiUrl(url) => write!(
+                f,
+                "API URL '{}' is not a valid http:// or https:// URL",
+                url
+            ),
+            ConfigWarning::EmptyModelVersion => write!(f, "'model_version' is empty"),
+            ConfigWarning::SameLanguages(lang) => {
+                write!(f, "Primary and secondary language are both '{:?}'", lang)
+            }
+            ConfigWarning::UnknownRegister(register) => write!(
+                f,
+                "'register' '{}' is not one of {:?}, using neutral",
+                register, REGISTERS
+            ),
+            ConfigWarning::UnknownMode(mode) => write!(
+                f,
+                "'mode' '{}' is not one of {:?}, translating",
+                mode, MODES
+            ),
+            ConfigWarning::UnknownButtonLabelCase(case) => write!(
+                f,
+                "'button_label_case' '{}' is not one of {:?}, using upper",
+                case, BUTTON_LABEL_CASES
+            ),
+            ConfigWarning::UnknownUiString(key) => {
+                write!(f, "'ui_strings' has an unknown key '{}', ignoring it", key)
+            }
+            ConfigWarning::ProviderRoutingNotObject => write!(
+                f,
+                "'provider_routing' is not a table (JSON object), not sending it"
+            ),
+        }
+    }
+}
+
+impl Config {
+    // Commented config.toml with every field, its default value and a description.
+    // Parses to Config::default(); optional fields are included as comments.
+    pub fn example_toml() -> String {
+        let defaults = toml::to_string_pretty(&Config::default())
+            .expect("Default config should serialize to TOML");
+
+        let mut example = String::from(
+            "# Clipboard Translator configuration\n\
+             # Every setting is listed with its default value.\n\n",
+        );
+        for line in defaults.lines() {
+            let field = line.split_once(" = ").map(|(field, _)| field);
+            let description = FIELD_DESCRIPTIONS
+                .iter()
+                .find(|(name, _)| Some(*name) == field)
+                .map(|(_, description)| description);
+            if let Some(description) = description {
+                example.push_str(&format!("# {}\n", description));
+            }
+            example.push_str(line);
+            example.push('\n');
+        }
+
+        example.push_str("\n# Optional settings, remove the leading '#' to use them\n");
+        for (name, value, description) in OPTIONAL_FIELD_EXAMPLES {
+            example.push_str(&format!("\n# {}\n# {} = {}\n", description, name, value));
+        }
+        example.push_str(
+            "\n# Custom system prompts per target language, keyed by ISO code.\n\
+             # {target_language} is replaced with the language name.\n\
+             # [per_language_prompt]\n\
+             # DE = \"Translate the text into formal {target_language}. Reply with the translation only.\"\n",
+        );
+        example.push_str(
+            "\n# Output limits of specific models, keyed by model_version.\n\
+             # max_tokens is lowered to the limit when that model is used.\n\
+             # [model_max_tokens]\n\
+             # \"openai/gpt-3.5-turbo\" = 4096\n",
+        );
+        example.push_str(
+            "\n# Model for specific language pairs, keyed by source and target ISO code.\n\
+             # Other pairs, and texts whose language isn't detected, use model_version.\n\
+             # [model_for_pair]\n\
+             # ZH-EN = \"openai/gpt-4o\"\n",
+        );
+        example.push_str(
+            "\n# Replacements for the texts shown while waiting, e.g. in your own language.\n\
+             # Keys: reading_clipboard, translating, translating_in_parts, translating_multiple,\n\
+             # rate_limited. {language}, {max_chars} and {count} are filled in.\n\
+             # [ui_strings]\n\
+             # translating = \"Übersetze ins {language}...\"\n",
+        );
+        example
+    }
+
+    // Text shown in the window for a ui_strings key, with its placeholders filled in
+    pub fn ui_text(&self, key: &str, values: &[(&str, String)]) -> String {
+        ui_strings::fill(ui_strings::resolve(&self.ui_strings, key), values)
+    }
+
+    // Whether mode is "proofread" (any case): the text is corrected, not translated.
+    // Unknown modes translate.
+    pub fn proofread(&self) -> bool {
+        self.mode.trim().eq_ignore_ascii_case("proofread")
+    }
+
+    // Target language for sources that couldn't be detected or aren't configured
+    pub fn fallback_language(&self) -> Language {
+        self.fallback_target_language
+            .unwrap_or(self.primary_language)
+    }
+
+    // Remove repeated entries from all_target_languages, keeping the first occurrence.
+    // Returns the number of duplicates removed.
+    pub fn dedup_target_languages(&mut self) -> usize {
+        let original_len = self.all_target_languages.len();
+        let mut seen = Vec::with_capacity(original_len);
+        self.all_target_languages.retain(|lang| {
+            if seen.contains(lang) {
+                false
+            } else {
+                seen.push(*lang);
+                true
+            }
+        });
+        original_len - self.all_target_languages.len()
+    }
+
+    // Append the primary and then the secondary language to all_target_languages if they
+    // are missing. Languages already listed keep their positions, so the button order only
+    // changes by the appended ones. Returns the languages added.
+    pub fn add_missing_primary_secondary(&mut self) -> Vec<Language> {
+        let mut added = Vec::new();
+        for lang in [self.primary_language, self.secondary_language] {
+            if !self.all_target_languages.contains(&lang) {
+                self.all_target_languages.push(lang);
+                added.push(lang);
+            }
+        }
+        added
+    }
+
+    // Give the secondary language a different value if it equals the primary one,
+    // otherwise the secondary fallback in target selection is a no-op.
+    // Uses the first target language that differs from primary, else English.
+    // Returns true if the secondary language was changed.
+    pub fn ensure_distinct_secondary(&mut self) -> bool {
+        if self.primary_language != self.secondary_language {
+            return false;
+        }
+        let primary = self.primary_language;
+        self.secondary_language = self
+            .all_target_languages
+            .iter()
+            .copied()
+            .chain([Language::English, Language::French]) // French if primary is English
+            .find(|lang| *lang != primary)
+            .unwrap_or(Language::English);
+        true
+    }
+
+    // Keep font_scale within MIN_FONT_SCALE..=MAX_FONT_SCALE so the window stays usable.
+    // A non-finite value falls back to the default. Returns true if the value was changed.
+    pub fn clamp_font_scale(&mut self) -> bool {
+        let original = self.font_scale;
+        self.font_scale = if original.is_finite() {
+            original.clamp(MIN_FONT_SCALE, MAX_FONT_SCALE)
+        } else {
+            default_font_scale()
+        };
+        self.font_scale != original
+    }
+
+    // Check the config for problems without modifying it
+    pub fn validate(&self) -> Vec<ConfigWarning> {
+        let mut warnings = Vec::new();
+
+        if self.all_target_languages.is_empty() {
+            warnings.push(ConfigWarning::EmptyTargets);
+        } else {
+            // Membership checks are only meaningful when there is a list to check against
+            if !self.all_target_languages.contains(&self.primary_language) {
+                warnings.push(ConfigWarning::PrimaryNotInTargets(self.primary_language));
+            }
+            if !self.all_target_languages.contains(&self.secondary_language) {
+                warnings.push(ConfigWarning::SecondaryNotInTargets(
+                    self.secondary_language,
+                ));
+            }
+            if let Some(pinned) = self
+                .pinned_target_language
+                .filter(|pinned| !self.all_target_languages.contains(pinned))
+            {
+                warnings.push(ConfigWarning::PinnedNotInTargets(pinned));
+            }
+        }
+
+        let url = self.api_url.trim();
+        let has_host = url
+            .strip_prefix("https://")
+            .or_else(|| url.strip_prefix("http://"))
+            .map(|rest| !rest.is_empty())
+            .unwrap_or(false);
+        if !has_host {
+            warnings.push(ConfigWarning::InvalidApiUrl(self.api_url.clone()));
+        }
+
+        if self.model_version.trim().is_empty() {
+            warnings.push(ConfigWarning::EmptyModelVersion);
+        }
+
+        if self.primary_language == self.secondary_language {
+            warnings.push(ConfigWarning::SameLanguages(self.primary_language));
+        }
+
+        if !REGISTERS
+            .iter()
+            .any(|register| self.register.trim().eq_ignore_ascii_case(register))
+        {
+            warnings.push(ConfigWarning::UnknownRegister(self.register.clone()));
+        }
+
+        if !MODES
+            .iter()
+            .any(|mode| self.mode.trim().eq_ignore_ascii_case(mode))
+        {
+            warnings.push(ConfigWarning::UnknownMode(self.mode.clone()));
+        }
+
+        if !BUTTON_LABEL_CASES
+            .iter()
+            .any(|case| self.button_label_case.trim().eq_ignore_ascii_case(case))
+        {
+            warnings.push(ConfigWarning::UnknownButtonLabelCase(
+                self.button_label_case.clone(),
+            ));
+        }
+
+        // Sorted so the warnings come out in the same order every time
+        let mut unknown_keys: Vec<&String> = self
+            .ui_strings
+            .keys()
+            .filter(|key| !ui_strings::is_known(key))
+            .collect();
+        unknown_keys.sort();
+        for key in unknown_keys {
+            warnings.push(ConfigWarning::UnknownUiString(key.clone()));
+        }
+
+        if self
+            .provider_routing
+            .as_ref()
+            .is_some_and(|routing| !routing.is_object())
+        {
+            warnings.push(ConfigWarning::ProviderRoutingNotObject);
+        }
+
+        warnings
+    }
+}
+
+// --- Backups of invalid config files ---
+
+// Backup settings for a config file that failed to parse
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackupPolicy {
+    pub enabled: bool,
+    pub max_backups: usize, // 0 keeps all
+}
+
+impl BackupPolicy {
+    // Read the backup fields from the invalid file, in its format, if they are readable on
+    // their own, e.g. when only another field has a wrong type. Otherwise use the defaults.
+    pub fn from_invalid_config(format: ConfigFormat, contents: &str) -> Self {
+        let value = format.parse_value(contents).ok();
+        let field = |name: &str| value.as_ref().and_then(|value| value.get(name));
+        BackupPolicy {
+            enabled: field("backup_invalid_config")
+                .and_then(toml::Value::as_bool)
+                .unwrap_or_else(default_backup_invalid_config),
+            max_backups: field("max_config_backups")
+                .and_then(toml::Value::as_integer)
+                .and_then(|max| usize::try_from(max).ok())
+                .unwrap_or_else(default_max_config_backups),
+        }
+    }
+}
+
+// Name of the backup of the invalid config file `path`
+pub fn backup_file_name(path: &Path, timestamp: u64) -> String {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    format!("{}{}{}", file_name, BACKUP_SUFFIX, timestamp)
+}
+
+// Delete all but the `keep` newest invalid config backups in `dir` (0 keeps all), those of
+// every config file format together. Returns the number of backups deleted.
+pub fn prune_config_backups(dir: &Path, keep: usize) -> Result<usize, std::io::Error> {
+    if keep == 0 {
+        return Ok(0);
+    }
+    let mut backups: Vec<(u64, PathBuf)> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let (file_name, timestamp) = name.split_once(BACKUP_SUFFIX)?;
+            if !CONFIG_FILES.contains(&file_name) {
+                return None;
+            }
+            let timestamp = timestamp.parse::<u64>().ok()?;
+            Some((timestamp, entry.path()))
+        })
+        .collect();
+    backups.sort_by(|a, b| b.cmp(a)); // Newest first
+
+    let mut removed = 0;
+    for (_, backup_path) in backups.into_iter().skip(keep) {
+        fs::remove_file(&backup_path)?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+// --- Config file formats ---
+
+// Format of a config file, chosen by its extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+    Yaml,
+}
+
+impl ConfigFormat {
+    // Format for the file's extension (.toml, .json, .yaml or .yml), None for others
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "toml" => Some(ConfigFormat::Toml),
+            "json" => Some(ConfigFormat::Json),
+            "yaml" | "yml" => Some(ConfigFormat::Yaml),
+            _ => None,
+        }
+    }
+
+    pub fn parse(self, contents: &str) -> Result<Config, String> {
+        match self {
+            ConfigFormat::Toml => toml::from_str(contents).map_err(|e| e.to_string()),
+            ConfigFormat::Json => serde_json::from_str(contents).map_err(|e| e.to_string()),
+            ConfigFormat::Yaml => serde_yaml::from_str(contents).map_err(|e| e.to_string()),
+        }
+    }
+
+    // The file's settings as a TOML value, before deserializing into a Config
+    pub fn parse_value(self, contents: &str) -> Result<toml::Value, String> {
+        match self {
+            ConfigFormat::Toml => toml::from_str(contents).map_err(|e| e.to_string()),
+            ConfigFormat::Json => serde_json::from_str(contents).map_err(|e| e.to_string()),
+            ConfigFormat::Yaml => serde_yaml::from_str(contents).map_err(|e| e.to_string()),
+        }
+    }
+
+    pub fn serialize(self, config: &Config) -> Result<String, String> {
+        match self {
+            ConfigFormat::Toml => toml::to_string_pretty(config).map_err(|e| e.to_string()),
+            ConfigFormat::Json => serde_json::to_string_pretty(config)
+                .map(|json| json + "\n")
+                .map_err(|e| e.to_string()),
+            ConfigFormat::Yaml => serde_yaml::to_string(config).map_err(|e| e.to_string()),
         }
     }
 }
 
 // --- Configuration Loading and Saving ---
 
-fn get_config_path() -> Option<PathBuf> {
-    // Check XDG_CONFIG_HOME first, then fall back to dirs::config_dir()
-    let config_dir = if let Ok(xdg_config) = std::env::var("XDG_CONFIG_HOME") {
-        PathBuf::from(xdg_config)
-    } else {
-        dirs::config_dir()?
+// The first existing file of CONFIG_FILES, or config.toml if there is none yet
+pub fn get_config_path() -> Option<PathBuf> {
+    let dir = paths::app_config_dir()?;
+    let existing = CONFIG_FILES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.exists());
+    Some(existing.unwrap_or_else(|| dir.join(CONFIG_FILE)))
+}
+
+// config.local.toml next to the config file
+pub fn get_local_config_path() -> Option<PathBuf> {
+    Some(paths::app_config_dir()?.join(LOCAL_CONFIG_FILE))
+}
+
+// Merge `overrides` into `base`: tables are merged key by key, any other value replaces the
+// base value
+pub fn merge_toml_values(base: &mut toml::Value, overrides: toml::Value) {
+    match (base, overrides) {
+        (toml::Value::Table(base_table), toml::Value::Table(override_table)) => {
+            for (key, value) in override_table {
+                match base_table.get_mut(&key) {
+                    Some(base_value) => merge_toml_values(base_value, value),
+                    None => {
+                        base_table.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+// Parse the config file `contents` with the settings of config.local.toml `local_contents`
+// laid over it
+pub fn parse_with_local_overrides(
+    format: ConfigFormat,
+    contents: &str,
+    local_contents: &str,
+) -> Result<Config, String> {
+    let mut value = format.parse_value(contents)?;
+    let overrides: toml::Value = toml::from_str(local_contents).map_err(|e| e.to_string())?;
+    merge_toml_values(&mut value, overrides);
+    value.try_into().map_err(|e: toml::de::Error| e.to_string())
+}
+
+// --- Helper function to apply config.local.toml to a successfully loaded config ---
+// A missing local file leaves the config as is; an invalid one is reported and ignored, so a
+// typo there doesn't replace the whole config with defaults
+fn apply_local_overrides(config: Config, format: ConfigFormat, contents: &str) -> Config {
+    let Some(local_path) = get_local_config_path().filter(|path| path.exists()) else {
+        return config;
     };
+    let merged = fs::read_to_string(&local_path)
+        .map_err(|e| e.to_string())
+        .and_then(|local_contents| parse_with_local_overrides(format, contents, &local_contents));
+    match merged {
+        Ok(merged) => {
+            println!("Applied overrides from {:?}", local_path);
+            merged
+        }
+        Err(e) => {
+            eprintln!(
+                "Failed to apply {:?}: {}. Using the config file alone.",
+                local_path, e
+            );
+            config
+        }
+    }
+}
 
-    let mut path = config_dir;
-    path.push(CONFIG_DIR);
-    path.push(CONFIG_FILE);
-    Some(path)
+// file:// URI of the config file, e.g. for opening it in the user's editor
+pub fn config_file_uri() -> Option<String> {
+    let path = get_config_path()?;
+    reqwest::Url::from_file_path(&path)
+        .map(|uri| uri.to_string())
+        .ok()
 }
 
 pub fn load_config() -> Config {
@@ -156,13 +1341,12 @@ pub fn load_config() -> Config {
                     "Config file not found at {:?}. Creating with defaults.",
                     path
                 );
-                let default_config = Config::default();
-                // Attempt to save the default config immediately
-                if let Err(e) = save_config(&default_config) {
+                // Write the commented example so every setting can be discovered
+                if let Err(e) = save_example_config() {
                     eprintln!("Failed to save default config: {}", e);
                     // Continue with default config even if saving failed initially
                 }
-                return default_config;
+                return Config::default();
             }
 
             match fs::File::open(&path) {
@@ -176,30 +1360,49 @@ pub fn load_config() -> Config {
                         return Config::default(); // Return default on read error
                     }
 
-                    // Attempt to parse.
-                    match toml::from_str::<Config>(&contents) {
-                        Ok(mut config) => {
+                    // Attempt to parse, in the format of the file's extension
+                    let format = ConfigFormat::from_path(&path).unwrap_or(ConfigFormat::Toml);
+                    match format.parse(&contents) {
+                        Ok(config) => {
                             println!("Successfully loaded config from {:?}", path); // Log success
-                                                                                    // Ensure all_target_languages is not empty, use default if it is
-                                                                                    // (Should be handled by serde(default), but as a fallback)
+                            let mut config = apply_local_overrides(config, format, &contents);
+
+                            // Duplicates would create identical buttons that fight each other
+                            let duplicates = config.dedup_target_languages();
+                            if duplicates > 0 {
+                                println!(
+                                    "Removed {} duplicate language(s) from 'all_target_languages'.",
+                                    duplicates
+                                );
+                            }
+
+                            // Surface any validation issues found in the file
+                            for warning in config.validate() {
+                                eprintln!("Warning: {}", warning);
+                            }
+
+                            // Ensure all_target_languages is not empty, use default if it is
+                            // (Should be handled by serde(default), but as a fallback)
                             if config.all_target_languages.is_empty() {
-                                println!("Warning: 'all_target_languages' was empty in config file, using default list.");
+                                println!("Using default 'all_target_languages' list.");
                                 config.all_target_languages = default_all_target_languages();
                             }
-                            // Ensure primary/secondary languages are actually in the list
-                            // (Optional validation, could also just let it be)
-                            if !config
-                                .all_target_languages
-                                .contains(&config.primary_language)
-                            {
-                                eprintln!("Warning: Primary language '{:?}' from config is not in 'all_target_languages'.", config.primary_language);
-                                // Optionally add it or reset to default? For now, just warn.
+
+                            // Identical primary/secondary would leave only one auto-selected target
+                            if config.ensure_distinct_secondary() {
+                                println!(
+                                    "Using {:?} as secondary language instead.",
+                                    config.secondary_language
+                                );
                             }
-                            if !config
-                                .all_target_languages
-                                .contains(&config.secondary_language)
-                            {
-                                eprintln!("Warning: Secondary language '{:?}' from config is not in 'all_target_languages'.", config.secondary_language);
+
+                            // Tiny or huge fonts would make the window unusable
+                            let requested_scale = config.font_scale;
+                            if config.clamp_font_scale() {
+                                eprintln!(
+                                    "Warning: 'font_scale' {} is out of range, using {}",
+                                    requested_scale, config.font_scale
+                                );
                             }
 
                             // Log the loaded languages for debugging
@@ -224,26 +1427,57 @@ pub fn load_config() -> Config {
                             eprintln!("Parsing Error: {}", e);
 
                             // --- Backup invalid config file ---
-                            let backup_path = path.with_extension({
+                            let policy = BackupPolicy::from_invalid_config(format, &contents);
+                            if policy.enabled {
                                 let timestamp = SystemTime::now()
                                     .duration_since(UNIX_EPOCH)
                                     .map(|d| d.as_secs())
                                     .unwrap_or(0);
-                                format!("toml.invalid_{}", timestamp)
-                            });
-                            eprintln!("Backing up invalid config to {:?}", backup_path);
-                            if let Err(backup_err) = fs::rename(&path, &backup_path) {
-                                eprintln!("Failed to backup invalid config file: {}", backup_err);
+                                let backup_path =
+                                    path.with_file_name(backup_file_name(&path, timestamp));
+                                eprintln!("Backing up invalid config to {:?}", backup_path);
+                                let backed_up = match fs::rename(&path, &backup_path) {
+                                    Ok(()) => true,
+                                    Err(backup_err) => {
+                                        eprintln!(
+                                            "Failed to backup invalid config file: {}",
+                                            backup_err
+                                        );
+                                        false
+                                    }
+                                };
+                                // Backups pile up if every launch fails, keep only the newest
+                                if let Some(dir) = path.parent() {
+                                    match prune_config_backups(dir, policy.max_backups) {
+                                        Ok(0) => {}
+                                        Ok(removed) => {
+                                            println!("Removed {} old config backup(s)", removed)
+                                        }
+                                        Err(e) => {
+                                            eprintln!("Failed to remove old config backups: {}", e)
+                                        }
+                                    }
+                                }
+
+                                // Create and save a default config file once the invalid one
+                                // is out of the way; otherwise it would be overwritten
+                                if backed_up {
+                                    println!("Creating a new default config file at {:?}", path);
+                                    if let Err(save_err) = save_example_config_at(&path) {
+                                        eprintln!(
+                                            "Failed to save new default config: {}",
+                                            save_err
+                                        );
+                                    }
+                                }
+                            } else {
+                                // Leave the file for the user to fix, the defaults are only used
+                                // for this run
+                                eprintln!("Not backing up or replacing invalid config ('backup_invalid_config' is false)");
                             }
                             // --- End backup ---
 
-                            // Create and save a default config file after backing up the invalid one
-                            println!("Creating a new default config file at {:?}", path);
-                            let default_config = Config::default();
-                            if let Err(save_err) = save_config(&default_config) {
-                                eprintln!("Failed to save new default config: {}", save_err);
-                            }
-                            default_config // Return default config
+                            Config::default() // Return default config
                         }
                     }
                 }
@@ -264,71 +1498,103 @@ pub fn load_config() -> Config {
     }
 }
 
+// Write `config` to the config file, after fixing what validation can fix. Everything in
+// `config` is written, so don't pass it the result of load_config when config.local.toml
+// exists: the local overrides would end up in the shared config file.
 pub fn save_config(config: &Config) -> Result<(), std::io::Error> {
-    let path = get_config_path().ok_or_else(|| {
-        std::io::Error::new(
-            std::io::ErrorKind::NotFound,
-            "Could not determine config directory",
-        )
-    })?;
-
-    // Create the parent directory if it doesn't exist
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)?; // Propagate IO errors
-    }
-
-    // Validate before saving (optional, but good practice)
+    // Validate before saving and fix what can be fixed
     let mut validated_config = config.clone();
     if validated_config.all_target_languages.is_empty() {
         println!("Warning: 'all_target_languages' is empty during save, restoring defaults.");
         validated_config.all_target_languages = default_all_target_languages();
     }
-    // Ensure primary/secondary are in the list (optional: add them if missing?)
-    if !validated_config
-        .all_target_languages
-        .contains(&validated_config.primary_language)
-    {
-        eprintln!(
-            "Warning: Primary language {:?} not in list during save. Adding it.",
-            validated_config.primary_language
+    let duplicates = validated_config.dedup_target_languages();
+    if duplicates > 0 {
+        println!(
+            "Removed {} duplicate language(s) from 'all_target_languages' during save.",
+            duplicates
         );
-        validated_config
-            .all_target_languages
-            .push(validated_config.primary_language);
     }
-    if !validated_config
-        .all_target_languages
-        .contains(&validated_config.secondary_language)
-    {
+    // Ensure primary/secondary are in the list by appending them, in a fixed order
+    for lang in validated_config.add_missing_primary_secondary() {
         eprintln!(
-            "Warning: Secondary language {:?} not in list during save. Adding it.",
-            validated_config.secondary_language
+            "Warning: {:?} is not in 'all_target_languages' during save. Adding it.",
+            lang
         );
-        validated_config
-            .all_target_languages
-            .push(validated_config.secondary_language);
+    }
+    for warning in validated_config.validate() {
+        eprintln!("Warning: {}", warning);
     }
 
-    let toml_string = toml::to_string_pretty(&validated_config) // Save the validated config
+    // Written in the format of the existing config file, TOML for a new one
+    let path = get_config_path().ok_or_else(config_dir_not_found)?;
+    let format = ConfigFormat::from_path(&path).unwrap_or(ConfigFormat::Toml);
+    let contents = format
+        .serialize(&validated_config) // Save the validated config
         .map_err(|e| {
             std::io::Error::new(
                 std::io::ErrorKind::Other,
-                format!("TOML serialization error: {}", e),
+                format!("Config serialization error: {}", e),
             )
         })?;
 
-    // Use temp file writing to avoid corrupting the file if saving is interrupted
-    let temp_path = path.with_extension("tmp");
-    {
-        // Scope for file writing
-        let mut file = fs::File::create(&temp_path)?;
-        file.write_all(toml_string.as_bytes())?;
-        file.sync_all()?; // Ensure data is written to disk
-    } // File is closed here
+    // Nothing to write if the file already holds exactly this config
+    if matches!(fs::read_to_string(&path), Ok(existing) if existing == contents) {
+        return Ok(());
+    }
+    write_config_file(&path, &contents)
+}
+
+// Write the commented example config (Config::example_toml) as config.toml
+pub fn save_example_config() -> Result<(), std::io::Error> {
+    let dir = paths::app_config_dir().ok_or_else(config_dir_not_found)?;
+    save_example_config_at(&dir.join(CONFIG_FILE))
+}
+
+// Write the example config to `path`, in the format of its extension. Only TOML has comments,
+// JSON and YAML get the plain defaults.
+pub fn save_example_config_at(path: &Path) -> Result<(), std::io::Error> {
+    let contents = match ConfigFormat::from_path(path).unwrap_or(ConfigFormat::Toml) {
+        ConfigFormat::Toml => Config::example_toml(),
+        format => format.serialize(&Config::default()).map_err(|e| {
+            std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!("Config serialization error: {}", e),
+            )
+        })?,
+    };
+    write_config_file(path, &contents)
+}
+
+fn config_dir_not_found() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        "Could not determine config directory",
+    )
+}
+
+// Write the config file atomically, creating the config directory if needed.
+// Nothing is written once the directory turned out to be read-only.
+fn write_config_file(path: &Path, contents: &str) -> Result<(), std::io::Error> {
+    let dir = path.parent().unwrap_or(path);
+    paths::write_unless_read_only(dir, || {
+        // Create the parent directory if it doesn't exist
+        fs::create_dir_all(dir)?; // Propagate IO errors
+
+        // Use temp file writing to avoid corrupting the file if saving is interrupted.
+        // The temp name is unique so simultaneous saves (e.g. two instances) don't clobber each other.
+        let temp_path = paths::unique_temp_path(path);
+        {
+            // Scope for file writing
+            let mut file = fs::File::create(&temp_path)?;
+            file.write_all(contents.as_bytes())?;
+            file.sync_all()?; // Ensure data is written to disk
+        } // File is closed here
 
-    // Rename the temporary file to the final config file name
-    fs::rename(&temp_path, &path)?;
+        // Rename the temporary file to the final config file name
+        paths::replace_file(&temp_path, path)?;
 
-    println!("Config saved to {:?}", path); // Log success
-    Ok(())
+        println!("Config saved to {:?}", path); // Log success
+        Ok(())
+    })
 }
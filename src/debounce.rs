@@ -0,0 +1,57 @@
+// Debouncing for live translation: only the most recent call runs,
+// earlier ones are cancelled whether they are still waiting or already running
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+use tokio::time::{sleep, Duration};
+
+// Cheap to clone; all clones share the same generation counter
+#[derive(Debug, Clone)]
+pub struct Debouncer {
+    delay: Duration,
+    generation: Arc<AtomicU64>, // Bumped by every call, so older calls know they are stale
+    superseded: Arc<Notify>,
+}
+
+impl Debouncer {
+    pub fn new(delay: Duration) -> Self {
+        Debouncer {
+            delay,
+            generation: Arc::new(AtomicU64::new(0)),
+            superseded: Arc::new(Notify::new()),
+        }
+    }
+
+    // Cancel any pending or in-flight call
+    pub fn cancel(&self) -> u64 {
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        self.superseded.notify_waiters();
+        generation
+    }
+
+    // Wait until `generation` is no longer the latest one
+    async fn wait_superseded(&self, generation: u64) {
+        loop {
+            // Created before the check so a cancel in between isn't missed
+            let notified = self.superseded.notified();
+            if self.generation.load(Ordering::SeqCst) != generation {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    // Wait for the delay, then run `task` to completion.
+    // Returns None if a newer call (or cancel) came in first; the task is dropped in that case.
+    pub async fn run<F: Future>(&self, task: F) -> Option<F::Output> {
+        let generation = self.cancel();
+        tokio::select! {
+            output = async {
+                sleep(self.delay).await;
+                task.await
+            } => Some(output),
+            _ = self.wait_superseded(generation) => None,
+        }
+    }
+}
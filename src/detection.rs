@@ -0,0 +1,460 @@
+// Source language detection used to pick the translation target
+use crate::config::Config;
+use crate::settings::Settings;
+use lingua::{Language, LanguageDetector, LanguageDetectorBuilder};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+
+// The app only looks at the start of the text and gives up after a while
+pub const DETECTION_SAMPLE_CHARS: usize = 100;
+pub const DETECTION_TIMEOUT: Duration = Duration::from_secs(2);
+pub const DETECTION_MIN_CONFIDENCE: f64 = 0.0; // Accept any guess
+
+// A lead over the runner-up smaller than this means the sample may not be representative
+pub const DETECTION_AMBIGUITY_GAP: f64 = 0.1;
+
+// Confidence given to a language guessed from the script when lingua has no answer.
+// Below ALREADY_IN_TARGET_MIN_CONFIDENCE in ui.rs, so such a guess never skips a translation.
+pub const SCRIPT_HINT_CONFIDENCE: f64 = 0.5;
+
+// Detected language plus the runner-up, to judge how clear-cut the detection was
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Detection {
+    pub language: Language,
+    pub confidence: f64,
+    pub runner_up: Option<(Language, f64)>, // None if the detector knows a single language
+}
+
+impl Detection {
+    // A language that wasn't detected but set by the user, e.g. forced_source_language
+    pub fn certain(language: Language) -> Self {
+        Detection {
+            language,
+            confidence: 1.0,
+            runner_up: None,
+        }
+    }
+
+    // Best guess from confidence values sorted highest first, as lingua returns them.
+    // None in the same cases as LanguageDetector::detect_language_of:
+    // no values, zero confidence, or a tie between the two most likely languages.
+    pub fn from_confidence_values(values: &[(Language, f64)]) -> Option<Self> {
+        let (language, confidence) = *values.first()?;
+        if confidence <= 0.0 {
+            return None;
+        }
+        let runner_up = values.get(1).copied();
+        if let Some((_, runner_up_confidence)) = runner_up {
+            if (confidence - runner_up_confidence).abs() < f64::EPSILON {
+                return None;
+            }
+        }
+        Some(Detection {
+            language,
+            confidence,
+            runner_up,
+        })
+    }
+
+    // A language guessed from the script of the text (see script_hint)
+    pub fn from_script(language: Language) -> Self {
+        Detection {
+            language,
+            confidence: SCRIPT_HINT_CONFIDENCE,
+            runner_up: None,
+        }
+    }
+
+    // How far the best guess is ahead of the runner-up (its own confidence without one)
+    pub fn confidence_gap(&self) -> f64 {
+        match self.runner_up {
+            Some((_, runner_up_confidence)) => self.confidence - runner_up_confidence,
+            None => self.confidence,
+        }
+    }
+
+    pub fn is_ambiguous(&self) -> bool {
+        self.confidence_gap() < DETECTION_AMBIGUITY_GAP
+    }
+
+    // One line for logs and the debug panel, e.g. "French (0.52), runner-up Italian (0.47)"
+    pub fn summary(&self) -> String {
+        match self.runner_up {
+            Some((runner_up, runner_up_confidence)) => format!(
+                "{} ({:.2}), runner-up {} ({:.2})",
+                self.language, self.confidence, runner_up, runner_up_confidence
+            ),
+            None => format!("{} ({:.2})", self.language, self.confidence),
+        }
+    }
+}
+
+// Confidence values with `bias` (clamped to 0.0..=1.0) added to the primary language,
+// re-sorted highest first. Tips near-ties on short text toward the primary language.
+pub fn apply_primary_bias(
+    values: &[(Language, f64)],
+    primary: Language,
+    bias: f64,
+) -> Vec<(Language, f64)> {
+    let bias = if bias.is_nan() {
+        0.0
+    } else {
+        bias.clamp(0.0, 1.0)
+    };
+    let mut biased: Vec<(Language, f64)> = values
+        .iter()
+        .map(|&(language, confidence)| {
+            if language == primary {
+                (language, (confidence + bias).min(1.0))
+            } else {
+                (language, confidence)
+            }
+        })
+        .collect();
+    // Stable, so languages with equal values keep lingua's order
+    biased.sort_by(|a, b| b.1.total_cmp(&a.1));
+    biased
+}
+
+// Language suggested by the writing system of the text, for short text that lingua gives up
+// on. Only scripts that point to one likely language count; Latin text gives None.
+// Cyrillic is Russian unless Ukrainian letters appear, Arabic script is Arabic unless
+// Persian letters appear, and Han characters are Japanese if there is any kana.
+pub fn script_hint(text: &str) -> Option<Language> {
+    let mut counts: HashMap<Language, usize> = HashMap::new();
+    let mut has_kana = false;
+    for c in text.chars() {
+        let language = match c {
+            'і' | 'ї' | 'є' | 'ґ' | 'І' | 'Ї' | 'Є' | 'Ґ' => Language::Ukrainian,
+            '\u{0400}'..='\u{04FF}' => Language::Russian,
+            '\u{0370}'..='\u{03FF}' => Language::Greek,
+            '\u{0530}'..='\u{058F}' => Language::Armenian,
+            '\u{0590}'..='\u{05FF}' => Language::Hebrew,
+            'پ' | 'چ' | 'ژ' | 'گ' => Language::Persian,
+            '\u{0600}'..='\u{06FF}' => Language::Arabic,
+            '\u{0900}'..='\u{097F}' => Language::Hindi,
+            '\u{0980}'..='\u{09FF}' => Language::Bengali,
+            '\u{0A00}'..='\u{0A7F}' => Language::Punjabi,
+            '\u{0A80}'..='\u{0AFF}' => Language::Gujarati,
+            '\u{0B80}'..='\u{0BFF}' => Language::Tamil,
+            '\u{0C00}'..='\u{0C7F}' => Language::Telugu,
+            '\u{0E00}'..='\u{0E7F}' => Language::Thai,
+            '\u{10A0}'..='\u{10FF}' => Language::Georgian,
+            '\u{1100}'..='\u{11FF}' | '\u{AC00}'..='\u{D7AF}' => Language::Korean,
+            '\u{3040}'..='\u{30FF}' => {
+                has_kana = true;
+                Language::Japanese
+            }
+            '\u{4E00}'..='\u{9FFF}' => Language::Chinese,
+            _ => continue,
+        };
+        *counts.entry(language).or_insert(0) += 1;
+    }
+
+    // A single Ukrainian or Persian letter decides between languages sharing a script
+    let merge = |counts: &mut HashMap<Language, usize>, from: Language, into: Language| {
+        if let Some(count) = counts.remove(&from) {
+            *counts.entry(into).or_insert(0) += count;
+        }
+    };
+    if counts.contains_key(&Language::Ukrainian) {
+        merge(&mut counts, Language::Russian, Language::Ukrainian);
+    }
+    if counts.contains_key(&Language::Persian) {
+        merge(&mut counts, Language::Arabic, Language::Persian);
+    }
+    if has_kana {
+        merge(&mut counts, Language::Chinese, Language::Japanese);
+    }
+
+    // The script with the most characters; ties go to the first language in lingua's order
+    counts
+        .into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+        .map(|(language, _)| language)
+}
+
+// The primary language and its detection bias from the config, None without a bias
+pub fn primary_bias(config: &Config) -> Option<(Language, f64)> {
+    (config.detection_primary_bias > 0.0)
+        .then_some((config.primary_language, config.detection_primary_bias))
+}
+
+// Detectors built so far, keyed by their sorted language set.
+// Loading the language models is the slow part, so each set is built once per process.
+static DETECTOR_CACHE: OnceLock<Mutex<HashMap<Vec<Language>, Arc<LanguageDetector>>>> =
+    OnceLock::new();
+
+// Languages the app's detector knows: the primary and secondary language and the target
+// languages from config, at most max_detection_languages of them
+pub fn detection_languages(config: &Config) -> Vec<Language> {
+    let mut candidates = vec![config.primary_language, config.secondary_language];
+    candidates.extend(&config.all_target_languages);
+    let (languages, dropped) = cap_detection_languages(
+        &candidates,
+        config.primary_language,
+        config.secondary_language,
+        config.max_detection_languages,
+    );
+    if !dropped.is_empty() {
+        println!(
+            "Detecting at most {} languages (max_detection_languages), leaving out: {:?}",
+            config.max_detection_languages, dropped
+        );
+    }
+    languages
+}
+
+// Whether the source language can tell that text is already in the target: true when it is
+// forced, or detected by a detector built from more than one language. With one language
+// lingua answers that language with full confidence whatever the text is.
+pub fn detection_is_conclusive(config: &Config) -> bool {
+    config.forced_source_language.is_some() || detection_languages(config).len() > 1
+}
+
+// Split `languages` into the ones to detect and the ones left out when there are more than
+// `max` (0 = no limit). The primary and secondary language are kept if present, always,
+// then the others in their configured order. Duplicates are dropped silently.
+pub fn cap_detection_languages(
+    languages: &[Language],
+    primary: Language,
+    secondary: Language,
+    max: usize,
+) -> (Vec<Language>, Vec<Language>) {
+    let mut ordered = Vec::with_capacity(languages.len());
+    for language in [primary, secondary].iter().chain(languages) {
+        if languages.contains(language) && !ordered.contains(language) {
+            ordered.push(*language);
+        }
+    }
+    if max == 0 || ordered.len() <= max {
+        return (ordered, Vec::new());
+    }
+    let protected = ordered
+        .iter()
+        .filter(|language| **language == primary || **language == secondary)
+        .count();
+    let dropped = ordered.split_off(max.max(protected));
+    (ordered, dropped)
+}
+
+// Detector for the given languages with their models loaded, built on first use and cached.
+// Blocks while building, so call it off the async thread (see preload_detector).
+pub fn detector_for_languages(languages: &[Language]) -> Arc<LanguageDetector> {
+    let mut key = languages.to_vec();
+    key.sort();
+    key.dedup();
+
+    let cache = DETECTOR_CACHE.get_or_init(Default::default);
+    if let Some(detector) = cache.lock().unwrap().get(&key) {
+        return detector.clone();
+    }
+
+    // Built without holding the lock, so lookups of other sets aren't blocked meanwhile
+    let detector = Arc::new(
+        LanguageDetectorBuilder::from_languages(&key)
+            .with_low_accuracy_mode()
+            .with_preloaded_language_models()
+            .build(),
+    );
+    cache.lock().unwrap().entry(key).or_insert(detector).clone()
+}
+
+// Detector for the app
+pub fn build_detector(config: &Config) -> Arc<LanguageDetector> {
+    let languages = detection_languages(config);
+    println!("Setting up language detector with: {:?}", languages);
+    detector_for_languages(&languages)
+}
+
+// Start building the app's detector on a blocking thread, so loading the language models
+// overlaps with reading the clipboard instead of delaying detection
+pub fn preload_detector(config: &Config) -> JoinHandle<Arc<LanguageDetector>> {
+    let languages = detection_languages(config);
+    println!("Preloading language detector with: {:?}", languages);
+    tokio::task::spawn_blocking(move || detector_for_languages(&languages))
+}
+
+// Leading part of the text used for detection, cut at a character boundary.
+// A sample size of 0 uses the whole text.
+pub fn sample_text(text: &str, sample_chars: usize) -> &str {
+    if sample_chars == 0 {
+        return text;
+    }
+    match text.char_indices().nth(sample_chars) {
+        Some((byte_index, _)) => &text[..byte_index],
+        None => text, // Shorter than the sample size
+    }
+}
+
+// Detect the language of `text` from its first `sample_chars` characters.
+// Returns None if detection fails, takes longer than `timeout`, or the best
+// guess has a confidence below `min_confidence` (0.0 accepts any guess).
+// `primary_bias` is added to that language's confidence first (see apply_primary_bias).
+// Logs a warning when the runner-up comes close, as the sample may be unrepresentative.
+pub async fn detect_source_language(
+    detector: Arc<LanguageDetector>,
+    text: &str,
+    sample_chars: usize,
+    timeout: Duration,
+    min_confidence: f64,
+    primary_bias: Option<(Language, f64)>,
+) -> Option<Detection> {
+    println!("Text length for detection: {} characters", text.len());
+    let sample = sample_text(text, sample_chars).to_string();
+    let sampled_only = sample.len() < text.len();
+
+    // Detection is CPU-bound, run it off the async thread so the timeout can fire
+    let detection = tokio::task::spawn_blocking(move || {
+        let mut values = detector.compute_language_confidence_values(&sample);
+        if let Some((primary, bias)) = primary_bias {
+            values = apply_primary_bias(&values, primary, bias);
+        }
+        // Lingua often has no answer for very short text, the script may still tell
+        Detection::from_confidence_values(&values)
+            .or_else(|| script_hint(&sample).map(Detection::from_script))
+            .filter(|detection| detection.confidence >= min_confidence)
+    });
+
+    match tokio::time::timeout(timeout, detection).await {
+        Ok(Ok(detection)) => {
+            if let Some(detection) = detection.filter(Detection::is_ambiguous) {
+                eprintln!(
+                    "Warning: Language detection is ambiguous: {}{}",
+                    detection.summary(),
+                    if sampled_only {
+                        " (only the start of the text was checked)"
+                    } else {
+                        ""
+                    }
+                );
+            }
+            detection
+        }
+        Ok(Err(e)) => {
+            eprintln!("Language detection failed: {}", e);
+            None
+        }
+        Err(_) => {
+            println!("Language detection timed out after {:?}", timeout);
+            None
+        }
+    }
+}
+
+// Two-phase detection: the quick pass on the sample picks the target right away, a second
+// pass on the whole text can correct it. Only worth it if the sample left part of the text out.
+pub fn needs_full_detection(text: &str, sample_chars: usize) -> bool {
+    sample_text(text, sample_chars).len() < text.len()
+}
+
+// Second phase of two-phase detection: detect the language of the whole text in the
+// background, with the same timeout, threshold and bias as the first pass
+pub fn spawn_full_detection(
+    detector: Arc<LanguageDetector>,
+    text: String,
+    timeout: Duration,
+    min_confidence: f64,
+    primary_bias: Option<(Language, f64)>,
+) -> JoinHandle<Option<Detection>> {
+    tokio::spawn(async move {
+        detect_source_language(detector, &text, 0, timeout, min_confidence, primary_bias).await
+    })
+}
+
+// Whether the source language has to be detected, i.e. a detector is needed at all
+pub fn detection_needed(config: &Config) -> bool {
+    config.detection_enabled && config.forced_source_language.is_none()
+}
+
+// The language selection algorithm from README.md: the target for `source_lang` (None if
+// detection failed), falling back to `fallback_lang` when the source isn't one of
+// `target_langs`
+pub fn choose_target_language(
+    source_lang: Option<Language>,
+    primary_lang: Language,
+    secondary_lang: Language,
+    last_lang: Language,
+    fallback_lang: Language,
+    target_langs: &[Language],
+) -> Language {
+    match source_lang {
+        // 1. Source detection failed: use the fallback
+        None => fallback_lang,
+        // 2. Source isn't one of our languages: use the fallback
+        Some(source) if source != primary_lang && !target_langs.contains(&source) => fallback_lang,
+        // 3. If the source isn't the primary language, translate into the primary language
+        Some(source) if source != primary_lang => primary_lang,
+        // Source IS primary language
+        Some(_) => {
+            // 4. If there's a meaningful last choice, use it
+            if last_lang != primary_lang {
+                last_lang
+            } else {
+                // 5. Fall back to secondary language
+                secondary_lang
+            }
+        }
+    }
+}
+
+// The target language at startup. A pinned target always wins. Otherwise the remembered
+// language is kept if automatic selection is disabled, or if the user picked it by hand and
+// it is still a target language; otherwise choose_target_language picks it.
+pub fn startup_target_language(
+    source_lang: Option<Language>,
+    config: &Config,
+    settings: &Settings,
+) -> Language {
+    if let Some(pinned) = config.pinned_target_language {
+        return pinned;
+    }
+    if !config.auto_select_target {
+        return settings.last_language;
+    }
+    if settings.prefer_manual_target
+        && config
+            .all_target_languages
+            .contains(&settings.last_language)
+    {
+        return settings.last_language;
+    }
+    choose_target_language(
+        source_lang,
+        config.primary_language,
+        config.secondary_language,
+        settings.last_language,
+        config.fallback_language(),
+        &config.all_target_languages,
+    )
+}
+
+// Source language for target selection: the forced one if configured, None if detection
+// is disabled, otherwise whatever `detect` finds. `detect` is only called in the last case.
+pub async fn resolve_source_language<F, Fut>(
+    forced: Option<Language>,
+    detection_enabled: bool,
+    detect: F,
+) -> Option<Detection>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Option<Detection>>,
+{
+    match forced {
+        Some(lang) => {
+            println!(
+                "Using configured source language {:?}, skipping detection",
+                lang
+            );
+            Some(Detection::certain(lang))
+        }
+        None if !detection_enabled => {
+            println!("Language detection is disabled, skipping detection");
+            None
+        }
+        None => detect().await,
+    }
+}
@@ -1,12 +1,26 @@
 // Declare and re-export modules
+pub mod api_key;
+pub mod app_state;
+pub mod cli;
 pub mod clipboard_utils;
 pub mod config;
+pub mod debounce;
+pub mod detection;
+pub mod link;
+pub mod memory;
+pub mod paths;
+pub mod selftest;
 pub mod settings;
+pub mod silent;
 pub mod translation;
 pub mod ui;
+pub mod ui_strings;
 
 // Re-export commonly used items
-pub use translation::{request_translation, translate_text, TranslationResult};
+pub use translation::{
+    request_translation, translate_text, Metrics, OutputSink, RateLimiter, TranslationOutcome,
+    TranslationResult,
+};
 
 // Re-export the clone macro for use in tests
 #[macro_export]
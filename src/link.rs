@@ -0,0 +1,110 @@
+// Translating the page behind a link: with translate_urls, a clipboard holding nothing but
+// a URL is replaced by the readable text of the page it points to
+use crate::clipboard_utils::html_to_text;
+use crate::config::Config;
+use crate::translation::build_http_client;
+use reqwest::header::CONTENT_TYPE;
+use tokio::time::Duration;
+
+// Page text sent for translation, in bytes; longer pages are cut off
+pub const MAX_PAGE_TEXT_BYTES: usize = 20_000;
+// Download size at which reading the page stops, so a huge file can't fill the memory
+pub const MAX_PAGE_DOWNLOAD_BYTES: usize = 5_000_000;
+pub const FETCH_TIMEOUT: Duration = Duration::from_secs(15);
+
+// The URL if the text is a single http(s) URL and nothing else (surrounding whitespace aside)
+pub fn clipboard_url(text: &str) -> Option<&str> {
+    let candidate = text.trim();
+    if candidate.chars().any(char::is_whitespace) {
+        return None;
+    }
+    let url = reqwest::Url::parse(candidate).ok()?;
+    let is_web = matches!(url.scheme(), "http" | "https");
+    (is_web && url.host_str().is_some_and(|host| !host.is_empty())).then_some(candidate)
+}
+
+// Largest page text to translate: MAX_PAGE_TEXT_BYTES, or max_clipboard_bytes if lower,
+// so the page is cut off rather than refused
+pub fn page_text_limit(max_clipboard_bytes: usize) -> usize {
+    match max_clipboard_bytes {
+        0 => MAX_PAGE_TEXT_BYTES,
+        limit => limit.min(MAX_PAGE_TEXT_BYTES),
+    }
+}
+
+// The start of `text` within `max_bytes`, cut at the last line break if there is one in the
+// second half, otherwise at a character boundary
+pub fn cap_text(text: &str, max_bytes: usize) -> &str {
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    let head = &text[..end];
+    match head.rfind('\n') {
+        Some(line_end) if line_end >= end / 2 => head[..line_end].trim_end(),
+        _ => head,
+    }
+}
+
+// Download the page at `url` and return its text, tags stripped for HTML, capped to
+// `max_bytes`. Errors are messages to show instead of the translation.
+pub async fn fetch_page_text(
+    url: &str,
+    config: &Config,
+    max_bytes: usize,
+) -> Result<String, String> {
+    let client = build_http_client(config)?;
+    let fetch_error = |e: reqwest::Error| format!("Failed to fetch {}: {}", url, e);
+    let mut response = client
+        .get(url)
+        .timeout(FETCH_TIMEOUT)
+        .send()
+        .await
+        .map_err(fetch_error)?;
+
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("Failed to fetch {}: HTTP {}", url, status));
+    }
+    // Plain text pages are taken as they are, anything else is treated as HTML
+    let is_plain_text = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("text/plain"));
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(fetch_error)? {
+        body.extend_from_slice(&chunk);
+        if body.len() >= MAX_PAGE_DOWNLOAD_BYTES {
+            break;
+        }
+    }
+    let body = String::from_utf8_lossy(&body);
+    let text = if is_plain_text {
+        body.into_owned()
+    } else {
+        html_to_text(&body)
+    };
+
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(format!("No text found at {}", url));
+    }
+    Ok(cap_text(text, max_bytes).to_string())
+}
+
+// The text to translate for clipboard `text`: the page text if it is a link and
+// translate_urls is on, otherwise the text itself
+pub async fn resolve_link(text: String, config: &Config) -> Result<String, String> {
+    match clipboard_url(&text).filter(|_| config.translate_urls) {
+        Some(url) => {
+            println!("Clipboard holds a link, translating the page at {}", url);
+            fetch_page_text(url, config, page_text_limit(config.max_clipboard_bytes)).await
+        }
+        None => Ok(text),
+    }
+}
@@ -1,71 +1,113 @@
-// Declare modules
-mod config;
-mod settings;
-mod translation;
-mod ui;
-
 use dotenvy::dotenv;
 use gtk::prelude::*;
-use gtk::{glib, Application};
+use gtk::{gio, glib, Application};
+// Modules live in the library crate (src/lib.rs)
+use translator::cli::{self, Command};
+use translator::{config, selftest, settings, silent, ui};
 
 const APP_ID: &str = "org.gtk_rs.ClipboardTranslator";
 
 // Use tokio runtime for async operations
 #[tokio::main]
 async fn main() -> glib::ExitCode {
+    // --version, --help, --list-languages and --reset-language exit before the config is
+    // loaded or created
+    let command = cli::parse_args(std::env::args().skip(1));
+    match command {
+        Command::Version => {
+            println!("{}", cli::version_text());
+            return glib::ExitCode::SUCCESS;
+        }
+        Command::Help => {
+            println!("{}", cli::help_text());
+            return glib::ExitCode::SUCCESS;
+        }
+        Command::ListLanguages => {
+            for (code, name) in config::supported_language_codes() {
+                println!("{}  {}", code, name);
+            }
+            return glib::ExitCode::SUCCESS;
+        }
+        Command::ResetLanguage => {
+            return match settings::clear_last_language() {
+                Ok(()) => {
+                    println!("The target language will be chosen automatically again.");
+                    glib::ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    eprintln!("Failed to reset the target language: {}", e);
+                    glib::ExitCode::FAILURE
+                }
+            };
+        }
+        Command::Gui | Command::Silent | Command::SelfTest | Command::Trigger => {}
+    }
+
+    // Create a new application; it is single-instance, keyed by APP_ID
+    let app = Application::builder().application_id(APP_ID).build();
+
+    // --trigger: if an instance is already running, make it translate the clipboard again
+    // instead of opening a second window; otherwise start normally
+    if command == Command::Trigger {
+        if let Err(e) = app.register(None::<&gio::Cancellable>) {
+            eprintln!("Failed to register the application: {}", e);
+            return glib::ExitCode::FAILURE;
+        }
+        // Running a remote instance only sends it the activation, then returns
+        if app.is_remote() {
+            return app.run_with_args(&gtk_args());
+        }
+    }
+
     // Load environment variables from .env file if present
     dotenv().ok(); // This is still useful for API keys, etc.
 
     // Load configuration from file (or defaults if not found/invalid)
     let config = config::load_config();
 
-    // Create a new application
-    let app = Application::builder().application_id(APP_ID).build();
+    // --selftest: translate a fixed word with the configured key and model and report the result
+    if command == Command::SelfTest {
+        return match selftest::run_selftest(&config).await {
+            Ok(message) => {
+                println!("{}", message);
+                glib::ExitCode::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("Self-test failed: {}", e);
+                glib::ExitCode::FAILURE
+            }
+        };
+    }
+
+    // --silent: translate the clipboard in place without a window and exit
+    if command == Command::Silent {
+        return match silent::run_silent(&config) {
+            Ok(()) => glib::ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("{}", e);
+                glib::ExitCode::FAILURE
+            }
+        };
+    }
 
     // Clone the config to move into the closure
     let initial_config = config.clone();
 
-    // Connect to "activate" signal of `app`
+    // Close the windows cleanly on SIGINT/SIGTERM; once per process, not per window
+    #[cfg(unix)]
+    app.connect_startup(ui::install_signal_handlers);
+
+    // Connect to "activate" signal of `app`, also emitted when the app is launched again
     // Pass the loaded initial config to the UI builder using a closure
     app.connect_activate(move |app| {
-        ui::build_ui(app, initial_config.clone()); // Pass the config
+        ui::activate(app, initial_config.clone()); // Pass the config
     });
 
     // Run the application
-    app.run()
+    app.run_with_args(&gtk_args())
 }
 
-// Helper macro for cloning Rc variables for closures
-// Keep it here or move to a dedicated utils module if needed elsewhere
-#[macro_export] // Export macro to make it visible in other modules like ui.rs
-macro_rules! clone {
-    (@param _) => ( _ );
-    (@param $x:ident) => ( $x );
-    ($($n:ident $(: $t:ty)?),+ => $body:expr) => (
-        {
-            $( let $n = $n.clone(); )+
-            $body
-        }
-    );
-    // This variant clones ONE variable using @strong syntax
-    (@strong $n:ident => $body:expr) => (
-        {
-            let $n = $n.clone();
-            $body
-        }
-    );
-     (@weak $n:ident => $body:expr) => (
-        {
-            let $n = $n.downgrade();
-            $body
-        }
-    );
-     (@weak $n:ident $(: $t:ty)? = $e:expr => $body:expr) => (
-        {
-            let $n = $e.downgrade();
-            $body
-        }
-    );
+// Arguments for GTK: --trigger is ours, the others are left to GTK
+fn gtk_args() -> Vec<String> {
+    std::env::args().filter(|arg| arg != "--trigger").collect()
 }
-// No need for `use clone;` here as the macro is defined in the same scope (root of the crate)
-// Other modules will need `use crate::clone;`
@@ -0,0 +1,110 @@
+// Session translation memory: with use_translation_memory, texts translated while the app
+// runs are kept, and the earlier ones sharing the most words with a new text go into its
+// prompt as examples, so terms are translated the same way throughout the session
+use lingua::Language;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+// Translations kept; the oldest is dropped when a new one comes in beyond this
+pub const MAX_MEMORY_ENTRIES: usize = 50;
+// Earlier translations added to a prompt at most
+pub const MAX_MEMORY_EXAMPLES: usize = 3;
+// Longer originals aren't kept, as examples of that size would crowd the prompt
+pub const MAX_MEMORY_TEXT_CHARS: usize = 500;
+// Shorter words, e.g. articles, don't make a translation relevant
+const MIN_WORD_CHARS: usize = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryEntry {
+    pub target: Language,
+    pub original: String,
+    pub translation: String,
+}
+
+static SESSION_MEMORY: Mutex<Vec<MemoryEntry>> = Mutex::new(Vec::new());
+
+// Keep a finished translation for later prompts of this session
+pub fn remember(target: Language, original: &str, translation: &str) {
+    let (original, translation) = (original.trim(), translation.trim());
+    if original.is_empty()
+        || translation.is_empty()
+        || original.chars().count() > MAX_MEMORY_TEXT_CHARS
+    {
+        return;
+    }
+    let mut memory = SESSION_MEMORY.lock().unwrap();
+    // Translating the same text again replaces its entry
+    memory.retain(|entry| entry.target != target || entry.original != original);
+    memory.push(MemoryEntry {
+        target,
+        original: original.to_string(),
+        translation: translation.to_string(),
+    });
+    if memory.len() > MAX_MEMORY_ENTRIES {
+        memory.remove(0);
+    }
+}
+
+// The session's earlier translations that are relevant to `text`, see relevant_entries
+pub fn session_examples(text: &str, target: Language) -> Vec<MemoryEntry> {
+    let memory = SESSION_MEMORY.lock().unwrap();
+    relevant_entries(&memory, text, target, MAX_MEMORY_EXAMPLES)
+        .into_iter()
+        .cloned()
+        .collect()
+}
+
+// Lowercase words of at least MIN_WORD_CHARS characters
+fn words(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= MIN_WORD_CHARS)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+// Up to `max` entries into `target` sharing words with `text`, those sharing the most first
+// and the most recent first among equals. Entries for the same text are left out, they
+// would just give the answer away.
+pub fn relevant_entries<'a>(
+    memory: &'a [MemoryEntry],
+    text: &str,
+    target: Language,
+    max: usize,
+) -> Vec<&'a MemoryEntry> {
+    let text_words = words(text);
+    let mut scored: Vec<(usize, &MemoryEntry)> = memory
+        .iter()
+        .rev() // Most recent first, kept among equal scores by the stable sort
+        .filter(|entry| entry.target == target && entry.original != text.trim())
+        .map(|entry| {
+            (
+                words(&entry.original).intersection(&text_words).count(),
+                entry,
+            )
+        })
+        .filter(|(shared, _)| *shared > 0)
+        .collect();
+    scored.sort_by(|a, b| b.0.cmp(&a.0));
+    scored
+        .into_iter()
+        .take(max)
+        .map(|(_, entry)| entry)
+        .collect()
+}
+
+// Added to the system prompt to show the model the earlier translations, None without any
+pub fn memory_instruction(examples: &[MemoryEntry]) -> Option<String> {
+    if examples.is_empty() {
+        return None;
+    }
+    let mut instruction = String::from(
+        "Translate terms the same way as in these earlier translations from this session:",
+    );
+    for example in examples {
+        instruction.push_str(&format!(
+            "\n\nOriginal:\n{}\nTranslation:\n{}",
+            example.original, example.translation
+        ));
+    }
+    Some(instruction)
+}
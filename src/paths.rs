@@ -0,0 +1,125 @@
+// Locations of the app's files, shared by config.rs and settings.rs
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+// How often replace_file tries the rename before giving up
+const RENAME_ATTEMPTS: u32 = 5;
+
+// Name of the app's directory inside the user config dir
+pub const APP_DIR_NAME: &str = "translator";
+
+// Overrides APP_DIR_NAME; an absolute path replaces the whole directory
+pub const CONFIG_DIR_ENV: &str = "TRANSLATOR_CONFIG_DIR";
+
+// --- Directory holding config.toml and settings.toml ---
+pub fn app_config_dir() -> Option<PathBuf> {
+    let dir_override = std::env::var(CONFIG_DIR_ENV)
+        .ok()
+        .filter(|value| !value.trim().is_empty());
+
+    if let Some(dir) = &dir_override {
+        let path = PathBuf::from(dir);
+        if path.is_absolute() {
+            return Some(path);
+        }
+    }
+
+    // Check XDG_CONFIG_HOME first, then fall back to dirs::config_dir()
+    let config_dir = if let Ok(xdg_config) = std::env::var("XDG_CONFIG_HOME") {
+        PathBuf::from(xdg_config)
+    } else {
+        dirs::config_dir()?
+    };
+
+    Some(config_dir.join(dir_override.as_deref().unwrap_or(APP_DIR_NAME)))
+}
+
+// Directories found to be read-only while running; saving there is skipped from then on
+// instead of failing (and logging the failure) on every save
+static READ_ONLY_DIRS: OnceLock<Mutex<HashSet<PathBuf>>> = OnceLock::new();
+
+// Whether a write failed because the directory can't be written to at all
+pub fn is_read_only_error(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem
+    )
+}
+
+// Whether saving to `dir` failed as read-only before
+pub fn is_read_only_dir(dir: &Path) -> bool {
+    READ_ONLY_DIRS
+        .get()
+        .is_some_and(|dirs| dirs.lock().unwrap().contains(dir))
+}
+
+// Run `write`, which writes into `dir`, unless `dir` turned out to be read-only before; then
+// nothing is written and the result is Ok. The first read-only failure is returned with a
+// message saying so and marks `dir`, so it is reported once.
+pub fn write_unless_read_only(
+    dir: &Path,
+    write: impl FnOnce() -> io::Result<()>,
+) -> io::Result<()> {
+    if is_read_only_dir(dir) {
+        return Ok(());
+    }
+    match write() {
+        Err(e) if is_read_only_error(&e) => {
+            READ_ONLY_DIRS
+                .get_or_init(Default::default)
+                .lock()
+                .unwrap()
+                .insert(dir.to_path_buf());
+            Err(io::Error::new(
+                e.kind(),
+                format!(
+                    "{} is read-only, not writing to this directory until restart: {}",
+                    dir.display(),
+                    e
+                ),
+            ))
+        }
+        result => result,
+    }
+}
+
+// Temp file next to `path` that no other process or thread writes to:
+// <file name>.<pid>.<counter>.tmp
+pub fn unique_temp_path(path: &Path) -> PathBuf {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let counter = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(
+        "{}.{}.{}.tmp",
+        file_name,
+        std::process::id(),
+        counter
+    ))
+}
+
+// Move a finished temp file over `path`. Retries if the rename fails because
+// another writer's file is in the way, and cleans up the temp file on failure.
+pub fn replace_file(temp_path: &Path, path: &Path) -> io::Result<()> {
+    let mut attempt = 1;
+    loop {
+        match fs::rename(temp_path, path) {
+            Ok(()) => return Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempt < RENAME_ATTEMPTS => {
+                std::thread::sleep(Duration::from_millis(10 * u64::from(attempt)));
+                attempt += 1;
+            }
+            Err(e) => {
+                let _ = fs::remove_file(temp_path);
+                return Err(e);
+            }
+        }
+    }
+}
@@ -0,0 +1,30 @@
+// Self-test mode (--selftest): check that the API key, endpoint and model work by
+// translating a fixed word, without opening a window
+use crate::api_key::resolve_api_key;
+use crate::config::Config;
+use crate::translation::translate_text;
+
+// Text translated by the self-test
+pub const SELFTEST_TEXT: &str = "Hello";
+
+// Translate SELFTEST_TEXT into the secondary language with the resolved API key.
+// Returns the line to print on success, the error otherwise.
+pub async fn run_selftest(config: &Config) -> Result<String, String> {
+    let api_key = match resolve_api_key(config) {
+        Ok(key) => key,
+        Err(_) if config.mock => String::new(), // Mock translations need no key
+        Err(message) => return Err(message),
+    };
+    run_selftest_with_key(config, api_key).await
+}
+
+// Same as run_selftest, with the API key passed in
+pub async fn run_selftest_with_key(config: &Config, api_key: String) -> Result<String, String> {
+    let target_lang = config.secondary_language;
+    let translation =
+        translate_text(SELFTEST_TEXT, None, target_lang, api_key, config, None).await?;
+    Ok(format!(
+        "OK: {} translated \"{}\" into {}: \"{}\"",
+        config.model_version, SELFTEST_TEXT, target_lang, translation
+    ))
+}
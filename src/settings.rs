@@ -1,105 +1,360 @@
-// Use lingua::Language and IsoCode639_1 directly
-use lingua::{IsoCode639_1, Language};
+// Use lingua::Language directly
+use lingua::Language;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
-use std::str::FromStr;
-
-const SETTINGS_DIR: &str = "translator";
-const LAST_LANG_FILE: &str = "last_language.txt"; // Store ISO code
-
-// --- Helper function to get last language file path ---
-fn get_last_lang_path() -> Option<PathBuf> {
-    // Check XDG_CONFIG_HOME first, then fall back to dirs::config_dir()
-    let config_dir = if let Ok(xdg_config) = std::env::var("XDG_CONFIG_HOME") {
-        PathBuf::from(xdg_config)
-    } else {
-        dirs::config_dir()?
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tokio::time::Duration;
+
+use crate::config::language_serde;
+use crate::paths;
+
+const SETTINGS_FILE: &str = "settings.toml";
+const USAGE_FILE: &str = "usage.toml";
+const LEGACY_LAST_LANG_FILE: &str = "last_language.txt"; // Old format: bare ISO code
+
+// How long changes to the last language are collected before settings.toml is written, so
+// cycling through the language buttons doesn't write the file on every click
+pub const SETTINGS_SAVE_DELAY: Duration = Duration::from_secs(1);
+
+// Settings waiting to be written by save_settings_later, with the file they go to
+static PENDING_SETTINGS: Mutex<Option<(PathBuf, Settings)>> = Mutex::new(None);
+
+// Runtime state persisted between launches (as opposed to user-edited config.toml)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Settings {
+    #[serde(with = "language_serde")] // Stored as an ISO code, same as config.toml
+    pub last_language: Language,
+    // Set when the user picks a target by hand; the next launch then keeps
+    // last_language instead of choosing one automatically
+    #[serde(default)]
+    pub prefer_manual_target: bool,
+    // Window size when the app was last closed
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window_width: Option<i32>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window_height: Option<i32>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        // Default to English if no saved language
+        Settings {
+            last_language: Language::English,
+            prefer_manual_target: false,
+            window_width: None,
+            window_height: None,
+        }
+    }
+}
+
+// How often each target language was picked by hand, keyed by uppercase ISO code
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct LanguageUsage {
+    #[serde(default)]
+    pub counts: BTreeMap<String, u32>,
+}
+
+impl LanguageUsage {
+    fn key(lang: Language) -> String {
+        lang.iso_code_639_1().to_string().to_uppercase()
+    }
+
+    pub fn count(&self, lang: Language) -> u32 {
+        self.counts.get(&Self::key(lang)).copied().unwrap_or(0)
+    }
+
+    pub fn increment(&mut self, lang: Language) {
+        let count = self.counts.entry(Self::key(lang)).or_insert(0);
+        *count = count.saturating_add(1);
+    }
+}
+
+// --- Helper function to get a file path in the settings directory ---
+fn get_settings_dir() -> Option<PathBuf> {
+    paths::app_config_dir()
+}
+
+fn get_settings_path() -> Option<PathBuf> {
+    get_settings_dir().map(|dir| dir.join(SETTINGS_FILE))
+}
+
+fn get_usage_path() -> Option<PathBuf> {
+    get_settings_dir().map(|dir| dir.join(USAGE_FILE))
+}
+
+// --- Write a value as TOML atomically ---
+// Nothing is written once the directory turned out to be read-only
+fn write_toml<T: Serialize>(path: &Path, value: &T) -> Result<(), std::io::Error> {
+    let toml_string = toml::to_string_pretty(value).map_err(|e| {
+        std::io::Error::new(
+            std::io::ErrorKind::Other,
+            format!("TOML serialization error: {}", e),
+        )
+    })?;
+
+    let dir = path.parent().unwrap_or(path);
+    paths::write_unless_read_only(dir, || {
+        // Create the parent directory if it doesn't exist
+        fs::create_dir_all(dir)?; // Propagate IO errors
+
+        // Use temp file writing to avoid corrupting the file if saving is interrupted.
+        // The temp name is unique so simultaneous saves (e.g. two instances) don't clobber each other.
+        let temp_path = paths::unique_temp_path(path);
+        fs::write(&temp_path, toml_string)?;
+
+        // Rename the temporary file to the final file name
+        paths::replace_file(&temp_path, path)
+    })
+}
+
+// --- Migration from the old last_language.txt ---
+// Returns the language stored in the legacy file, moving it into settings.toml
+fn migrate_legacy_last_language() -> Option<Language> {
+    let legacy_path = get_settings_dir()?.join(LEGACY_LAST_LANG_FILE);
+    let contents = fs::read_to_string(&legacy_path).ok()?;
+
+    let Some(lang) = language_serde::parse_language(&contents) else {
+        println!(
+            "Invalid language '{}' in {:?}, ignoring it",
+            contents.trim(),
+            legacy_path
+        );
+        return None;
     };
 
-    let mut path = config_dir;
-    path.push(SETTINGS_DIR);
-    path.push(LAST_LANG_FILE);
-    Some(path)
+    println!("Migrating last language {:?} from {:?}", lang, legacy_path);
+    match save_settings(&Settings {
+        last_language: lang,
+        ..Settings::default()
+    }) {
+        Ok(()) => {
+            if let Err(e) = fs::remove_file(&legacy_path) {
+                eprintln!("Failed to remove legacy file {:?}: {}", legacy_path, e);
+            }
+        }
+        Err(e) => eprintln!("Failed to migrate last language: {}", e),
+    }
+    Some(lang)
 }
 
-// --- Helper function to load last language from settings ---
-// Returns lingua::Language
-pub fn load_last_language() -> Language {
-    // Default to English if no saved language
-    let default_language = Language::from_iso_code_639_1(&IsoCode639_1::from_str("EN").unwrap());
-    match get_last_lang_path() {
-        Some(path) => {
-            match fs::read_to_string(path) {
-                Ok(iso_code) => {
-                    // Try to convert the ISO code to a Language
-                    let iso_code_str = iso_code.trim().to_uppercase();
-                    println!("Loaded last language ISO code: {}", iso_code_str);
-
-                    // Try to parse the string as an IsoCode639_1 enum value
-                    match IsoCode639_1::from_str(&iso_code_str) {
-                        Ok(iso_code) => {
-                            // Convert from IsoCode639_1 to Language
-                            let lang = Language::from_iso_code_639_1(&iso_code);
-                            println!("Loaded last language: {:?}", lang);
-                            lang
-                        }
-                        Err(_) => {
-                            // Try to parse as language name for backward compatibility
-                            match Language::from_str(&iso_code_str) {
-                                Ok(lang) => lang,
-                                Err(_) => {
-                                    println!("Invalid ISO code '{}' in settings file, using default {:?}", iso_code_str, default_language);
-                                    default_language
-                                }
-                            }
-                        }
-                    }
-                }
-                Err(e) => {
-                    // Don't print error if file simply doesn't exist, that's expected on first run
-                    if e.kind() != std::io::ErrorKind::NotFound {
-                        println!("Could not load language setting: {}", e); // Log other errors
-                    }
-                    default_language // Default if file can't be read
-                }
+// --- Load settings, migrating the legacy file if settings.toml doesn't exist yet ---
+// Changes not written yet by save_settings_later are included
+pub fn load_settings() -> Settings {
+    let Some(path) = get_settings_path() else {
+        println!("Could not determine config directory for settings");
+        return Settings::default(); // Default if path can't be determined
+    };
+
+    if let Some((pending_path, settings)) = PENDING_SETTINGS.lock().unwrap().as_ref() {
+        if *pending_path == path {
+            return settings.clone();
+        }
+    }
+
+    match fs::read_to_string(&path) {
+        Ok(contents) => match toml::from_str::<Settings>(&contents) {
+            Ok(settings) => settings,
+            Err(e) => {
+                println!("Invalid settings file {:?}: {}. Using defaults.", path, e);
+                Settings::default()
+            }
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            // Not an error on first run; pick up the old format if it's there
+            match migrate_legacy_last_language() {
+                Some(lang) => Settings {
+                    last_language: lang,
+                    ..Settings::default()
+                },
+                None => Settings::default(),
             }
         }
-        None => {
-            println!("Could not determine config directory for last language");
-            default_language // Default if path can't be determined
+        Err(e) => {
+            println!("Could not load settings: {}", e); // Log other errors
+            Settings::default()
         }
     }
 }
 
-// --- Helper function to save last language to settings ---
-// Accepts lingua::Language
-pub fn save_last_language(lang: Language) -> Result<(), std::io::Error> {
-    let path = get_last_lang_path().ok_or_else(|| {
+// --- Save settings atomically ---
+// Replaces changes still waiting in save_settings_later, as `settings` are based on them
+pub fn save_settings(settings: &Settings) -> Result<(), std::io::Error> {
+    let path = settings_path_or_error()?;
+    PENDING_SETTINGS.lock().unwrap().take();
+    write_toml(&path, settings)
+}
+
+fn settings_path_or_error() -> Result<PathBuf, std::io::Error> {
+    get_settings_path().ok_or_else(|| {
         std::io::Error::new(
             std::io::ErrorKind::NotFound,
-            "Could not determine config directory for last language",
+            "Could not determine config directory for settings",
         )
-    })?;
+    })
+}
 
-    // Create the parent directory if it doesn't exist
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)?; // Propagate IO errors
+// --- Save settings after SETTINGS_SAVE_DELAY, together with later changes ---
+// load_settings sees the changes right away. Written immediately without a tokio runtime
+// to run the timer; write errors are logged as the caller has moved on by then.
+pub fn save_settings_later(settings: Settings) -> Result<(), std::io::Error> {
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+        return save_settings(&settings);
+    };
+    let path = settings_path_or_error()?;
+    // A pending save already has its timer running and writes these settings instead
+    let timer_running = PENDING_SETTINGS
+        .lock()
+        .unwrap()
+        .replace((path, settings))
+        .is_some();
+    if !timer_running {
+        runtime.spawn(async {
+            tokio::time::sleep(SETTINGS_SAVE_DELAY).await;
+            if let Err(e) = flush_settings() {
+                eprintln!("Failed to save settings: {}", e);
+            }
+        });
     }
+    Ok(())
+}
 
-    // Get the ISO 639-1 code for the language
-    let iso_code = lang.iso_code_639_1().to_string().to_uppercase();
+// --- Write settings waiting in save_settings_later now, e.g. when the app exits ---
+// Returns whether settings.toml was written
+pub fn flush_settings() -> Result<bool, std::io::Error> {
+    let pending = PENDING_SETTINGS.lock().unwrap().take();
+    match pending {
+        Some((path, settings)) => {
+            write_toml(&path, &settings)?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
 
-    // Use temp file writing to avoid corrupting the file if saving is interrupted
-    let temp_path = path.with_extension("tmp");
-    // Write the ISO code (e.g., "EN")
-    fs::write(&temp_path, &iso_code)?;
+// --- Helper function to load last language from settings ---
+// Returns lingua::Language
+pub fn load_last_language() -> Language {
+    let lang = load_settings().last_language;
+    println!("Loaded last language: {:?}", lang);
+    lang
+}
 
-    // Rename the temporary file to the final file name
-    fs::rename(&temp_path, &path)?;
+// --- Helper function to save last language to settings ---
+// Accepts lingua::Language
+pub fn save_last_language(lang: Language) -> Result<(), std::io::Error> {
+    let mut settings = load_settings();
+    settings.last_language = lang;
+    save_settings_later(settings)?;
 
     println!(
-        "Last language saved to {:?}: {:?} (ISO: {})",
-        path, lang, iso_code
+        "Last language save scheduled: {:?} (ISO: {})",
+        lang,
+        lang.iso_code_639_1().to_string().to_uppercase()
     );
     Ok(())
 }
+
+// --- Forget the remembered target language ---
+// Resets it to the default and turns off prefer_manual_target, so the next launch picks the
+// target automatically. Other settings, such as the window size, are kept.
+pub fn clear_last_language() -> Result<(), std::io::Error> {
+    let mut settings = load_settings();
+    settings.last_language = Settings::default().last_language;
+    settings.prefer_manual_target = false;
+    save_settings(&settings)?;
+
+    // A leftover legacy file would bring the old language back if settings.toml is deleted
+    if let Some(dir) = get_settings_dir() {
+        match fs::remove_file(dir.join(LEGACY_LAST_LANG_FILE)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+
+    println!("Last language cleared");
+    Ok(())
+}
+
+// --- Helper function to save a target language picked by the user ---
+// Also turns on prefer_manual_target so the choice survives the next launch
+pub fn save_manual_target(lang: Language) -> Result<(), std::io::Error> {
+    let mut settings = load_settings();
+    settings.last_language = lang;
+    settings.prefer_manual_target = true;
+    save_settings_later(settings)?;
+
+    println!("Manual target language save scheduled: {:?}", lang);
+    Ok(())
+}
+
+// State written when the app exits, whether closed or killed by a signal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ExitState {
+    pub last_language: Option<Language>, // Language of the active button, if any
+    pub window_size: Option<(i32, i32)>,
+}
+
+// Settings with the exit state applied, or None if they already match it
+pub fn apply_exit_state(settings: &Settings, exit_state: &ExitState) -> Option<Settings> {
+    let mut updated = settings.clone();
+    if let Some(lang) = exit_state.last_language {
+        updated.last_language = lang;
+    }
+    if let Some((width, height)) = exit_state.window_size {
+        updated.window_width = Some(width);
+        updated.window_height = Some(height);
+    }
+    let changed = updated.last_language != settings.last_language
+        || updated.window_width != settings.window_width
+        || updated.window_height != settings.window_height;
+    changed.then_some(updated)
+}
+
+// --- Write the exit state, skipping the write if nothing changed ---
+// Returns whether settings.toml was written
+pub fn flush_exit_state(exit_state: &ExitState) -> Result<bool, std::io::Error> {
+    match apply_exit_state(&load_settings(), exit_state) {
+        Some(settings) => {
+            save_settings(&settings)?;
+            println!("Saved settings on exit: {:?}", exit_state);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+// --- Load language usage counts; missing or invalid files count as no usage ---
+pub fn load_usage() -> LanguageUsage {
+    let Some(path) = get_usage_path() else {
+        return LanguageUsage::default();
+    };
+    match fs::read_to_string(&path) {
+        Ok(contents) => toml::from_str(&contents).unwrap_or_else(|e| {
+            println!("Invalid usage file {:?}: {}. Starting over.", path, e);
+            LanguageUsage::default()
+        }),
+        Err(_) => LanguageUsage::default(), // Not an error on first run
+    }
+}
+
+// --- Save language usage counts atomically ---
+pub fn save_usage(usage: &LanguageUsage) -> Result<(), std::io::Error> {
+    let path = get_usage_path().ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "Could not determine config directory for usage counts",
+        )
+    })?;
+    write_toml(&path, usage)
+}
+
+// --- Helper function to count a manual selection of a target language ---
+pub fn record_language_use(lang: Language) -> Result<(), std::io::Error> {
+    let mut usage = load_usage();
+    usage.increment(lang);
+    save_usage(&usage)
+}
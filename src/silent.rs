@@ -0,0 +1,94 @@
+// Silent mode (--silent): translate the clipboard in place without showing a window,
+// e.g. bound to a keyboard shortcut
+use gtk::{gdk, glib};
+use lingua::{Language, LanguageDetector};
+use std::sync::Arc;
+
+use crate::api_key::resolve_api_key;
+use crate::clipboard_utils::{read_clipboard_text, write_clipboard_text};
+use crate::config::Config;
+use crate::detection::{
+    build_detector, detect_source_language, primary_bias, resolve_source_language,
+    startup_target_language, DETECTION_MIN_CONFIDENCE, DETECTION_SAMPLE_CHARS, DETECTION_TIMEOUT,
+};
+use crate::link::resolve_link;
+use crate::settings::{self, Settings};
+use crate::translation::{check_clipboard_size, config_for_pair, translate_long_text};
+
+// Translate `text` into the target the window would pick at startup.
+// No GTK involved, so this can run (and be tested) without a display.
+// `detector` builds the detector, it is only called if detection is needed.
+// Returns the target language and the translation.
+pub async fn translate_silently<D>(
+    text: &str,
+    config: &Config,
+    settings: &Settings,
+    api_key: String,
+    detector: D,
+) -> Result<(Language, String), String>
+where
+    D: FnOnce() -> Arc<LanguageDetector>,
+{
+    check_clipboard_size(text.len(), config.max_clipboard_bytes)?;
+
+    let source_lang = resolve_source_language(
+        config.forced_source_language,
+        config.detection_enabled,
+        || {
+            detect_source_language(
+                detector(),
+                text,
+                DETECTION_SAMPLE_CHARS,
+                DETECTION_TIMEOUT,
+                DETECTION_MIN_CONFIDENCE,
+                primary_bias(config),
+            )
+        },
+    )
+    .await
+    .map(|detection| detection.language);
+    let target_lang = startup_target_language(source_lang, config, settings);
+
+    // A single translation, so no rate limiter needed
+    let config = config_for_pair(source_lang, target_lang, config);
+    let translation =
+        translate_long_text(text, source_lang, target_lang, api_key, &config, None).await?;
+    Ok((target_lang, translation))
+}
+
+// Read the clipboard, translate it and write the translation back.
+//
+// GDK clipboard operations only complete while the GLib main context is being
+// iterated. Without a GtkApplication main loop, MainContext::block_on provides
+// that minimal loop: it iterates the default context until the future finishes.
+pub fn run_silent(config: &Config) -> Result<(), String> {
+    gtk::init().map_err(|e| format!("Failed to initialize GTK: {}", e))?;
+    let display = gdk::Display::default().ok_or("Could not get default display")?;
+    let clipboard = display.clipboard();
+
+    glib::MainContext::default().block_on(async {
+        let text = read_clipboard_text(&clipboard, config.accept_html_clipboard)
+            .await
+            .map_err(|e| e.to_string())?;
+        let text = resolve_link(text, config).await?;
+
+        let api_key = match resolve_api_key(config) {
+            Ok(key) => key,
+            Err(_) if config.mock => String::new(), // Mock translations need no key
+            Err(message) => return Err(message),
+        };
+
+        let settings = settings::load_settings();
+        let (target_lang, translation) =
+            translate_silently(&text, config, &settings, api_key, || build_detector(config))
+                .await?;
+
+        write_clipboard_text(&clipboard, &translation, config.private_mode);
+        // Hand the text over to the clipboard manager, otherwise it disappears when we exit
+        if let Err(e) = clipboard.store_future(glib::Priority::DEFAULT).await {
+            eprintln!("Failed to store clipboard contents: {}", e);
+        }
+        println!("Translated to {:?} and copied to clipboard", target_lang);
+        Ok(())
+    })
+}
@@ -1,119 +1,1517 @@
 // Use lingua::Language directly
-use async_openai::{
-    config::OpenAIConfig,
-    error::OpenAIError,
-    types::{
-        ChatCompletionRequestSystemMessageArgs, ChatCompletionRequestUserMessageArgs,
-        CreateChatCompletionRequestArgs,
-    },
-    Client,
+use crate::api_key::api_key_source;
+use crate::config::Config;
+use crate::memory;
+use crate::ui_strings;
+use async_openai::types::{
+    ChatCompletionRequestSystemMessageArgs, ChatCompletionRequestUserMessageArgs,
+    CreateChatCompletionRequest, CreateChatCompletionRequestArgs, CreateChatCompletionResponse,
+    ResponseFormat, Stop,
 };
-use gtk::Label;
+use gtk::prelude::*;
+use gtk::{Justification, Label, TextDirection, TextView};
 use lingua::Language;
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use tokio::time::{sleep, Duration, Instant};
+
+// Longest response body excerpt included in error messages
+const MAX_ERROR_BODY_CHARS: usize = 500;
 
 // Result type for translations
 pub type TranslationResult = Result<String, String>;
 
+// --- Rate limiting ---
+
+// Token bucket state shared by all clones of a RateLimiter
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    refill_per_sec: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    // Add the tokens accumulated since the last refill, up to capacity
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.last_refill = now;
+    }
+}
+
+// Simple token-bucket limiter to stay under the API's per-minute request cap.
+// Cheap to clone; all clones share the same bucket.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    bucket: Option<Arc<Mutex<TokenBucket>>>, // None means unlimited
+}
+
+impl RateLimiter {
+    // Create a limiter allowing `requests_per_minute` requests, starting with a full bucket.
+    // A value of 0 disables limiting.
+    pub fn per_minute(requests_per_minute: u32) -> Self {
+        if requests_per_minute == 0 {
+            return RateLimiter { bucket: None };
+        }
+        let capacity = requests_per_minute as f64;
+        RateLimiter {
+            bucket: Some(Arc::new(Mutex::new(TokenBucket {
+                capacity,
+                tokens: capacity,
+                refill_per_sec: capacity / 60.0,
+                last_refill: Instant::now(),
+            }))),
+        }
+    }
+
+    // Check whether a request could be issued right now without waiting
+    pub fn has_token(&self) -> bool {
+        match &self.bucket {
+            Some(bucket) => {
+                let mut bucket = bucket.lock().unwrap();
+                bucket.refill();
+                bucket.tokens >= 1.0
+            }
+            None => true,
+        }
+    }
+
+    // Wait until a token is available and consume it
+    pub async fn acquire(&self) {
+        let Some(bucket) = &self.bucket else {
+            return;
+        };
+        loop {
+            let wait = {
+                let mut bucket = bucket.lock().unwrap();
+                bucket.refill();
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    return;
+                }
+                // Time until the bucket holds one full token again
+                Duration::from_secs_f64((1.0 - bucket.tokens) / bucket.refill_per_sec)
+            }; // Lock is released before sleeping
+            sleep(wait).await;
+        }
+    }
+}
+
+// Default system prompt instructing the model to translate into the target language
+fn default_system_prompt(target_language: Language, json_response: bool) -> String {
+    if json_response {
+        format!("You are a helpful assistant that translates text into {}. Respond with a JSON object of the form {{\"translation\": \"...\"}} containing only the translation text.", target_language)
+    } else {
+        format!("You are a helpful assistant that translates text into {}. Provide only the translation text and nothing else.", target_language)
+    }
+}
+
+// System prompt in proofread mode, for any target language: the text stays in its language
+fn proofread_system_prompt(json_response: bool) -> String {
+    if json_response {
+        "You are a helpful assistant that proofreads text. Correct its grammar, spelling and style, keeping its language and meaning. Respond with a JSON object of the form {\"translation\": \"...\"} containing only the corrected text.".to_string()
+    } else {
+        "You are a helpful assistant that proofreads text. Correct its grammar, spelling and style, keeping its language and meaning. Provide only the corrected text and nothing else.".to_string()
+    }
+}
+
+// Instruction added to the default prompt for the configured register.
+// None for "neutral" and unknown values, so the model picks the form of address.
+pub fn register_instruction(register: &str) -> Option<&'static str> {
+    match register.trim().to_ascii_lowercase().as_str() {
+        "formal" => Some("Use the formal form of address (e.g. Sie in German, vous in French)."),
+        "informal" => Some("Use the informal form of address (e.g. du in German, tu in French)."),
+        _ => None,
+    }
+}
+
+// Sentence telling the model what field the text is from, e.g. "medical".
+// None when no domain is configured or it is blank.
+pub fn domain_hint(domain: Option<&str>) -> Option<String> {
+    domain
+        .map(str::trim)
+        .filter(|domain| !domain.is_empty())
+        .map(|domain| format!("This text is from the {} domain.", domain))
+}
+
+// Markers around the parts of the user message when context is configured
+pub const CONTEXT_BEFORE_MARKER: &str = "[Context before, do not translate]";
+pub const TEXT_MARKER: &str = "[Text to translate]";
+pub const CONTEXT_AFTER_MARKER: &str = "[Context after, do not translate]";
+
+// Added to the system prompt when the user message contains context
+pub const CONTEXT_INSTRUCTION: &str = "The user message marks surrounding text as context. \
+     Use the context only to understand the text, and reply with the translation of the part \
+     marked as text to translate alone.";
+
+// Blank context counts as none
+fn context_part(context: Option<&str>) -> Option<&str> {
+    context.map(str::trim).filter(|context| !context.is_empty())
+}
+
+// Whether context_prefix or context_suffix is set
+pub fn has_context(config: &Config) -> bool {
+    context_part(config.context_prefix.as_deref()).is_some()
+        || context_part(config.context_suffix.as_deref()).is_some()
+}
+
+// User message for the text: the text itself, or the text between the configured context,
+// each part under its marker
+pub fn user_message(text: &str, prefix: Option<&str>, suffix: Option<&str>) -> String {
+    let (prefix, suffix) = (context_part(prefix), context_part(suffix));
+    if prefix.is_none() && suffix.is_none() {
+        return text.to_string();
+    }
+    let mut message = String::new();
+    if let Some(prefix) = prefix {
+        message.push_str(&format!("{}\n{}\n\n", CONTEXT_BEFORE_MARKER, prefix));
+    }
+    message.push_str(&format!("{}\n{}", TEXT_MARKER, text));
+    if let Some(suffix) = suffix {
+        message.push_str(&format!("\n\n{}\n{}", CONTEXT_AFTER_MARKER, suffix));
+    }
+    message
+}
+
+// Sentence telling the model the language of the text, None if it is unknown
+pub fn source_language_hint(source_language: Option<Language>) -> Option<String> {
+    source_language.map(|source| format!("The source text is in {}.", source))
+}
+
+// System prompt for the target language: the configured per-language prompt if any,
+// otherwise the default one with the register instruction, followed by the source language
+// (with hint_source_language), the domain hint and, if context is configured, how to treat it.
+// Per-language prompts are used as written, they can state the register themselves.
+// In proofread mode the proofreading prompt with the hints, whatever the target.
+fn system_prompt(
+    source_language: Option<Language>,
+    target_language: Language,
+    config: &Config,
+) -> String {
+    let mut prompt = if config.proofread() {
+        proofread_system_prompt(config.json_response)
+    } else {
+        base_system_prompt(target_language, config)
+    };
+    if let Some(hint) = source_language_hint(source_language) {
+        if config.hint_source_language {
+            prompt = format!("{} {}", prompt.trim_end(), hint);
+        }
+    }
+    if let Some(hint) = domain_hint(config.domain_hint.as_deref()) {
+        prompt = format!("{} {}", prompt.trim_end(), hint);
+    }
+    if has_context(config) && !config.proofread() {
+        prompt = format!("{} {}", prompt.trim_end(), CONTEXT_INSTRUCTION);
+    }
+    prompt
+}
+
+fn base_system_prompt(target_language: Language, config: &Config) -> String {
+    let iso_code = target_language.iso_code_639_1().to_string();
+    let custom_prompt = config
+        .per_language_prompt
+        .iter()
+        .find(|(code, _)| code.trim().eq_ignore_ascii_case(&iso_code))
+        .map(|(_, prompt)| prompt.replace("{target_language}", &target_language.to_string()));
+
+    match custom_prompt {
+        // The JSON format still has to be requested when using a custom prompt
+        Some(prompt) if config.json_response => format!(
+            "{} Respond with a JSON object of the form {{\"translation\": \"...\"}}.",
+            prompt.trim_end()
+        ),
+        Some(prompt) => prompt,
+        None => {
+            let prompt = default_system_prompt(target_language, config.json_response);
+            match register_instruction(&config.register) {
+                Some(instruction) => format!("{} {}", prompt, instruction),
+                None => prompt,
+            }
+        }
+    }
+}
+
+// Model reply in JSON response mode
+#[derive(Debug, Deserialize)]
+struct JsonTranslation {
+    translation: String,
+}
+
+// Extract the translation from a JSON mode reply
+fn parse_json_translation(content: &str) -> TranslationResult {
+    serde_json::from_str::<JsonTranslation>(content.trim())
+        .map(|reply| reply.translation.trim().to_string())
+        .map_err(|e| {
+            let excerpt: String = content.trim().chars().take(MAX_ERROR_BODY_CHARS).collect();
+            format!(
+                "API Error: Expected a JSON object with a \"translation\" field ({}): {}",
+                e, excerpt
+            )
+        })
+}
+
+// Why a response holds no usable translation.
+// Kept apart so a refusal can be told from a malformed reply; shown to the user via Display.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TranslationError {
+    Refused(String),   // The model declined, with its explanation
+    NoChoices(String), // Empty choices array, with the model named in the response
+    NoContent,         // Content missing or ""
+    BlankContent,      // Content of only whitespace
+    // The API rejected the key: where the key was read from, and the API's error message
+    Auth { key_source: String, detail: String },
+}
+
+impl TranslationError {
+    // Whether the reply was empty, which retry_on_empty retries
+    pub fn is_empty_reply(&self) -> bool {
+        matches!(
+            self,
+            TranslationError::NoContent | TranslationError::BlankContent
+        )
+    }
+}
+
+impl std::fmt::Display for TranslationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TranslationError::Refused(reason) => {
+                write!(f, "The model refused to translate: {}", reason)
+            }
+            TranslationError::NoChoices(model) => {
+                let model = if model.trim().is_empty() {
+                    "the model".to_string()
+                } else {
+                    format!("model '{}'", model)
+                };
+                write!(
+                    f,
+                    "API returned no choices for {}. The reply may have been blocked by a \
+                     content filter, or the model identifier may be wrong: check model_version \
+                     in the config.",
+                    model
+                )
+            }
+            TranslationError::NoContent => write!(f, "API returned no translation content."),
+            TranslationError::BlankContent => {
+                write!(f, "API returned only whitespace instead of a translation.")
+            }
+            TranslationError::Auth { key_source, detail } => write!(
+                f,
+                "Invalid or missing API key \u{2014} check your configuration. \
+                 The key is read from {}.\n{}",
+                key_source, detail
+            ),
+        }
+    }
+}
+
+// Text of the first choice, or why there is none.
+// Some models answer with a refusal or tool calls instead of content.
+pub fn response_content(response: &CreateChatCompletionResponse) -> Result<&str, TranslationError> {
+    let Some(choice) = response.choices.first() else {
+        return Err(TranslationError::NoChoices(response.model.clone()));
+    };
+    if let Some(refusal) = choice.message.refusal.as_deref() {
+        if !refusal.trim().is_empty() {
+            return Err(TranslationError::Refused(refusal.trim().to_string()));
+        }
+    }
+    match choice.message.content.as_deref() {
+        Some(content) if !content.trim().is_empty() => Ok(content),
+        Some(content) if !content.is_empty() => Err(TranslationError::BlankContent),
+        _ => {
+            // Unexpected shape, log everything so it can be looked into
+            eprintln!("Response choice without content: {:?}", choice);
+            Err(TranslationError::NoContent)
+        }
+    }
+}
+
+// Token counts reported by the API
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenUsage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub total_tokens: u32,
+}
+
+// Appended to translations that were cut off at max_tokens
+pub const TRUNCATION_MARKER: &str = "…(truncated, raise max_tokens)";
+
+// Appended to translations whose input was cut off at translation_max_chars
+pub const INPUT_TRUNCATED_NOTE: &str = "…(truncated)";
+
+// Ok if clipboard text of `text_len` bytes is small enough to send for translation
+// (`max_bytes` 0 means no limit), otherwise the message to show instead
+pub fn check_clipboard_size(text_len: usize, max_bytes: usize) -> Result<(), String> {
+    if max_bytes == 0 || text_len <= max_bytes {
+        return Ok(());
+    }
+    Err(format!(
+        "Clipboard text is too large to translate ({} bytes, limit is {} bytes). Increase 'max_clipboard_bytes' in the config to translate it anyway.",
+        text_len, max_bytes
+    ))
+}
+
+// The start of `text` within `max_chars` characters, cut at the last whitespace before the
+// limit (or at the limit if the text has none there). None if the text fits.
+pub fn truncate_input(text: &str, max_chars: usize) -> Option<&str> {
+    let (end, next) = text.char_indices().nth(max_chars)?;
+    let head = &text[..end];
+    if next.is_whitespace() {
+        return Some(head.trim_end());
+    }
+    match head.rfind(char::is_whitespace) {
+        Some(word_start) if !head[..word_start].trim().is_empty() => {
+            Some(head[..word_start].trim_end())
+        }
+        _ => Some(head),
+    }
+}
+
+// The translation with INPUT_TRUNCATED_NOTE appended
+pub fn with_input_truncated_note(translation: &str) -> String {
+    format!("{} {}", translation.trim_end(), INPUT_TRUNCATED_NOTE)
+}
+
+// The text to send under config.translation_max_chars, and whether it was cut
+fn limit_input<'a>(text: &'a str, config: &Config) -> (&'a str, bool) {
+    match config
+        .translation_max_chars
+        .filter(|&max_chars| max_chars > 0)
+        .and_then(|max_chars| truncate_input(text, max_chars))
+    {
+        Some(head) => {
+            println!(
+                "Input exceeds translation_max_chars ({} characters), translating the first {}",
+                text.chars().count(),
+                head.chars().count()
+            );
+            (head, true)
+        }
+        None => (text, false),
+    }
+}
+
+// A translation plus response metadata, for the debug panel
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TranslationDetails {
+    pub text: String,
+    pub finish_reason: Option<String>, // "stop", or "length" if the output was cut off
+    pub usage: Option<TokenUsage>,     // None for dry runs, mock translations and some APIs
+}
+
+impl TranslationDetails {
+    fn text_only(text: String) -> Self {
+        TranslationDetails {
+            text,
+            ..TranslationDetails::default()
+        }
+    }
+
+    // Short description of the response metadata
+    pub fn summary(&self) -> String {
+        let finish_reason = match self.finish_reason.as_deref() {
+            Some("length") => "length (output was truncated)".to_string(),
+            Some(reason) => reason.to_string(),
+            None => "n/a".to_string(),
+        };
+        let usage = match self.usage {
+            Some(usage) => format!(
+                "{} prompt + {} completion = {} total",
+                usage.prompt_tokens, usage.completion_tokens, usage.total_tokens
+            ),
+            None => "n/a".to_string(),
+        };
+        format!("finish_reason: {}\ntokens: {}", finish_reason, usage)
+    }
+
+    // Whether the model stopped at max_tokens, so the translation is incomplete
+    pub fn is_truncated(&self) -> bool {
+        self.finish_reason.as_deref() == Some("length")
+    }
+
+    // Text to show: the translation, with TRUNCATION_MARKER appended if it was cut off
+    pub fn display_text(&self) -> String {
+        if self.is_truncated() {
+            format!("{} {}", self.text, TRUNCATION_MARKER)
+        } else {
+            self.text.clone()
+        }
+    }
+
+    // Fold in the details of the next chunk of a chunked translation
+    fn merge_chunk(&mut self, chunk: TranslationDetails) {
+        self.text.push_str(&chunk.text);
+        // Keep the first unusual finish reason, a truncated chunk matters more than the rest
+        if self.finish_reason.is_none() || self.finish_reason.as_deref() == Some("stop") {
+            self.finish_reason = chunk.finish_reason;
+        }
+        self.usage = match (self.usage, chunk.usage) {
+            (Some(total), Some(usage)) => Some(TokenUsage {
+                prompt_tokens: total.prompt_tokens + usage.prompt_tokens,
+                completion_tokens: total.completion_tokens + usage.completion_tokens,
+                total_tokens: total.total_tokens + usage.total_tokens,
+            }),
+            (total, usage) => total.or(usage),
+        };
+    }
+}
+
 // Core translation function without UI dependencies
 pub async fn translate_text(
     text_to_translate: &str,
+    source_language: Option<Language>,
     target_language: Language,
     api_key: String,
-    api_url: String,
-    model_version: String,
+    config: &Config,
+    rate_limiter: Option<&RateLimiter>,
 ) -> TranslationResult {
+    translate_text_with_details(
+        text_to_translate,
+        source_language,
+        target_language,
+        api_key,
+        config,
+        rate_limiter,
+    )
+    .await
+    .map(|details| details.text)
+}
+
+// Like translate_text, but also returns the finish reason and token usage
+pub async fn translate_text_with_details(
+    text_to_translate: &str,
+    source_language: Option<Language>,
+    target_language: Language,
+    api_key: String,
+    config: &Config,
+    rate_limiter: Option<&RateLimiter>,
+) -> Result<TranslationDetails, String> {
+    let (text_to_send, input_truncated) = limit_input(text_to_translate, config);
+    let mut details = translate_single_text(
+        text_to_send,
+        source_language,
+        target_language,
+        api_key,
+        config,
+        rate_limiter,
+    )
+    .await?;
+    if input_truncated {
+        details.text = with_input_truncated_note(&details.text);
+    }
+    Ok(details)
+}
+
+// One request for the whole text, without the translation_max_chars limit
+async fn translate_single_text(
+    text_to_translate: &str,
+    source_language: Option<Language>,
+    target_language: Language,
+    api_key: String,
+    config: &Config,
+    rate_limiter: Option<&RateLimiter>,
+) -> Result<TranslationDetails, String> {
     // Check if text is empty before making API call
     if text_to_translate.trim().is_empty() {
         return Err("Clipboard text is empty.".to_string());
     }
 
-    // Configure API Client using provided URL
-    let config = OpenAIConfig::new()
-        .with_api_key(api_key)
-        .with_api_base(api_url);
+    // Hard line breaks, e.g. from PDFs, would make the model translate line by line
+    let normalized_text;
+    let text_to_translate = if config.normalize_newlines {
+        normalized_text = normalize_text(text_to_translate);
+        normalized_text.as_str()
+    } else {
+        text_to_translate
+    };
 
-    let client = Client::with_config(config);
+    let mut system_message = system_prompt(source_language, target_language, config);
+    // Earlier translations of this session, so terms come out the same way
+    let use_memory = config.use_translation_memory && !config.proofread();
+    if use_memory {
+        let examples = memory::session_examples(text_to_translate, target_language);
+        if let Some(instruction) = memory::memory_instruction(&examples) {
+            system_message = format!("{}\n\n{}", system_message.trim_end(), instruction);
+        }
+    }
+    // The context goes along in the user message, only the text itself is translated.
+    // Proofreading sends the text alone.
+    let message = if config.proofread() {
+        text_to_translate.to_string()
+    } else {
+        user_message(
+            text_to_translate,
+            config.context_prefix.as_deref(),
+            config.context_suffix.as_deref(),
+        )
+    };
 
-    // Create Translation Request using provided model version
-    let request_result = CreateChatCompletionRequestArgs::default()
-        .max_tokens(1024u16)
-        .model(model_version)
+    // In dry-run mode show what would be sent instead of calling the API
+    if config.dry_run {
+        return Ok(TranslationDetails::text_only(format!(
+            "[Dry run] Model: {}\nURL: {}\n\nSystem:\n{}\n\nUser:\n{}",
+            config.model_version,
+            chat_completions_url(&config.api_url),
+            system_message,
+            message
+        )));
+    }
+
+    // Offline mock translation, e.g. for demos without an API key
+    if config.mock {
+        return Ok(TranslationDetails::text_only(mock_translation(
+            text_to_translate,
+            target_language,
+        )));
+    }
+
+    let max_tokens = cap_max_tokens(
+        if config.auto_max_tokens {
+            auto_max_tokens(text_to_translate.chars().count(), config.max_tokens_ceiling)
+        } else {
+            config.max_tokens
+        },
+        config,
+    );
+    let mut details = request_completion(
+        &message,
+        &system_message,
+        &api_key,
+        max_tokens,
+        config,
+        rate_limiter,
+    )
+    .await?;
+
+    // One more try with room for a longer reply instead of showing a cut-off translation,
+    // unless the model's cap leaves no room
+    let extended_max_tokens = cap_max_tokens(max_tokens.saturating_mul(2), config);
+    if details.is_truncated()
+        && config.auto_extend_on_truncation
+        && extended_max_tokens > max_tokens
+    {
+        println!(
+            "Translation was truncated at {} tokens, retrying with {}",
+            max_tokens, extended_max_tokens
+        );
+        details = request_completion(
+            &message,
+            &system_message,
+            &api_key,
+            extended_max_tokens,
+            config,
+            rate_limiter,
+        )
+        .await?;
+    }
+
+    if config.strip_preamble {
+        details.text = strip_preamble(&details.text).to_string();
+    }
+    if config.fix_capitalization {
+        details.text = fix_capitalization(text_to_translate, &details.text);
+    }
+    if use_memory && !details.is_truncated() {
+        memory::remember(target_language, text_to_translate, &details.text);
+    }
+    Ok(details)
+}
+
+// max_tokens limited to the cap configured for the model in model_max_tokens, if any,
+// as requests above a model's output limit are rejected
+pub fn cap_max_tokens(max_tokens: u16, config: &Config) -> u16 {
+    match config.model_max_tokens.get(config.model_version.trim()) {
+        Some(&cap) => max_tokens.min(cap),
+        None => max_tokens,
+    }
+}
+
+// Model for a translation from `source` into `target`: the one model_for_pair maps the
+// "SRC-DST" pair to (any case), else model_version. An unknown source matches no pair.
+pub fn model_for_pair(source: Option<Language>, target: Language, config: &Config) -> &str {
+    let Some(source) = source else {
+        return &config.model_version;
+    };
+    let pair = format!("{}-{}", source.iso_code_639_1(), target.iso_code_639_1());
+    config
+        .model_for_pair
+        .iter()
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case(&pair))
+        .map_or(config.model_version.as_str(), |(_, model)| model.as_str())
+}
+
+// The config with model_version set to the pair's model, so the request and
+// model_max_tokens use it
+pub fn config_for_pair(source: Option<Language>, target: Language, config: &Config) -> Config {
+    Config {
+        model_version: model_for_pair(source, target, config).to_string(),
+        ..config.clone()
+    }
+}
+
+// Smallest max_tokens chosen by auto_max_tokens, so short texts still fit a reply that is
+// longer than the original (e.g. English into German)
+pub const MIN_AUTO_MAX_TOKENS: u16 = 256;
+
+// max_tokens for a text of `input_chars` characters: half the character count, as a token is
+// a few characters in most languages, between MIN_AUTO_MAX_TOKENS and `ceiling`
+pub fn auto_max_tokens(input_chars: usize, ceiling: u16) -> u16 {
+    let tokens = u16::try_from(input_chars / 2).unwrap_or(u16::MAX);
+    tokens.max(MIN_AUTO_MAX_TOKENS).min(ceiling)
+}
+
+// Chat completion request for one text, with the options from the config
+pub fn build_chat_request(
+    user_content: &str,
+    system_message: &str,
+    max_tokens: u16,
+    config: &Config,
+) -> Result<CreateChatCompletionRequest, String> {
+    // Create Translation Request using configured model version
+    let mut request_args = CreateChatCompletionRequestArgs::default();
+    request_args
+        .max_tokens(max_tokens)
+        .model(config.model_version.clone())
         .messages([
             ChatCompletionRequestSystemMessageArgs::default()
-                .content(format!("You are a helpful assistant that translates text into {}. Provide only the translation text and nothing else.", target_language))
+                .content(system_message.to_string())
                 .build()
                 .map_err(|e| format!("Failed to build system message: {}", e))?
                 .into(),
             ChatCompletionRequestUserMessageArgs::default()
-                .content(text_to_translate.to_string())
+                .content(user_content.to_string())
                 .build()
                 .map_err(|e| format!("Failed to build user message: {}", e))?
                 .into(),
-        ])
-        .build();
-
-    match request_result {
-        Ok(request) => {
-            // Call API
-            match client.chat().create(request).await {
-                Ok(response) => {
-                    if let Some(choice) = response.choices.first() {
-                        if let Some(translated_text) = &choice.message.content {
-                            Ok(translated_text.trim().to_string())
-                        } else {
-                            Err("API returned no translation content.".to_string())
-                        }
-                    } else {
-                        Err("API returned no choices.".to_string())
-                    }
-                }
-                Err(e) => {
-                    // Provide more specific error feedback if possible
-                    let error_message = match e {
-                        OpenAIError::ApiError(api_err) => format!(
-                            "API Error: {} (Type: {:?}, Code: {:?})",
-                            api_err.message, api_err.r#type, api_err.code
-                        ),
-                        OpenAIError::Reqwest(req_err) => format!("Network Error: {}", req_err),
-                        _ => format!("API Error: {}", e),
-                    };
-                    Err(error_message)
+        ]);
+    if config.json_response {
+        request_args.response_format(ResponseFormat::JsonObject);
+    }
+    // Omitted when empty, some APIs reject an empty list
+    if !config.stop_sequences.is_empty() {
+        request_args.stop(Stop::StringArray(config.stop_sequences.clone()));
+    }
+    if let Some(seed) = config.seed {
+        request_args.seed(seed);
+    }
+    request_args
+        .build()
+        .map_err(|e| format!("Error building request: {}", e))
+}
+
+// JSON body of a chat request. OpenRouter's provider routing has no field in
+// CreateChatCompletionRequest, so it is added as "provider" here; a provider_routing that
+// isn't an object is left out (Config::validate warns about it).
+pub fn request_body(
+    request: &CreateChatCompletionRequest,
+    config: &Config,
+) -> Result<serde_json::Value, String> {
+    let mut body =
+        serde_json::to_value(request).map_err(|e| format!("Error building request: {}", e))?;
+    if let (Some(routing), Some(fields)) = (
+        config
+            .provider_routing
+            .as_ref()
+            .filter(|routing| routing.is_object()),
+        body.as_object_mut(),
+    ) {
+        fields.insert("provider".to_string(), routing.clone());
+    }
+    Ok(body)
+}
+
+// Send one chat completion request for the translation and read the reply
+async fn request_completion(
+    user_content: &str,
+    system_message: &str,
+    api_key: &str,
+    max_tokens: u16,
+    config: &Config,
+    rate_limiter: Option<&RateLimiter>,
+) -> Result<TranslationDetails, String> {
+    let request = build_chat_request(user_content, system_message, max_tokens, config)?;
+    let body = request_body(&request, config)?;
+
+    // Call API, again if the reply is empty and retry_on_empty allows it
+    let client = build_http_client(config)?;
+    let mut retries_left = if config.retry_on_empty {
+        config.max_retries
+    } else {
+        0
+    };
+    let response = loop {
+        // Wait for a free slot so we stay under the API's rate limit
+        if let Some(limiter) = rate_limiter {
+            limiter.acquire().await;
+        }
+        let response = send_chat_request(&client, config, api_key, &body).await?;
+        match response_content(&response) {
+            Err(error) if error.is_empty_reply() && retries_left > 0 => {
+                retries_left -= 1;
+                println!("{} Retrying ({} retries left)", error, retries_left);
+            }
+            _ => break response,
+        }
+    };
+    let translated_text = response_content(&response).map_err(|e| e.to_string())?;
+    let choice = &response.choices[0]; // response_content checked there is one
+    let text = if config.json_response {
+        parse_json_translation(translated_text)?
+    } else {
+        translated_text.trim().to_string()
+    };
+
+    Ok(TranslationDetails {
+        text,
+        // Serialized form, e.g. "length" rather than the enum variant name
+        finish_reason: choice.finish_reason.as_ref().and_then(|reason| {
+            serde_json::to_value(reason)
+                .ok()
+                .and_then(|value| value.as_str().map(str::to_string))
+        }),
+        usage: response.usage.as_ref().map(|usage| TokenUsage {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            total_tokens: usage.total_tokens,
+        }),
+    })
+}
+
+// Fake translation used in mock mode: the input prefixed with the target language
+fn mock_translation(text_to_translate: &str, target_language: Language) -> String {
+    format!("[{}] {}", target_language, text_to_translate.trim())
+}
+
+// --- HTTP layer ---
+
+// Error body returned by OpenAI-compatible APIs
+#[derive(Debug, Deserialize)]
+struct ApiErrorBody {
+    error: ApiErrorDetail,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiErrorDetail {
+    message: String,
+    #[serde(default, rename = "type")]
+    error_type: Option<String>,
+    #[serde(default)]
+    param: Option<String>,
+    // A string for OpenAI, a number for OpenRouter
+    #[serde(default)]
+    code: Option<serde_json::Value>,
+    // OpenRouter puts upstream provider details here
+    #[serde(default)]
+    metadata: Option<serde_json::Value>,
+}
+
+// Whether a non-success HTTP response means the API key was rejected: HTTP 401, or an
+// "invalid_api_key" error type or code (OpenAI uses the code)
+pub fn is_auth_failure(status: reqwest::StatusCode, body: &str) -> bool {
+    if status == reqwest::StatusCode::UNAUTHORIZED {
+        return true;
+    }
+    let Ok(ApiErrorBody { error }) = serde_json::from_str::<ApiErrorBody>(body) else {
+        return false;
+    };
+    let is_invalid_key = |value: &str| value.eq_ignore_ascii_case("invalid_api_key");
+    error.error_type.as_deref().is_some_and(is_invalid_key)
+        || error
+            .code
+            .as_ref()
+            .and_then(serde_json::Value::as_str)
+            .is_some_and(is_invalid_key)
+}
+
+// Build a user-facing message for a non-success HTTP response,
+// keeping the status and whatever detail the body provides
+fn format_api_error(status: reqwest::StatusCode, body: &str) -> String {
+    match serde_json::from_str::<ApiErrorBody>(body) {
+        Ok(ApiErrorBody { error }) => {
+            let code = error.code.map(|code| match code {
+                serde_json::Value::String(code) => code,
+                other => other.to_string(),
+            });
+            let mut message = format!(
+                "API Error: {} (HTTP {}, Type: {:?}, Code: {:?}, Param: {:?})",
+                error.message, status, error.error_type, code, error.param
+            );
+            if let Some(metadata) = error.metadata {
+                message.push_str(&format!(" Details: {}", metadata));
+            }
+            message
+        }
+        Err(_) => {
+            // Not a JSON error object; show the raw body instead
+            let excerpt: String = body.trim().chars().take(MAX_ERROR_BODY_CHARS).collect();
+            format!("API Error: HTTP {}: {}", status, excerpt)
+        }
+    }
+}
+
+fn format_network_error(err: reqwest::Error) -> String {
+    match err.status() {
+        Some(status) => format!("Network Error: {} (HTTP {})", err, status),
+        None => format!("Network Error: {}", err),
+    }
+}
+
+fn chat_completions_url(api_url: &str) -> String {
+    format!("{}/chat/completions", api_url.trim_end_matches('/'))
+}
+
+// User-Agent sent with API requests unless the config sets one
+pub const DEFAULT_USER_AGENT: &str = concat!("translator/", env!("CARGO_PKG_VERSION"));
+
+// User-Agent for API requests: the configured one, or DEFAULT_USER_AGENT if unset or blank
+pub fn user_agent(config: &Config) -> &str {
+    config
+        .user_agent
+        .as_deref()
+        .map(str::trim)
+        .filter(|agent| !agent.is_empty())
+        .unwrap_or(DEFAULT_USER_AGENT)
+}
+
+// HTTP client for API calls, routed through the configured proxy if any.
+// Without one, reqwest picks up HTTPS_PROXY / HTTP_PROXY from the environment.
+pub fn build_http_client(config: &Config) -> Result<reqwest::Client, String> {
+    let mut builder = reqwest::Client::builder().user_agent(user_agent(config));
+    if let Some(proxy_url) = config
+        .proxy_url
+        .as_deref()
+        .map(str::trim)
+        .filter(|url| !url.is_empty())
+    {
+        let proxy = reqwest::Proxy::all(proxy_url)
+            .map_err(|e| format!("Invalid proxy URL '{}': {}", proxy_url, e))?;
+        builder = builder.proxy(proxy);
+    }
+    builder
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {}", e))
+}
+
+// POST the request to the chat completions endpoint of an OpenAI-compatible API.
+// async-openai is only used for the request and response types: its Client reports a failed
+// request without the HTTP status or the raw body (both needed for the error message and
+// is_auth_failure), and it can't send fields outside its types, such as "provider". The
+// proxy and User-Agent are set on the reqwest client by build_http_client.
+async fn send_chat_request(
+    client: &reqwest::Client,
+    config: &Config,
+    api_key: &str,
+    body: &serde_json::Value,
+) -> Result<CreateChatCompletionResponse, String> {
+    let response = client
+        .post(chat_completions_url(&config.api_url))
+        .bearer_auth(api_key)
+        .json(body)
+        .send()
+        .await
+        .map_err(format_network_error)?;
+
+    let status = response.status();
+    let body = response.text().await.map_err(format_network_error)?;
+    if !status.is_success() {
+        let message = format_api_error(status, &body);
+        if is_auth_failure(status, &body) {
+            let error = TranslationError::Auth {
+                key_source: api_key_source(config),
+                detail: message,
+            };
+            return Err(error.to_string());
+        }
+        return Err(message);
+    }
+
+    serde_json::from_str(&body).map_err(|e| format!("API Error: Failed to parse response: {}", e))
+}
+
+// Capitalize the first letter of the translation if the original starts with a capital,
+// as some models lowercase it. Scripts without case (CJK, Arabic, ...) are left alone,
+// since their letters are neither upper- nor lowercase.
+pub fn fix_capitalization(original: &str, translation: &str) -> String {
+    let starts_uppercase = original
+        .chars()
+        .find(|c| c.is_alphabetic())
+        .is_some_and(char::is_uppercase);
+    let first_letter = translation.char_indices().find(|(_, c)| c.is_alphabetic());
+    match first_letter {
+        Some((index, letter)) if starts_uppercase && letter.is_lowercase() => {
+            let rest = &translation[index + letter.len_utf8()..];
+            format!("{}{}{}", &translation[..index], letter.to_uppercase(), rest)
+        }
+        _ => translation.to_string(),
+    }
+}
+
+// Words a model's lead-in sentence starts with, lowercase
+const PREAMBLE_OPENERS: &[&str] = &[
+    "here is",
+    "here's",
+    "here\u{2019}s",
+    "below is",
+    "sure",
+    "certainly",
+    "of course",
+];
+
+// Labels a model puts in front of the translation on the same line
+const PREAMBLE_LABELS: &[&str] = &["translation:", "translated text:"];
+
+// Whether a line only announces the translation, e.g. "Here is the translation:" or
+// "French translation:". It has to end with a colon and mention the translation.
+fn is_preamble_line(line: &str) -> bool {
+    let Some(line) = line.trim().strip_suffix(':') else {
+        return false;
+    };
+    let lower = line.to_lowercase();
+    if !lower.contains("translat") {
+        return false;
+    }
+    let words = lower.split_whitespace().count();
+    let is_label = lower == "translation"
+        || lower == "translated text"
+        || (words <= 3
+            && ["translation (", "translation into ", "translation to "]
+                .iter()
+                .any(|label| lower.starts_with(label)))
+        || (words == 2 && lower.ends_with(" translation"));
+    let is_lead_in = words <= 12
+        && PREAMBLE_OPENERS
+            .iter()
+            .any(|opener| lower.starts_with(opener));
+    is_label || is_lead_in
+}
+
+// Remove a lead-in the model put before the translation despite the prompt: a first line
+// like "Here is the translation:" or a "Translation: " label. Text that doesn't clearly
+// announce the translation is kept, and so is a lead-in with nothing after it.
+pub fn strip_preamble(translation: &str) -> &str {
+    let text = translation.trim_start();
+    let (first_line, rest) = text.split_once('\n').unwrap_or((text, ""));
+    let rest = rest.trim_start();
+    if is_preamble_line(first_line) && !rest.is_empty() {
+        return rest;
+    }
+    // "Translation: Bonjour" on a single line
+    for label in PREAMBLE_LABELS {
+        let is_labelled = text
+            .get(..label.len())
+            .is_some_and(|start| start.eq_ignore_ascii_case(label));
+        if is_labelled {
+            let remainder = text[label.len()..].trim_start();
+            if !remainder.is_empty() {
+                return remainder;
+            }
+        }
+    }
+    translation
+}
+
+// --- Chunking for long input ---
+
+// Whether a line starts a list item ("- ", "* ", "• ", "1. ", "2) "), which keeps its own line
+fn starts_list_item(line: &str) -> bool {
+    let line = line.trim_start();
+    if ["- ", "* ", "• "]
+        .iter()
+        .any(|marker| line.starts_with(marker))
+    {
+        return true;
+    }
+    let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    digits > 0 && (line[digits..].starts_with(". ") || line[digits..].starts_with(") "))
+}
+
+// Join lines broken in the middle of a paragraph with a space, as in text copied from PDFs.
+// Blank lines between paragraphs and list items on their own lines are kept.
+pub fn normalize_text(text: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match lines.last_mut() {
+            Some(previous)
+                if !previous.trim().is_empty()
+                    && !line.trim().is_empty()
+                    && !starts_list_item(line) =>
+            {
+                previous.truncate(previous.trim_end().len());
+                previous.push(' ');
+                previous.push_str(line.trim_start());
+            }
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines.join("\n")
+}
+
+// A piece of the input small enough to translate in one request,
+// plus the separator that followed it in the original text
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TextChunk {
+    pub text: String,
+    pub separator: String,
+}
+
+// Find the first paragraph break (a newline followed by at least one more newline,
+// possibly with whitespace in between). Returns the byte range of the break.
+fn find_paragraph_break(text: &str) -> Option<(usize, usize)> {
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\n' {
+            let mut j = i + 1;
+            let mut end = None;
+            while j < bytes.len() && bytes[j].is_ascii_whitespace() {
+                if bytes[j] == b'\n' {
+                    end = Some(j + 1);
                 }
+                j += 1;
+            }
+            if let Some(end) = end {
+                return Some((i, end));
             }
+            i = j;
+        } else {
+            i += 1;
         }
-        Err(e) => Err(format!("Error building request: {}", e)),
+    }
+    None
+}
+
+// Split a paragraph longer than max_chars at whitespace (or hard at the limit if there is none)
+fn split_oversized<'a>(
+    paragraph: &'a str,
+    separator: &'a str,
+    max_chars: usize,
+    units: &mut Vec<(&'a str, &'a str)>,
+) {
+    let mut rest = paragraph;
+    while rest.chars().count() > max_chars {
+        // Byte index of the first character past the limit
+        let limit = rest
+            .char_indices()
+            .nth(max_chars)
+            .map(|(i, _)| i)
+            .unwrap_or(rest.len());
+        // Prefer breaking at the last whitespace before the limit
+        let split_at = rest[..limit]
+            .rfind(char::is_whitespace)
+            .filter(|&i| i > 0)
+            .unwrap_or(limit);
+        let (piece, tail) = rest.split_at(split_at);
+        let whitespace_len = tail.len() - tail.trim_start().len();
+        units.push((piece, &tail[..whitespace_len]));
+        rest = &tail[whitespace_len..];
+    }
+    units.push((rest, separator));
+}
+
+// Split text into chunks of at most max_chars characters, breaking on paragraph
+// boundaries where possible. Concatenating each chunk's text and separator
+// reproduces the original text.
+pub fn split_into_chunks(text: &str, max_chars: usize) -> Vec<TextChunk> {
+    let max_chars = max_chars.max(1);
+
+    // Break the text into paragraph-sized units, each with its trailing separator
+    let mut units = Vec::new();
+    let mut rest = text;
+    while let Some((start, end)) = find_paragraph_break(rest) {
+        split_oversized(&rest[..start], &rest[start..end], max_chars, &mut units);
+        rest = &rest[end..];
+    }
+    split_oversized(rest, "", max_chars, &mut units);
+
+    // Greedily pack units into chunks
+    let mut chunks = Vec::new();
+    let mut current = TextChunk::default();
+    let mut current_len = 0;
+    let mut started = false;
+    for (unit, separator) in units {
+        let unit_len = unit.chars().count();
+        if started && current_len + current.separator.chars().count() + unit_len > max_chars {
+            chunks.push(std::mem::take(&mut current));
+            current_len = 0;
+            started = false;
+        }
+        if started {
+            current_len += current.separator.chars().count();
+            current.text.push_str(&current.separator);
+        }
+        current.text.push_str(unit);
+        current.separator = separator.to_string();
+        current_len += unit_len;
+        started = true;
+    }
+    if started {
+        chunks.push(current);
+    }
+    chunks
+}
+
+// Translate text that may exceed config.max_input_chars by splitting it into chunks,
+// translating them in order and joining the results with the original separators.
+// A max_input_chars of 0 disables chunking.
+pub async fn translate_long_text(
+    text_to_translate: &str,
+    source_language: Option<Language>,
+    target_language: Language,
+    api_key: String,
+    config: &Config,
+    rate_limiter: Option<&RateLimiter>,
+) -> TranslationResult {
+    translate_long_text_with_details(
+        text_to_translate,
+        source_language,
+        target_language,
+        api_key,
+        config,
+        rate_limiter,
+    )
+    .await
+    .map(|details| details.text)
+}
+
+// Like translate_long_text, with the finish reason and token usage of all chunks combined
+pub async fn translate_long_text_with_details(
+    text_to_translate: &str,
+    source_language: Option<Language>,
+    target_language: Language,
+    api_key: String,
+    config: &Config,
+    rate_limiter: Option<&RateLimiter>,
+) -> Result<TranslationDetails, String> {
+    let (text_to_translate, input_truncated) = limit_input(text_to_translate, config);
+    let mut translated = translate_chunked(
+        text_to_translate,
+        source_language,
+        target_language,
+        api_key,
+        config,
+        rate_limiter,
+    )
+    .await?;
+    if input_truncated {
+        translated.text = with_input_truncated_note(&translated.text);
+    }
+    Ok(translated)
+}
+
+// Split text over max_input_chars into chunks and translate them one by one
+async fn translate_chunked(
+    text_to_translate: &str,
+    source_language: Option<Language>,
+    target_language: Language,
+    api_key: String,
+    config: &Config,
+    rate_limiter: Option<&RateLimiter>,
+) -> Result<TranslationDetails, String> {
+    let max_input_chars = config.max_input_chars;
+    if max_input_chars == 0 || text_to_translate.chars().count() <= max_input_chars {
+        return translate_single_text(
+            text_to_translate,
+            source_language,
+            target_language,
+            api_key,
+            config,
+            rate_limiter,
+        )
+        .await;
+    }
+
+    let chunks = split_into_chunks(text_to_translate, max_input_chars);
+    println!(
+        "Input exceeds {} characters, translating in {} chunks",
+        max_input_chars,
+        chunks.len()
+    );
+
+    let mut translated = TranslationDetails::default();
+    for chunk in chunks {
+        if chunk.text.trim().is_empty() {
+            // Nothing to translate, keep the whitespace as is
+            translated.text.push_str(&chunk.text);
+        } else {
+            let translated_chunk = translate_single_text(
+                &chunk.text,
+                source_language,
+                target_language,
+                api_key.clone(),
+                config,
+                rate_limiter,
+            )
+            .await?;
+            translated.merge_chunk(translated_chunk);
+        }
+        translated.text.push_str(&chunk.separator);
+    }
+    translated.text = translated.text.trim().to_string();
+    Ok(translated)
+}
+
+// Translate the text into several languages concurrently.
+// Results are in the order of target_languages, however the requests finish.
+pub async fn translate_into_targets(
+    text_to_translate: &str,
+    source_language: Option<Language>,
+    target_languages: &[Language],
+    api_key: &str,
+    config: &Config,
+    rate_limiter: Option<&RateLimiter>,
+) -> Vec<(Language, TranslationResult)> {
+    let requests = target_languages.iter().map(|&target_language| async move {
+        let config = config_for_pair(source_language, target_language, config);
+        let result = translate_long_text(
+            text_to_translate,
+            source_language,
+            target_language,
+            api_key.to_string(),
+            &config,
+            rate_limiter,
+        )
+        .await;
+        (target_language, result)
+    });
+    futures_util::future::join_all(requests).await
+}
+
+// Translate the text with each of `models` concurrently, for comparing them.
+// Results are in the order of models, however the requests finish; model_for_pair is not used.
+pub async fn translate_with_models(
+    text_to_translate: &str,
+    source_language: Option<Language>,
+    target_language: Language,
+    models: &[String],
+    api_key: &str,
+    config: &Config,
+    rate_limiter: Option<&RateLimiter>,
+) -> Vec<(String, TranslationResult)> {
+    let requests = models.iter().map(|model| async move {
+        let config = Config {
+            model_version: model.clone(),
+            ..config.clone()
+        };
+        let result = translate_long_text(
+            text_to_translate,
+            source_language,
+            target_language,
+            api_key.to_string(),
+            &config,
+            rate_limiter,
+        )
+        .await;
+        (model.clone(), result)
+    });
+    futures_util::future::join_all(requests).await
+}
+
+// --- Output sink for translation results ---
+// Either the read-only label (default) or an editable TextView.
+// Memory keeps the text in a string, for running without a display (e.g. tests).
+#[derive(Debug, Clone)]
+pub enum OutputSink {
+    Label(Label),
+    TextView(TextView),
+    Memory(Rc<RefCell<String>>),
+}
+
+impl OutputSink {
+    // Sink that only stores the text; clones share the same string
+    pub fn memory() -> Self {
+        OutputSink::Memory(Rc::new(RefCell::new(String::new())))
+    }
+
+    pub fn set_text(&self, text: &str) {
+        match self {
+            OutputSink::Label(label) => label.set_text(text),
+            OutputSink::TextView(text_view) => text_view.buffer().set_text(text),
+            OutputSink::Memory(contents) => *contents.borrow_mut() = text.to_string(),
+        }
+    }
+
+    // Current text, including any edits made by the user in the editable view
+    pub fn text(&self) -> String {
+        match self {
+            OutputSink::Label(label) => label.text().to_string(),
+            OutputSink::TextView(text_view) => {
+                let buffer = text_view.buffer();
+                buffer
+                    .text(&buffer.start_iter(), &buffer.end_iter(), false)
+                    .to_string()
+            }
+            OutputSink::Memory(contents) => contents.borrow().clone(),
+        }
+    }
+
+    // Character offsets of the selected text, None if nothing is selected
+    pub fn selection_bounds(&self) -> Option<(i32, i32)> {
+        match self {
+            OutputSink::Label(label) => label.selection_bounds(),
+            OutputSink::TextView(text_view) => text_view
+                .buffer()
+                .selection_bounds()
+                .map(|(start, end)| (start.offset(), end.offset())),
+            OutputSink::Memory(_) => None,
+        }
+    }
+
+    // Lay out the text right-to-left (e.g. for Arabic) or left-to-right
+    pub fn set_rtl(&self, rtl: bool) {
+        let (direction, justification) = if rtl {
+            (TextDirection::Rtl, Justification::Right)
+        } else {
+            (TextDirection::Ltr, Justification::Left)
+        };
+        match self {
+            OutputSink::Label(label) => {
+                label.set_direction(direction);
+                label.set_justify(justification);
+            }
+            OutputSink::TextView(text_view) => {
+                text_view.set_direction(direction);
+                text_view.set_justification(justification);
+            }
+            OutputSink::Memory(_) => {} // No layout to change
+        }
+    }
+}
+
+// Whether the language is written right-to-left
+pub fn is_rtl(language: Language) -> bool {
+    matches!(
+        language,
+        Language::Arabic | Language::Hebrew | Language::Persian | Language::Urdu
+    )
+}
+
+// Result of request_translation, for callers that react to it beyond the output text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TranslationOutcome {
+    Success(TranslationDetails),
+    Failure(String), // The error message shown in the output
+}
+
+impl TranslationOutcome {
+    // Response details of a successful translation, e.g. for the debug panel
+    pub fn details(&self) -> Option<&TranslationDetails> {
+        match self {
+            TranslationOutcome::Success(details) => Some(details),
+            TranslationOutcome::Failure(_) => None,
+        }
+    }
+
+    // Text shown in the output: the translation (marked if truncated) or the error
+    pub fn output_text(&self) -> String {
+        match self {
+            TranslationOutcome::Success(details) => details.display_text(),
+            TranslationOutcome::Failure(message) => message.clone(),
+        }
+    }
+}
+
+// Where the time of a translation went, for diagnosing slowness in the debug panel
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Metrics {
+    pub detection: Option<Duration>, // Source language detection, if the caller detected it
+    pub api_latency: Duration, // Waiting for the API, including rate-limit waits and all chunks
+    pub total: Duration,       // From the start of the caller's flow to the result being shown
+}
+
+impl Metrics {
+    // Adds the time the caller spent detecting the source language before translating, with
+    // the total measured from the caller's start
+    pub fn with_detection(self, detection: Duration, total: Duration) -> Self {
+        Metrics {
+            detection: Some(detection),
+            total: total.max(self.total),
+            ..self
+        }
+    }
+
+    // Short description of the durations, in milliseconds
+    pub fn summary(&self) -> String {
+        let detection = match self.detection {
+            Some(duration) => format!("{} ms", duration.as_millis()),
+            None => "n/a".to_string(),
+        };
+        format!(
+            "detection: {}\napi: {} ms\ntotal: {} ms",
+            detection,
+            self.api_latency.as_millis(),
+            self.total.as_millis()
+        )
     }
 }
 
 // --- Helper function to request translation ---
-// UI wrapper around core translation function
+// UI wrapper around core translation function.
+// Shows the translation or the error in the output and returns it as well, with its timing.
 pub async fn request_translation(
     text_to_translate: String,
+    source_language: Option<Language>,
     target_language: Language,
     api_key: String,
-    api_url: String,
-    model_version: String,
-    label_to_update: Label,
-) {
-    // Update UI to show translation in progress
-    label_to_update.set_label(&format!("Translating to {}...", target_language));
+    config: Config,
+    rate_limiter: RateLimiter,
+    output: OutputSink,
+) -> (TranslationOutcome, Metrics) {
+    // Wall-clock time, also when a test pauses tokio's clock
+    let started = std::time::Instant::now();
 
-    // Call core translation function
-    match translate_text(
+    // Match the output direction to the target language's script
+    output.set_rtl(is_rtl(target_language));
+
+    // Update UI to show translation in progress (or that we have to wait for the rate limit)
+    let max_input_chars = config.max_input_chars;
+    let is_chunked = max_input_chars > 0 && text_to_translate.chars().count() > max_input_chars;
+    let language = ("language", target_language.to_string());
+    if !rate_limiter.has_token() {
+        output.set_text(&config.ui_text(ui_strings::RATE_LIMITED, &[]));
+    } else if is_chunked {
+        output.set_text(&config.ui_text(
+            ui_strings::TRANSLATING_IN_PARTS,
+            &[language, ("max_chars", max_input_chars.to_string())],
+        ));
+    } else {
+        output.set_text(&config.ui_text(ui_strings::TRANSLATING, &[language]));
+    }
+
+    // Call core translation function (splits long text into chunks)
+    let api_started = std::time::Instant::now();
+    let outcome = match translate_long_text_with_details(
         &text_to_translate,
+        source_language,
         target_language,
         api_key,
-        api_url,
-        model_version,
+        &config,
+        Some(&rate_limiter),
     )
     .await
     {
-        Ok(translated_text) => {
-            label_to_update.set_text(&translated_text);
-        }
+        Ok(details) => TranslationOutcome::Success(details),
         Err(error_message) => {
             eprintln!("Translation Error: {}", error_message);
-            label_to_update.set_text(&error_message);
+            TranslationOutcome::Failure(error_message)
         }
-    }
+    };
+    let api_latency = api_started.elapsed();
+    // A cut-off translation must not pass for a complete one
+    output.set_text(&outcome.output_text());
+    let metrics = Metrics {
+        detection: None,
+        api_latency,
+        total: started.elapsed(),
+    };
+    (outcome, metrics)
 }
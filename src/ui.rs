@@ -1,62 +1,491 @@
 use gtk::prelude::*;
 use gtk::{
-    gdk, glib, Align, Application, ApplicationWindow, Box as GtkBox, Button, Label, Orientation,
-    ToggleButton,
+    gdk, gio, glib, Align, Application, ApplicationWindow, Box as GtkBox, Button,
+    EventControllerKey, Expander, Label, Orientation, PolicyType, ScrolledWindow, TextView,
+    ToggleButton, WrapMode,
 };
 use std::cell::RefCell;
-use std::env;
+use std::fmt::Display;
+use std::future::Future;
 use std::rc::Rc;
-use tokio::time::{timeout, Duration};
+use tokio::time::Duration;
 // Use lingua::Language directly
-use lingua::{Language, LanguageDetectorBuilder};
+use lingua::Language;
 
-// Type aliases to reduce complexity
-type LanguageButtonRc = Rc<RefCell<ToggleButton>>;
-type LanguageButtonsVec = Vec<(Language, LanguageButtonRc)>;
+// How long live preview waits after the last keystroke before translating
+const LIVE_PREVIEW_DELAY: Duration = Duration::from_millis(600);
 
+// Length of the translation preview shown in completion notifications
+const NOTIFICATION_PREVIEW_CHARS: usize = 100;
+
+// Detection confidence needed to skip translating text that is already in the target language
+const ALREADY_IN_TARGET_MIN_CONFIDENCE: f64 = 0.9;
+
+use crate::api_key::resolve_api_key;
+use crate::app_state::AppState;
+use crate::clipboard_utils::{read_clipboard_contents, write_clipboard_text};
 use crate::clone;
-use crate::config::Config; // Import Config struct
-use crate::settings; // Import settings module
-use crate::translation::request_translation; // Import the clone macro
+use crate::config::{self, Config}; // Import Config struct
+use crate::debounce::Debouncer;
+use crate::detection::{
+    build_detector, detect_source_language, detection_is_conclusive, detection_needed,
+    needs_full_detection, preload_detector, primary_bias, resolve_source_language,
+    spawn_full_detection, startup_target_language, Detection, DETECTION_MIN_CONFIDENCE,
+    DETECTION_SAMPLE_CHARS, DETECTION_TIMEOUT,
+};
+use crate::link::{clipboard_url, resolve_link};
+use crate::settings::{self, ExitState, LanguageUsage, Settings}; // Import settings module
+use crate::translation::{
+    check_clipboard_size, config_for_pair, is_rtl, request_translation, translate_into_targets,
+    translate_with_models, Metrics, OutputSink, TranslationDetails, TranslationResult,
+}; // Import the clone macro
+use crate::ui_strings;
 
-/// Implements the language selection algorithm from README.md
+/// Target language after the second phase of two-phase detection, if it corrects the first pick
+///
+/// Runs the same selection as at startup with the language detected on the whole text
 ///
 /// # Arguments
 ///
-/// * `source_lang` - The detected source language (Option<Language>)
-/// * `primary_lang` - The user's primary language
-/// * `secondary_lang` - The user's secondary language
-/// * `last_lang` - The last selected target language
+/// * `full_detection` - Detection on the whole text, None if it failed or timed out
+/// * `current_target` - The target picked from the detection on the sample
+/// * `config` - The loaded configuration
+/// * `settings` - The persisted settings as loaded at startup
 ///
 /// # Returns
 ///
-/// The selected target language based on the algorithm
-pub fn choose_target_language(
-    source_lang: Option<Language>,
-    primary_lang: Language,
-    secondary_lang: Language,
+/// The new target if it differs from `current_target` and has a button, None otherwise
+pub fn refined_target_language(
+    full_detection: Option<&Detection>,
+    current_target: Language,
+    config: &Config,
+    settings: &Settings,
+) -> Option<Language> {
+    let full_detection = full_detection?;
+    let target = startup_target_language(Some(full_detection.language), config, settings);
+    (target != current_target && config.all_target_languages.contains(&target)).then_some(target)
+}
+
+/// Schedules saving the startup target language as the last language if automatic selection
+/// changed it. The settings are written up to `SETTINGS_SAVE_DELAY` later, or at exit.
+///
+/// Nothing is written when `auto_select_target` is disabled or the target is pinned, so the
+/// remembered language stays and is used again once the pin is removed
+///
+/// # Arguments
+///
+/// * `target_lang` - The target language chosen at startup
+/// * `config` - The loaded configuration
+/// * `last_lang` - The last target language loaded from the settings
+///
+/// # Returns
+///
+/// True if the new target was scheduled to be saved
+pub fn remember_startup_target(
+    target_lang: Language,
+    config: &Config,
     last_lang: Language,
-) -> Language {
-    // 1. If the source isn't the primary language, translate into the primary language
-    let is_source_primary = source_lang
-        .map(|detected| detected == primary_lang)
-        .unwrap_or(false);
+) -> bool {
+    if !config.auto_select_target {
+        println!("Automatic target selection is disabled -> Not saving the target language");
+        return false;
+    }
+    if config.pinned_target_language.is_some() {
+        println!("Target language is pinned -> Not saving the target language");
+        return false;
+    }
+    if target_lang == last_lang {
+        println!("Target language remains: {:?}", target_lang);
+        return false;
+    }
+    match settings::save_last_language(target_lang) {
+        Ok(()) => {
+            println!(
+                "Target language automatically set to: {:?}, saving it.",
+                target_lang
+            );
+            true
+        }
+        Err(e) => {
+            eprintln!("Failed to save last language after auto-switch: {}", e);
+            false
+        }
+    }
+}
+
+// --- Helper function to find the language the output is translated into ---
+// The target of the startup flow or the last pick, or the last language from the settings
+// before the startup flow chose one
+fn current_target(state: &AppState) -> Language {
+    state
+        .target_language()
+        .unwrap_or_else(settings::load_last_language)
+}
+
+// --- Helper function to show the Undo button after the target was switched automatically ---
+// Not offered in multi-target mode or for a locked pinned target, where the buttons don't
+// pick a single target
+fn offer_switch_undo(
+    state: &AppState,
+    undo_button: &Button,
+    previous_lang: Language,
+    new_lang: Language,
+) {
+    let config = state.config();
+    if config.multi_target || locked_target_language(config).is_some() {
+        return;
+    }
+    match state.record_auto_switch(previous_lang, new_lang) {
+        Some(restore_lang) => {
+            undo_button.set_tooltip_text(Some(&format!("Translate into {} again", restore_lang)));
+            undo_button.set_visible(true);
+        }
+        None => undo_button.set_visible(false),
+    }
+}
+
+/// Runs a future with an overall time budget, dropping it if the budget runs out
+///
+/// # Arguments
+///
+/// * `budget` - Maximum time the future may take (zero means no limit)
+/// * `flow` - The future to run
+///
+/// # Returns
+///
+/// True if the future completed, false if it was cut off
+pub async fn run_with_budget<F: Future<Output = ()>>(budget: Duration, flow: F) -> bool {
+    if budget.is_zero() {
+        flow.await;
+        return true;
+    }
+    tokio::time::timeout(budget, flow).await.is_ok()
+}
+
+/// Formats the ISO code of a language for its button
+///
+/// # Arguments
+///
+/// * `lang` - The language of the button
+/// * `case` - `"upper"` (EN), `"lower"` (en) or `"title"` (En), in any case; unknown values
+///   use upper case
+///
+/// # Returns
+///
+/// The ISO 639-1 code in the requested case
+pub fn format_button_label(lang: Language, case: &str) -> String {
+    let code = lang.iso_code_639_1().to_string();
+    match case.trim().to_ascii_lowercase().as_str() {
+        "lower" => code.to_lowercase(),
+        "title" => {
+            let mut chars = code.chars();
+            chars
+                .next()
+                .map(|first| {
+                    first
+                        .to_uppercase()
+                        .chain(chars.flat_map(char::to_lowercase))
+                        .collect()
+                })
+                .unwrap_or_default()
+        }
+        _ => code.to_uppercase(),
+    }
+}
+
+/// Orders target languages by how often they were picked, most used first
+///
+/// # Arguments
+///
+/// * `target_langs` - Target languages in config order
+/// * `usage` - Usage counts per language
+///
+/// # Returns
+///
+/// The languages sorted by usage; languages used equally often keep their config order
+pub fn sort_languages_by_usage(target_langs: &[Language], usage: &LanguageUsage) -> Vec<Language> {
+    let mut sorted = target_langs.to_vec();
+    // Stable sort, so ties stay in config order
+    sorted.sort_by_key(|lang| std::cmp::Reverse(usage.count(*lang)));
+    sorted
+}
+
+/// Decides whether a finished translation should be announced with a desktop notification
+///
+/// # Arguments
+///
+/// * `notify_on_complete` - The `notify_on_complete` config flag
+/// * `window_active` - Whether the translator window currently has focus
+/// * `details` - The finished translation, or None if it failed
+///
+/// # Returns
+///
+/// True only for successful translations while the window is in the background
+pub fn should_notify(
+    notify_on_complete: bool,
+    window_active: bool,
+    details: Option<&TranslationDetails>,
+) -> bool {
+    notify_on_complete && !window_active && details.is_some()
+}
+
+/// Shortens a translation for the body of a notification
+///
+/// # Arguments
+///
+/// * `text` - The translated text
+/// * `max_chars` - Maximum number of characters to keep
+///
+/// # Returns
+///
+/// The trimmed text, cut after `max_chars` characters with an ellipsis appended
+pub fn notification_preview(text: &str, max_chars: usize) -> String {
+    let text = text.trim();
+    match text.char_indices().nth(max_chars) {
+        Some((cut, _)) => format!("{}…", text[..cut].trim_end()),
+        None => text.to_string(),
+    }
+}
+
+/// Counts the words in a text, splitting on Unicode whitespace
+///
+/// Scripts written without spaces between words (Chinese, Japanese, Thai) count a whole run of
+/// text as one word, so the number is only meaningful for space-separated languages; the
+/// character count is the better measure there.
+///
+/// # Arguments
+///
+/// * `text` - The text to count
+///
+/// # Returns
+///
+/// The number of whitespace-separated words
+pub fn count_words(text: &str) -> usize {
+    text.split_whitespace().count()
+}
+
+/// Describes the length of a text for the count labels
+///
+/// # Arguments
+///
+/// * `text` - The text to describe
+///
+/// # Returns
+///
+/// The word and character count, e.g. "3 words, 17 characters"
+pub fn text_counts(text: &str) -> String {
+    let words = count_words(text);
+    let chars = text.chars().count();
+    format!(
+        "{} word{}, {} character{}",
+        words,
+        if words == 1 { "" } else { "s" },
+        chars,
+        if chars == 1 { "" } else { "s" }
+    )
+}
+
+/// The only language whose button stays clickable, if the pinned target is locked
+///
+/// # Arguments
+///
+/// * `config` - The loaded configuration
+///
+/// # Returns
+///
+/// `pinned_target_language` if `lock_pinned_target` is set, otherwise None
+pub fn locked_target_language(config: &Config) -> Option<Language> {
+    config
+        .pinned_target_language
+        .filter(|_| config.lock_pinned_target)
+}
+
+/// Picks the language button to grey out because it matches the source language
+///
+/// # Arguments
+///
+/// * `source_lang` - The detected source language, if any
+/// * `target_langs` - Languages that have a button
+/// * `active_lang` - The language currently translated into, which always stays clickable
+///
+/// # Returns
+///
+/// The source language if it has a button and isn't the active target, otherwise None
+pub fn source_button_to_disable(
+    source_lang: Option<Language>,
+    target_langs: &[Language],
+    active_lang: Language,
+) -> Option<Language> {
+    source_lang.filter(|source| *source != active_lang && target_langs.contains(source))
+}
 
-    if !is_source_primary {
-        // Rule 1: If source isn't primary language, translate to primary
-        primary_lang
+/// Decides whether the startup translation can be skipped because the text is already in the
+/// target language
+///
+/// # Arguments
+///
+/// * `detection` - The detected (or forced) source language, if any
+/// * `target_lang` - The language the text would be translated into
+/// * `conclusive` - Whether the source was forced or detected among several languages (see
+///   `detection_is_conclusive`); a one-language detector always answers its language
+///
+/// # Returns
+///
+/// True if the source is the target language with high confidence and no close runner-up
+pub fn is_already_in_target(
+    detection: Option<&Detection>,
+    target_lang: Language,
+    conclusive: bool,
+) -> bool {
+    conclusive
+        && detection.is_some_and(|detection| {
+            detection.language == target_lang
+                && detection.confidence >= ALREADY_IN_TARGET_MIN_CONFIDENCE
+                && !detection.is_ambiguous()
+        })
+}
+
+/// Joins the translations of multi-target mode (or of compare_models) into one text for copying
+///
+/// # Arguments
+///
+/// * `results` - Translation results in button order, or model names and their results
+///
+/// # Returns
+///
+/// Each successful translation under its language (or model) name, separated by blank lines;
+/// failed ones are left out
+pub fn format_multi_translations<T: Display>(results: &[(T, TranslationResult)]) -> String {
+    results
+        .iter()
+        .filter_map(|(lang, result)| {
+            result
+                .as_ref()
+                .ok()
+                .map(|text| format!("{}:\n{}", lang, text))
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Picks the text that "Copy & Close" copies: the selected part of the output, if any
+///
+/// # Arguments
+///
+/// * `full_text` - The whole output text
+/// * `selection` - Character offsets of the selection (in either order), if any
+///
+/// # Returns
+///
+/// The selected characters, or the full text if nothing (or an empty range) is selected
+pub fn text_to_copy(full_text: &str, selection: Option<(i32, i32)>) -> String {
+    let Some((start, end)) = selection else {
+        return full_text.to_string();
+    };
+    let (start, end) = (
+        start.min(end).max(0) as usize,
+        start.max(end).max(0) as usize,
+    );
+    if start == end {
+        return full_text.to_string();
+    }
+    full_text.chars().skip(start).take(end - start).collect()
+}
+
+/// Clipboard or translated text as it may appear in the terminal output
+///
+/// # Arguments
+///
+/// * `text` - The text to log
+/// * `private_mode` - The `private_mode` config flag
+///
+/// # Returns
+///
+/// The text itself, or only its length in private mode
+pub fn loggable_text(text: &str, private_mode: bool) -> String {
+    if private_mode {
+        format!("<{} characters hidden>", text.chars().count())
     } else {
-        // Source IS primary language
-        // Rule 2: If there's a meaningful last choice, use it
-        if last_lang != primary_lang {
-            last_lang
-        } else {
-            // Rule 3: Fall back to secondary language
-            secondary_lang
+        text.to_string()
+    }
+}
+
+/// What the copy button does after copying the translation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AfterCopy {
+    /// Close the window ("Copy & Close")
+    Close,
+    /// Keep the window open ("Copy"), e.g. for the next `--trigger`
+    KeepOpen,
+}
+
+impl AfterCopy {
+    /// Label of the copy button
+    pub fn button_label(self) -> &'static str {
+        match self {
+            AfterCopy::Close => "Copy & Close",
+            AfterCopy::KeepOpen => "Copy",
         }
     }
 }
 
+/// Decides whether the copy button closes the window
+///
+/// # Arguments
+///
+/// * `config` - The loaded configuration
+///
+/// # Returns
+///
+/// `AfterCopy::Close` unless `close_after_copy` is disabled
+pub fn after_copy(config: &Config) -> AfterCopy {
+    if config.close_after_copy {
+        AfterCopy::Close
+    } else {
+        AfterCopy::KeepOpen
+    }
+}
+
+/// Converts `label_max_width_chars` to GTK's max-width-chars value
+///
+/// # Arguments
+///
+/// * `max_chars` - The configured width limit in characters, 0 for no limit
+///
+/// # Returns
+///
+/// The limit, or -1 (GTK's "unset") for no limit
+pub fn label_max_width(max_chars: u32) -> i32 {
+    if max_chars == 0 {
+        -1
+    } else {
+        i32::try_from(max_chars).unwrap_or(i32::MAX)
+    }
+}
+
+/// Returns the index of the language button to activate when cycling with the keyboard
+///
+/// # Arguments
+///
+/// * `current` - Index of the currently active button, if any
+/// * `len` - Number of language buttons
+/// * `forward` - Move to the next button if true, to the previous one otherwise
+///
+/// # Returns
+///
+/// The new index, wrapping around at both ends, or None if there are fewer than two buttons
+pub fn cycle_language_index(current: Option<usize>, len: usize, forward: bool) -> Option<usize> {
+    if len < 2 {
+        return None;
+    }
+    match (current, forward) {
+        (Some(index), true) => Some((index + 1) % len),
+        (Some(index), false) => Some((index + len - 1) % len),
+        (None, true) => Some(0),
+        (None, false) => Some(len - 1),
+    }
+}
+
 // --- Helper function to update button states ---
 // Now accepts lingua::Language and a slice of button tuples with Language
 fn update_active_button_simple(
@@ -69,33 +498,301 @@ fn update_active_button_simple(
     }
 }
 
+// --- Helper function to grey out a language button ---
+// All other buttons are made clickable again, so None re-enables every button
+fn update_button_sensitivity(
+    disabled_lang: Option<Language>,
+    locked_lang: Option<Language>,
+    buttons: &[(Language, Rc<RefCell<ToggleButton>>)],
+) {
+    for (lang, button_rc) in buttons {
+        let unlocked = locked_lang.is_none_or(|locked| locked == *lang);
+        button_rc
+            .borrow()
+            .set_sensitive(Some(*lang) != disabled_lang && unlocked);
+    }
+}
+
+// --- Helper function to show the length of the translation ---
+// Cleared if the translation failed
+fn update_translation_counts(counts_label: &Label, details: Option<&TranslationDetails>) {
+    match details {
+        Some(details) => {
+            counts_label.set_text(&format!("Translation: {}", text_counts(&details.text)))
+        }
+        None => counts_label.set_text(""),
+    }
+}
+
+// --- Helper function to show the response details in the debug panel ---
+// Does nothing if the panel is disabled
+fn update_debug_panel(
+    debug_label: Option<&Label>,
+    details: Option<&TranslationDetails>,
+    metrics: &Metrics,
+) {
+    if let Some(label) = debug_label {
+        let details = match details {
+            Some(details) => details.summary(),
+            None => "No response details (translation failed).".to_string(),
+        };
+        label.set_text(&format!("{}\n{}", details, metrics.summary()));
+    }
+}
+
+// --- Helper function to show the results of multi-target mode ---
+// Replaces the rows in the container with a labeled row per target language (or per model
+// of compare_models); `rtl` tells which rows are in a right-to-left language
+fn show_multi_translations<T: Display>(
+    container: &GtkBox,
+    results: &[(T, TranslationResult)],
+    rtl: impl Fn(&T) -> bool,
+    max_width_chars: u32,
+) {
+    while let Some(child) = container.first_child() {
+        container.remove(&child);
+    }
+    for (lang, result) in results {
+        let heading = Label::builder()
+            .label(lang.to_string())
+            .halign(Align::Start)
+            .build();
+        heading.add_css_class("heading");
+        let text = match result {
+            Ok(text) => text.clone(),
+            Err(error_message) => error_message.clone(),
+        };
+        let body = Label::builder()
+            .label(text)
+            .selectable(true)
+            .halign(Align::Start)
+            .build();
+        wrap_label(&body, max_width_chars);
+        let row = GtkBox::builder()
+            .orientation(Orientation::Vertical)
+            .spacing(4)
+            .build();
+        row.append(&heading);
+        row.append(&body);
+        container.append(&row);
+        // Same text direction handling as the main output
+        OutputSink::Label(body).set_rtl(rtl(lang));
+    }
+}
+
+// --- Helper function to translate with every model of compare_models ---
+// Shows a row per model, labeled with its name, once all of them answered. Returns false if a
+// newer comparison (or multi-target translation) replaced this one first.
+async fn run_model_comparison(
+    state: Rc<AppState>,
+    rows: GtkBox,
+    output: OutputSink,
+    debouncer: Debouncer,
+    text: String,
+    target_lang: Language,
+    api_key: String,
+) -> bool {
+    let config = state.config().clone();
+    output.set_text(&config.ui_text(
+        ui_strings::TRANSLATING,
+        &[("language", target_lang.to_string())],
+    ));
+    let rate_limiter = state.rate_limiter();
+    let comparison = translate_with_models(
+        &text,
+        state.source_language(),
+        target_lang,
+        &config.compare_models,
+        &api_key,
+        &config,
+        Some(&rate_limiter),
+    );
+    let Some(results) = debouncer.run(comparison).await else {
+        return false;
+    };
+    show_multi_translations(
+        &rows,
+        &results,
+        |_| is_rtl(target_lang),
+        config.label_max_width_chars,
+    );
+    state.set_multi_text(Some(format_multi_translations(&results)));
+    output.set_text(&format!(
+        "Translated into {} with {} model(s).",
+        target_lang,
+        results.len()
+    ));
+    true
+}
+
+// --- Helper function to announce a finished translation ---
+// Sends a desktop notification if enabled and no window of the app has focus
+fn notify_translation_complete(
+    app: &Application,
+    notify_on_complete: bool,
+    private_mode: bool,
+    details: Option<&TranslationDetails>,
+) {
+    let window_active = app
+        .active_window()
+        .map(|window| window.is_active())
+        .unwrap_or(false);
+    if !should_notify(notify_on_complete, window_active, details) {
+        return;
+    }
+    if let Some(details) = details {
+        let notification = gio::Notification::new("Translation ready");
+        // Notification daemons keep a history, so no preview of private text
+        if !private_mode {
+            notification.set_body(Some(&notification_preview(
+                &details.text,
+                NOTIFICATION_PREVIEW_CHARS,
+            )));
+        }
+        app.send_notification(Some("translation-complete"), &notification);
+    }
+}
+
+// --- Helper function to close the window a while after a successful translation ---
+// Only with auto_close_ms set and as long as the user hasn't clicked in the window.
+// `widget` is any widget in the window, which may not be built yet when this is set up.
+fn schedule_auto_close(state: &Rc<AppState>, widget: &impl IsA<gtk::Widget>, succeeded: bool) {
+    let Some(delay) = state.auto_close_delay(succeeded) else {
+        return;
+    };
+    let state_timer = Rc::downgrade(state);
+    let widget = widget.as_ref().downgrade();
+    let source = glib::timeout_add_local_once(delay, move || {
+        if let Some(state) = state_timer.upgrade() {
+            state.clear_auto_close();
+        }
+        let window = widget
+            .upgrade()
+            .and_then(|widget| widget.root())
+            .and_then(|root| root.downcast::<gtk::Window>().ok());
+        if let Some(window) = window {
+            println!("Closing the window after {:?} (auto_close_ms)", delay);
+            window.close();
+        }
+    });
+    state.set_auto_close(source);
+}
+
+// --- Helper function to make a label wrap instead of widening the window ---
+// Long words are broken too, e.g. URLs
+fn wrap_label(label: &Label, max_width_chars: u32) {
+    label.set_wrap(true);
+    label.set_wrap_mode(gtk::pango::WrapMode::WordChar);
+    label.set_max_width_chars(label_max_width(max_width_chars));
+}
+
+// --- Helper function to scale the fonts of the window ---
+// Label and text box fonts are scaled relative to the theme's size; button text is a label too
+fn apply_font_scale(window: &ApplicationWindow, font_scale: f64) {
+    if font_scale == 1.0 {
+        return;
+    }
+    let provider = gtk::CssProvider::new();
+    provider.load_from_string(&format!(
+        "label, textview {{ font-size: {:.0}%; }}",
+        font_scale * 100.0
+    ));
+    gtk::style_context_add_provider_for_display(
+        &WidgetExt::display(window),
+        &provider,
+        gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
+    );
+}
+
+// --- Helper function to add a line below the details in the debug panel ---
+// Does nothing if the panel is disabled
+fn append_debug_note(debug_label: Option<&Label>, note: &str) {
+    if let Some(label) = debug_label {
+        label.set_text(&format!("{}\n{}", label.text(), note));
+    }
+}
+
+// --- Helper function to open config.toml in the default application ---
+// Creates the commented example config first if the file doesn't exist yet
+fn open_config_file() -> Result<(), String> {
+    let path = config::get_config_path().ok_or("Could not determine config directory")?;
+    if !path.exists() {
+        config::save_example_config()
+            .map_err(|e| format!("Failed to create config file: {}", e))?;
+    }
+    let uri = config::config_file_uri().ok_or("Could not build config file URI")?;
+    gio::AppInfo::launch_default_for_uri(&uri, None::<&gio::AppLaunchContext>)
+        .map_err(|e| format!("Failed to open config file: {}", e))
+}
+
+/// What an activation of the application should do
+///
+/// The application is single-instance: launching it again (e.g. with `--trigger` from a
+/// global shortcut) activates the running instance instead of starting a second one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Activation {
+    /// No window is open yet: open one
+    Open,
+    /// A window is open: replace it with a new one that reads the clipboard again
+    Refresh,
+}
+
+/// Decides what an activation does
+///
+/// # Arguments
+///
+/// * `open_windows` - Number of windows the application has open
+///
+/// # Returns
+///
+/// `Activation::Refresh` if a window is open, `Activation::Open` otherwise
+pub fn activation_action(open_windows: usize) -> Activation {
+    if open_windows > 0 {
+        Activation::Refresh
+    } else {
+        Activation::Open
+    }
+}
+
+/// Handles the "activate" signal, both for the first launch and for later ones
+///
+/// # Arguments
+///
+/// * `app` - The application
+/// * `config` - The configuration loaded at startup
+pub fn activate(app: &Application, config: Config) {
+    // Keep the application running while the old window is gone and the new one isn't built
+    let _hold = (activation_action(app.windows().len()) == Activation::Refresh).then(|| {
+        println!("Activated again, translating the clipboard anew");
+        let hold = app.hold();
+        // Closing writes the settings, so the new window starts from them
+        for window in app.windows() {
+            window.close();
+        }
+        hold
+    });
+    build_ui(app, config);
+}
+
 // Modified function signature to accept initial Config
 pub fn build_ui(app: &Application, initial_config: Config) {
     // --- State Management ---
-    // Use the initial config passed from main
-    let config_rc = Rc::new(RefCell::new(initial_config));
+    // Config, clipboard text, API key, language buttons and rate limiter, shared by the handlers
+    let state = Rc::new(AppState::new(initial_config));
 
-    // Load last target language (now lingua::Language) from settings
-    let last_target_language = settings::load_last_language();
-    let original_clipboard_text = Rc::new(RefCell::new(None::<String>));
-    let api_key_rc = Rc::new(RefCell::new(None::<String>)); // Keep API key separate
+    // Load last target language (now lingua::Language) and target preference from settings
+    let startup_settings = settings::load_settings();
+    let last_target_language = startup_settings.last_language;
+    // Window size from the last session (startup_settings moves into the startup flow)
+    let window_width = startup_settings.window_width.unwrap_or(450);
+    let window_height = startup_settings.window_height.unwrap_or(400);
+    println!("Loaded last language: {:?}", last_target_language);
 
     // --- Lingua Detector ---
-    // Only load languages we need for detection from config
-    let detector = {
-        let config = config_rc.borrow();
-        let detection_languages = vec![config.primary_language];
-
-        println!(
-            "Setting up language detector with: {:?}",
-            detection_languages
-        );
-        Rc::new(
-            LanguageDetectorBuilder::from_languages(&detection_languages)
-                .with_low_accuracy_mode()
-                .build(),
-        )
-    };
+    // Built in the background while the window comes up and the clipboard is read,
+    // unless the source language is forced or detection is disabled
+    let detector_handle =
+        detection_needed(state.config()).then(|| preload_detector(state.config()));
 
     // --- UI Elements ---
 
@@ -118,47 +815,175 @@ pub fn build_ui(app: &Application, initial_config: Config) {
 
     // --- Create Language Buttons Dynamically ---
     // Store buttons in a Vec with lingua::Language
-    let language_buttons_rc: Rc<RefCell<LanguageButtonsVec>> = Rc::new(RefCell::new(Vec::new()));
     {
-        // Scope for borrowing config_rc and language_buttons_rc mutably
-        let mut buttons_mut = language_buttons_rc.borrow_mut();
-        let config = config_rc.borrow(); // Borrow immutably to read all_target_languages
+        let mut buttons = Vec::new();
+        let config = state.config(); // Read all_target_languages
 
         if config.all_target_languages.is_empty() {
             // Handle case where config might somehow have an empty list despite defaults
             eprintln!("Error: No target languages defined in configuration!");
             // Maybe add a fallback label here?
         } else {
-            for lang in &config.all_target_languages {
-                // Get the ISO code and convert to string
-                let button_label = lang.iso_code_639_1().to_string().to_uppercase();
+            // Most used languages first if configured, otherwise config order
+            let button_langs = if config.sort_buttons_by_usage {
+                sort_languages_by_usage(&config.all_target_languages, &settings::load_usage())
+            } else {
+                config.all_target_languages.clone()
+            };
+            for lang in &button_langs {
+                // ISO code in the configured case
+                let button_label = format_button_label(*lang, &config.button_label_case);
 
                 let button = ToggleButton::with_label(&button_label);
                 button.set_tooltip_text(Some(&lang.to_string())); // Tooltip shows full name
+                                                                  // Only the pinned target is clickable if it is locked
+                button.set_sensitive(
+                    locked_target_language(config).is_none_or(|locked| locked == *lang),
+                );
+                // Proofreading has no target, the buttons would only retranslate the same way
+                button.set_visible(!config.proofread() || config.multi_target);
                 lang_hbox.append(&button); // Add button to the UI layout
-                buttons_mut.push((*lang, Rc::new(RefCell::new(button)))); // Store lang and button Rc
+                buttons.push((*lang, Rc::new(RefCell::new(button)))); // Store lang and button Rc
             }
         }
-    } // Mutable borrow of language_buttons_rc drops here
+        state.set_language_buttons(buttons);
+    }
 
-    // Vertical box for content (label + copy button)
+    // Undo button goes back to the language used before an automatic switch at startup,
+    // shown only after one
+    let undo_button = Button::with_label("Undo");
+    undo_button.set_visible(false);
+    lang_hbox.append(&undo_button);
+
+    // Settings button opens config.toml for editing
+    let settings_button = Button::with_label("Settings");
+    settings_button.set_tooltip_text(Some("Open config file"));
+    lang_hbox.append(&settings_button);
+
+    // Vertical box for content (output + copy button)
     let content_vbox = GtkBox::builder()
         .orientation(Orientation::Vertical)
         .spacing(10)
         .build();
 
-    // Label for translation output
-    let label = Label::builder()
-        .label("Reading clipboard...")
-        .wrap(true)
-        .selectable(true)
+    // Source text for live preview: edits are retranslated as you type
+    let live_input = if state.config().live_preview {
+        let text_view = TextView::builder()
+            .wrap_mode(WrapMode::WordChar)
+            .editable(true)
+            .build();
+
+        let scrolled_window = ScrolledWindow::builder()
+            .hscrollbar_policy(PolicyType::Never)
+            .vexpand(true)
+            .child(&text_view)
+            .build();
+        content_vbox.append(&scrolled_window);
+        Some(text_view)
+    } else {
+        None
+    };
+    // Only the latest live preview request is kept, older ones are cancelled
+    let live_debouncer = Debouncer::new(LIVE_PREVIEW_DELAY);
+
+    // Translation output: an editable text view if configured, otherwise a read-only label
+    let output = if state.config().editable_output {
+        let text_view = TextView::builder()
+            .wrap_mode(WrapMode::WordChar)
+            .editable(true)
+            .build();
+        text_view
+            .buffer()
+            .set_text(&state.config().ui_text(ui_strings::READING_CLIPBOARD, &[]));
+
+        let scrolled_window = ScrolledWindow::builder()
+            .hscrollbar_policy(PolicyType::Never)
+            .vexpand(true)
+            .child(&text_view)
+            .build();
+        content_vbox.append(&scrolled_window);
+        OutputSink::TextView(text_view)
+    } else {
+        let label = Label::builder()
+            .label(state.config().ui_text(ui_strings::READING_CLIPBOARD, &[]))
+            .selectable(true)
+            .build();
+        wrap_label(&label, state.config().label_max_width_chars);
+        content_vbox.append(&label);
+        OutputSink::Label(label)
+    };
+
+    // Multi-target mode: one row per active language button, stacked below the status output.
+    // Comparing models uses the same rows, one per model.
+    let comparing_models = !state.config().compare_models.is_empty();
+    let result_rows = if state.config().multi_target || comparing_models {
+        let rows = GtkBox::builder()
+            .orientation(Orientation::Vertical)
+            .spacing(12)
+            .build();
+        let scrolled_window = ScrolledWindow::builder()
+            .hscrollbar_policy(PolicyType::Never)
+            .vexpand(true)
+            .child(&rows)
+            .build();
+        content_vbox.append(&scrolled_window);
+        Some(rows)
+    } else {
+        None
+    };
+    let multi_target = state.config().multi_target;
+    let multi_output = result_rows.clone().filter(|_| multi_target);
+    let compare_output = result_rows.filter(|_| !multi_target);
+    // Only the latest set of active languages (or comparison) is shown, older requests are
+    // cancelled
+    let multi_debouncer = Debouncer::new(Duration::ZERO);
+
+    // Word and character counts of the original text and the translation, for comparing lengths
+    let original_counts_label = Label::builder().halign(Align::Start).hexpand(true).build();
+    original_counts_label.add_css_class("dim-label");
+    let translation_counts_label = Label::builder().halign(Align::End).build();
+    translation_counts_label.add_css_class("dim-label");
+    let counts_hbox = GtkBox::builder()
+        .orientation(Orientation::Horizontal)
+        .spacing(6)
         .build();
+    counts_hbox.append(&original_counts_label);
+    counts_hbox.append(&translation_counts_label);
+    content_vbox.append(&counts_hbox);
+
+    // Copy Original button puts the untranslated text back on the clipboard
+    let copy_original_button = Button::with_label("Copy Original");
+    copy_original_button.set_tooltip_text(Some("Copy the original text back to the clipboard"));
+    copy_original_button.set_sensitive(false); // Enabled once the clipboard text is read
 
-    // Copy & Close button (standard button)
-    let copy_button = Button::with_label("Copy & Close");
+    // Copy & Close button (standard button), just Copy if the window stays open
+    let copy_action = after_copy(state.config());
+    let copy_button = Button::with_label(copy_action.button_label());
 
-    content_vbox.append(&label);
-    content_vbox.append(&copy_button);
+    // Horizontal box for the copy buttons
+    let copy_hbox = GtkBox::builder()
+        .orientation(Orientation::Horizontal)
+        .spacing(6)
+        .homogeneous(true)
+        .build();
+    copy_hbox.append(&copy_original_button);
+    copy_hbox.append(&copy_button);
+
+    content_vbox.append(&copy_hbox);
+
+    // Collapsed debug panel with the finish reason and token usage of the last response
+    let debug_label = if state.config().debug_panel {
+        let label = Label::builder()
+            .label("No response yet.")
+            .selectable(true)
+            .halign(Align::Start)
+            .build();
+        let expander = Expander::builder().label("Debug").child(&label).build();
+        content_vbox.append(&expander);
+        Some(label)
+    } else {
+        None
+    };
 
     // Add language buttons and content box to the main box
     main_vbox.append(&lang_hbox);
@@ -168,79 +993,144 @@ pub fn build_ui(app: &Application, initial_config: Config) {
     let display = gdk::Display::default().expect("Could not get default display");
     let clipboard = display.clipboard();
 
-    // Clone state Rcs for the initial load future
-    let label_clone_init = label.clone();
-    let original_text_rc_clone_init = original_clipboard_text.clone();
-    let api_key_rc_clone_init = api_key_rc.clone();
-    let config_rc_clone_init = config_rc.clone(); // Clone the config Rc
-    let detector_clone_init = detector.clone(); // Clone detector for the async block
-    let language_buttons_rc_clone_init = language_buttons_rc.clone(); // Clone buttons Vec Rc
+    // Clone the state and widgets for the initial load future
+    let output_clone_init = output.clone();
+    let state_init = state.clone();
+    let detector_handle_init = detector_handle; // Moved into the async block
+    let live_input_clone_init = live_input.clone();
+    let copy_original_button_clone_init = copy_original_button.clone();
+    let debug_label_clone_init = debug_label.clone();
+    let app_clone_init = app.clone();
+    let original_counts_clone_init = original_counts_label.clone();
+    let translation_counts_clone_init = translation_counts_label.clone();
+    let main_vbox_clone_init = main_vbox.clone();
+    let undo_button_clone_init = undo_button.clone();
+    let compare_output_clone_init = compare_output.clone();
+    let multi_debouncer_clone_init = multi_debouncer.clone();
 
-    glib::spawn_future_local(async move {
-        // 1. Read API Key once (still reading from env var for now)
-        match env::var("OPENROUTER_API_KEY") {
+    let startup_flow = async move {
+        // 1. Read API Key once (env var, then key file, then config)
+        let api_key = resolve_api_key(state_init.config());
+        match api_key {
             Ok(key) => {
-                *api_key_rc_clone_init.borrow_mut() = Some(key);
+                state_init.set_api_key(Some(key));
+            }
+            Err(_) if state_init.config().mock => {
+                // Mock translations don't call the API, so no key is needed
+                state_init.set_api_key(Some(String::new()));
             }
-            Err(_) => {
-                label_clone_init
-                    .set_text("Error: OPENROUTER_API_KEY environment variable not set.");
+            Err(message) => {
+                output_clone_init.set_text(&message);
                 // Update button state even on error (show last language from settings)
                 let lang_to_show = last_target_language; // Use last_target_language (lingua::Language) from settings
                                                          // Use the imported clone macro
-                glib::idle_add_local_once(
-                    clone!(@strong language_buttons_rc_clone_init => move || {
-                        update_active_button_simple(lang_to_show, &language_buttons_rc_clone_init.borrow());
-                    }),
-                );
+                glib::idle_add_local_once(clone!(@strong state_init => move || {
+                    update_active_button_simple(lang_to_show, &state_init.language_buttons());
+                }));
                 return; // Stop if no API key
             }
         }
 
         // 2. Read text from clipboard once
-        match clipboard.read_text_future().await {
-            Ok(Some(gstring_text)) => {
-                // text is glib::GString here
-                let text = gstring_text.to_string(); // Convert to String
-                *original_text_rc_clone_init.borrow_mut() = Some(text.clone()); // Store original text as String
+        let accept_html = state_init.config().accept_html_clipboard;
+        match read_clipboard_contents(&clipboard, accept_html).await {
+            Ok(Some(text)) => {
+                // A bare link is replaced by the text of the page it points to
+                if state_init.config().translate_urls && clipboard_url(&text).is_some() {
+                    output_clone_init.set_text("Fetching the linked page...");
+                }
+                let text = match resolve_link(text, state_init.config()).await {
+                    Ok(text) => text,
+                    Err(message) => {
+                        eprintln!("{}", message);
+                        output_clone_init.set_text(&message);
+                        let lang_to_show = last_target_language;
+                        glib::idle_add_local_once(clone!(@strong state_init => move || {
+                            update_active_button_simple(lang_to_show, &state_init.language_buttons());
+                        }));
+                        return;
+                    }
+                };
+
+                // Refuse huge texts instead of silently sending them to the API
+                let max_clipboard_bytes = state_init.config().max_clipboard_bytes;
+                if let Err(message) = check_clipboard_size(text.len(), max_clipboard_bytes) {
+                    eprintln!("{}", message);
+                    output_clone_init.set_text(&message);
+                    let lang_to_show = last_target_language; // Use last_target_language from settings
+                    glib::idle_add_local_once(clone!(@strong state_init => move || {
+                        update_active_button_simple(lang_to_show, &state_init.language_buttons());
+                    }));
+                    return; // Not stored, so the language buttons won't translate it either
+                }
+
+                state_init.set_original_text(Some(text.clone())); // Store original text as String
+                copy_original_button_clone_init.set_sensitive(true);
+                original_counts_clone_init.set_text(&format!("Original: {}", text_counts(&text)));
+                if let Some(input) = &live_input_clone_init {
+                    // Stored text matches, so the live preview handler won't retranslate it
+                    input.buffer().set_text(&text);
+                }
 
                 // --- Performance Logging Start ---
                 let start_time = std::time::Instant::now();
                 println!("Starting language detection at {:?}", start_time);
 
                 // --- Language Detection with Timeout ---
-                // detected_language is Option<lingua::Language>
-                println!("Text length for detection: {} characters", text.len());
+                // detected_source_lang is Option<lingua::Language>
+                // A source language forced in the config, or disabled detection, skips
+                // detection entirely
+                let forced_source_lang = state_init.config().forced_source_language;
+                let detection_enabled = state_init.config().detection_enabled;
                 let detection_start = std::time::Instant::now();
+                let detection =
+                    resolve_source_language(forced_source_lang, detection_enabled, || async {
+                        // Usually finished by now; only waited for when detection is needed
+                        let preloaded = match detector_handle_init {
+                            Some(handle) => match handle.await {
+                                Ok(detector) => Some(detector),
+                                Err(e) => {
+                                    eprintln!("Preloading the language detector failed: {}", e);
+                                    None
+                                }
+                            },
+                            None => None,
+                        };
+                        let detector =
+                            preloaded.unwrap_or_else(|| build_detector(state_init.config()));
+                        detect_source_language(
+                            detector,
+                            &text,
+                            DETECTION_SAMPLE_CHARS,
+                            DETECTION_TIMEOUT,
+                            DETECTION_MIN_CONFIDENCE,
+                            primary_bias(state_init.config()),
+                        )
+                        .await
+                    })
+                    .await;
+                let detected_source_lang = detection.map(|detection| detection.language);
 
-                // Only use a small sample of text for detection (first 100 chars or less)
-                // Use a safe way to truncate that respects UTF-8 character boundaries
-                let sample_text = if text.chars().count() > 100 {
-                    // Collect first 100 characters safely
-                    text.chars().take(100).collect::<String>()
-                } else {
-                    text.clone()
-                };
-
-                // Add timeout to prevent long detection times
-                let detected_source_lang = match timeout(
-                    Duration::from_secs(2), // 2 second timeout
-                    async { detector_clone_init.detect_language_of(sample_text) },
-                )
-                .await
-                {
-                    Ok(lang) => lang,
-                    Err(_) => {
-                        println!("Language detection timed out after 2 seconds");
-                        None // Return None if detection times out
-                    }
-                };
+                // Second detection phase on the whole text, running while the first
+                // translation is requested
+                let mut full_detection = (state_init.config().two_phase_detection
+                    && detection_needed(state_init.config())
+                    && needs_full_detection(&text, DETECTION_SAMPLE_CHARS))
+                .then(|| {
+                    spawn_full_detection(
+                        build_detector(state_init.config()),
+                        text.clone(),
+                        DETECTION_TIMEOUT,
+                        DETECTION_MIN_CONFIDENCE,
+                        primary_bias(state_init.config()),
+                    )
+                });
 
                 let detection_duration = detection_start.elapsed();
                 println!("Language detection took: {:?}", detection_duration);
 
-                if let Some(lang) = detected_source_lang {
-                    println!("Detected source language: {:?}", lang); // Log detected language
+                if let Some(detection) = &detection {
+                    println!("Detected source language: {}", detection.summary()); // Log detected language
                     println!(
                         "Total time from start to detection: {:?}",
                         start_time.elapsed()
@@ -250,21 +1140,50 @@ pub fn build_ui(app: &Application, initial_config: Config) {
                 }
 
                 // --- Implement language selection logic from README.md ---
-                let (primary_lang, secondary_lang) = {
-                    let config = config_rc_clone_init.borrow();
-                    (config.primary_language, config.secondary_language)
+                let (primary_lang, secondary_lang, fallback_lang, target_langs, auto_select) = {
+                    let config = state_init.config();
+                    (
+                        config.primary_language,
+                        config.secondary_language,
+                        config.fallback_language(),
+                        config.all_target_languages.clone(),
+                        config.auto_select_target,
+                    )
                 };
+                let pinned_lang = state_init.config().pinned_target_language;
 
                 // Use the extracted function for language selection
-                let mut final_target_lang = choose_target_language(
+                let mut final_target_lang = startup_target_language(
                     detected_source_lang,
-                    primary_lang,
-                    secondary_lang,
-                    last_target_language,
+                    state_init.config(),
+                    &startup_settings,
                 );
 
                 // Log the decision
+                let keep_manual_target = startup_settings.prefer_manual_target
+                    && target_langs.contains(&last_target_language);
                 match detected_source_lang {
+                    _ if pinned_lang.is_some() => {
+                        println!("Target language is pinned -> Using {:?}", final_target_lang);
+                    }
+                    _ if !auto_select => {
+                        println!(
+                            "Automatic target selection is disabled -> Using last target ({:?})",
+                            last_target_language
+                        );
+                    }
+                    _ if keep_manual_target => {
+                        println!(
+                            "Target was picked manually -> Keeping last target ({:?})",
+                            last_target_language
+                        );
+                    }
+                    Some(src) if src != primary_lang && !target_langs.contains(&src) => {
+                        println!(
+                            "Source is not a configured language -> Using fallback ({:?})",
+                            fallback_lang
+                        );
+                    }
                     Some(src) if src != primary_lang => {
                         println!(
                             "Source is not primary language -> Translating to primary ({:?})",
@@ -280,29 +1199,29 @@ pub fn build_ui(app: &Application, initial_config: Config) {
                     }
                     None => {
                         println!(
-                            "Could not detect source language -> Using primary language ({:?})",
-                            primary_lang
+                            "Could not detect source language -> Using fallback ({:?})",
+                            fallback_lang
                         );
                     }
                 }
 
                 // Ensure the final_target_lang is actually available in the UI buttons
-                let is_target_available = config_rc_clone_init
-                    .borrow()
+                let is_target_available = state_init
+                    .config()
                     .all_target_languages
                     .contains(&final_target_lang);
                 if !is_target_available {
                     println!("Warning: Auto-selected target language {:?} is not in 'all_target_languages'. Reverting to last target {:?}", final_target_lang, last_target_language);
                     final_target_lang = last_target_language; // Revert if not available
                                                               // Also ensure the last_target_language itself is available, otherwise pick the first from config?
-                    if !config_rc_clone_init
-                        .borrow()
+                    if !state_init
+                        .config()
                         .all_target_languages
                         .contains(&final_target_lang)
                     {
                         println!("Warning: Last target language {:?} is also not in 'all_target_languages'. Using first available.", final_target_lang);
-                        final_target_lang = config_rc_clone_init
-                            .borrow()
+                        final_target_lang = state_init
+                            .config()
                             .all_target_languages
                             .first()
                             .cloned()
@@ -311,71 +1230,215 @@ pub fn build_ui(app: &Application, initial_config: Config) {
                 }
 
                 // Update last_target_language in settings if the target language changed
-                if final_target_lang != last_target_language {
-                    if let Err(e) = settings::save_last_language(final_target_lang) {
-                        eprintln!("Failed to save last language after auto-switch: {}", e);
-                    } else {
-                        println!(
-                            "Target language automatically set to: {:?} and saved.",
-                            final_target_lang
-                        );
-                    }
-                } else {
-                    println!("Target language remains: {:?}", final_target_lang);
+                if remember_startup_target(
+                    final_target_lang,
+                    state_init.config(),
+                    last_target_language,
+                ) {
+                    offer_switch_undo(
+                        &state_init,
+                        &undo_button_clone_init,
+                        last_target_language,
+                        final_target_lang,
+                    );
                 }
 
-                // Update buttons in the main thread (always run this to set initial state correctly based on final_target_lang)
-                glib::idle_add_local_once(
-                    clone!(@strong language_buttons_rc_clone_init => move || {
-                        update_active_button_simple(final_target_lang, &language_buttons_rc_clone_init.borrow());
-                    }),
+                // Translating into the source language is pointless, grey out its button if configured
+                let disabled_lang = if state_init.config().exclude_source_button {
+                    source_button_to_disable(detected_source_lang, &target_langs, final_target_lang)
+                } else {
+                    None
+                };
+                update_button_sensitivity(
+                    disabled_lang,
+                    locked_target_language(state_init.config()),
+                    &state_init.language_buttons(),
                 );
 
-                // 3. Perform translation with the determined final language
-                let (api_url, model_version) = {
-                    let config = config_rc_clone_init.borrow();
-                    (config.api_url.clone(), config.model_version.clone())
-                };
+                // The language buttons compare against it, a pinned target isn't in the settings
+                state_init.set_target_language(Some(final_target_lang));
+                // Update buttons in the main thread (always run this to set initial state correctly based on final_target_lang)
+                glib::idle_add_local_once(clone!(@strong state_init => move || {
+                    update_active_button_simple(final_target_lang, &state_init.language_buttons());
+                }));
 
-                let api_key_clone = api_key_rc_clone_init.borrow().clone();
-                if let Some(key) = api_key_clone.as_ref() {
-                    request_translation(
-                        text,
-                        final_target_lang, // Use the determined target language (lingua::Language)
-                        key.clone(),
-                        api_url,
-                        model_version,
-                        label_clone_init,
-                    )
-                    .await;
-                } else {
-                    label_clone_init.set_text("Error retrieving API key for translation.");
+                // 3. Perform translation with the determined final language, a second time if
+                // the second detection phase corrects the target
+                let mut detection = detection;
+                let mut target_lang = final_target_lang;
+                loop {
+                    let source_lang = detection.as_ref().map(|detection| detection.language);
+                    state_init.set_source_language(source_lang);
+                    let config = config_for_pair(source_lang, target_lang, state_init.config());
+                    let notify_on_complete = config.notify_on_complete;
+                    let private_mode = config.private_mode;
+
+                    let api_key_clone = state_init.api_key();
+                    if config.multi_target {
+                        // Activating the target button above makes its handler translate
+                        // into every active language
+                    } else if !config.proofread()
+                        && is_already_in_target(
+                            detection.as_ref(),
+                            target_lang,
+                            detection_is_conclusive(&config),
+                        )
+                    {
+                        // Nothing to translate, show the text as it is
+                        println!(
+                            "Text is already in {:?} -> Skipping translation",
+                            target_lang
+                        );
+                        output_clone_init.set_text(&text);
+                        translation_counts_clone_init
+                            .set_text(&format!("Already in {}.", target_lang));
+                        state_init.set_skipped_same_language(true);
+                    } else if let (Some(rows), Some(key)) =
+                        (compare_output_clone_init.as_ref(), api_key_clone.as_ref())
+                    {
+                        let compared = run_model_comparison(
+                            state_init.clone(),
+                            rows.clone(),
+                            output_clone_init.clone(),
+                            multi_debouncer_clone_init.clone(),
+                            text.clone(),
+                            target_lang,
+                            key.clone(),
+                        )
+                        .await;
+                        schedule_auto_close(&state_init, &main_vbox_clone_init, compared);
+                    } else if let Some(key) = api_key_clone.as_ref() {
+                        let (outcome, metrics) = request_translation(
+                            text.clone(),
+                            source_lang,
+                            target_lang, // Use the determined target language (lingua::Language)
+                            key.clone(),
+                            config,
+                            state_init.rate_limiter(),
+                            output_clone_init.clone(),
+                        )
+                        .await;
+                        let metrics =
+                            metrics.with_detection(detection_duration, start_time.elapsed());
+                        println!(
+                            "Translation timing: {}",
+                            metrics.summary().replace('\n', ", ")
+                        );
+                        update_debug_panel(
+                            debug_label_clone_init.as_ref(),
+                            outcome.details(),
+                            &metrics,
+                        );
+                        update_translation_counts(
+                            &translation_counts_clone_init,
+                            outcome.details(),
+                        );
+                        // The target may be wrong if detection could have gone either way
+                        if let Some(detection) = detection.filter(Detection::is_ambiguous) {
+                            append_debug_note(
+                                debug_label_clone_init.as_ref(),
+                                &format!("ambiguous source language: {}", detection.summary()),
+                            );
+                        }
+                        notify_translation_complete(
+                            &app_clone_init,
+                            notify_on_complete,
+                            private_mode,
+                            outcome.details(),
+                        );
+                        // Picking another language is a click, which keeps the window open,
+                        // so only the startup translation closes it
+                        schedule_auto_close(
+                            &state_init,
+                            &main_vbox_clone_init,
+                            outcome.details().is_some(),
+                        );
+                    } else {
+                        output_clone_init.set_text("Error retrieving API key for translation.");
+                    }
+
+                    // Second detection phase: the whole text may show the sample was misleading
+                    let Some(handle) = full_detection.take() else {
+                        break;
+                    };
+                    let full = handle.await.unwrap_or_else(|e| {
+                        eprintln!("Detection on the whole text failed: {}", e);
+                        None
+                    });
+                    let Some(corrected_lang) = refined_target_language(
+                        full.as_ref(),
+                        target_lang,
+                        state_init.config(),
+                        &startup_settings,
+                    ) else {
+                        break;
+                    };
+                    println!(
+                        "Detection on the whole text found {} -> Switching target to {:?}",
+                        full.map(|full| full.summary()).unwrap_or_default(),
+                        corrected_lang
+                    );
+                    if remember_startup_target(corrected_lang, state_init.config(), target_lang) {
+                        offer_switch_undo(
+                            &state_init,
+                            &undo_button_clone_init,
+                            target_lang,
+                            corrected_lang,
+                        );
+                    }
+                    if state_init.config().exclude_source_button {
+                        update_button_sensitivity(
+                            source_button_to_disable(
+                                full.map(|full| full.language),
+                                &target_langs,
+                                corrected_lang,
+                            ),
+                            locked_target_language(state_init.config()),
+                            &state_init.language_buttons(),
+                        );
+                    }
+                    state_init.set_target_language(Some(corrected_lang));
+                    // Queued after the first update, so the buttons end on the corrected target
+                    glib::idle_add_local_once(clone!(@strong state_init => move || {
+                        update_active_button_simple(corrected_lang, &state_init.language_buttons());
+                    }));
+                    state_init.set_skipped_same_language(false);
+                    detection = full;
+                    target_lang = corrected_lang;
                 }
             }
             Ok(None) => {
-                label_clone_init.set_text("Clipboard does not contain text.");
-                *original_text_rc_clone_init.borrow_mut() = None; // Ensure it's None
-                                                                  // Update button state even if clipboard is empty
+                output_clone_init.set_text("Clipboard does not contain text.");
+                state_init.set_original_text(None); // Ensure it's None
+
+                // Update button state even if clipboard is empty
                 let lang_to_show = last_target_language; // Use last_target_language from settings
-                glib::idle_add_local_once(
-                    clone!(@strong language_buttons_rc_clone_init => move || {
-                        update_active_button_simple(lang_to_show, &language_buttons_rc_clone_init.borrow());
-                    }),
-                );
+                glib::idle_add_local_once(clone!(@strong state_init => move || {
+                    update_active_button_simple(lang_to_show, &state_init.language_buttons());
+                }));
             }
             Err(e) => {
                 eprintln!("Error reading clipboard: {}", e);
-                label_clone_init.set_text(&format!("Error reading clipboard: {}", e));
-                *original_text_rc_clone_init.borrow_mut() = None; // Ensure it's None
-                                                                  // Update button state even on error
+                output_clone_init.set_text(&format!("Error reading clipboard: {}", e));
+                state_init.set_original_text(None); // Ensure it's None
+
+                // Update button state even on error
                 let lang_to_show = last_target_language; // Use last_target_language from settings
-                glib::idle_add_local_once(
-                    clone!(@strong language_buttons_rc_clone_init => move || {
-                        update_active_button_simple(lang_to_show, &language_buttons_rc_clone_init.borrow());
-                    }),
-                );
+                glib::idle_add_local_once(clone!(@strong state_init => move || {
+                    update_active_button_simple(lang_to_show, &state_init.language_buttons());
+                }));
             }
         }
+    };
+
+    // Bound the whole startup flow so the UI never stays stuck in an intermediate state
+    let output_clone_timeout = output.clone();
+    let startup_timeout = Duration::from_millis(state.config().startup_timeout_ms);
+    glib::spawn_future_local(async move {
+        if !run_with_budget(startup_timeout, startup_flow).await {
+            eprintln!("Startup timed out after {:?}", startup_timeout);
+            output_clone_timeout.set_text("Operation timed out.");
+        }
     });
 
     // --- Window Setup ---
@@ -383,48 +1446,203 @@ pub fn build_ui(app: &Application, initial_config: Config) {
         .application(app)
         .title("Clipboard Translator")
         .child(&main_vbox)
-        .default_width(450)
-        .default_height(400) // Adjusted default height slightly
+        .default_width(window_width)
+        .default_height(window_height)
         .build();
+    apply_font_scale(&window, state.config().font_scale);
+
+    // Any click in the window, e.g. on a button or in the text, stops auto_close_ms.
+    // Capture phase so it is seen before the clicked widget handles it.
+    if state.config().auto_close_ms.is_some() {
+        let click_controller = gtk::GestureClick::new();
+        click_controller.set_propagation_phase(gtk::PropagationPhase::Capture);
+        let state_click = state.clone();
+        click_controller.connect_pressed(move |_gesture, _n_press, _x, _y| {
+            state_click.cancel_auto_close();
+        });
+        window.add_controller(click_controller);
+    }
 
     // --- Language Button Toggle Handlers ---
     // Define the handler logic once
     let create_lang_button_handler =
         |
         button_lang: Language, // The language this specific button represents (lingua::Language)
-        all_buttons_rc: Rc<RefCell<LanguageButtonsVec>> // Rc to the Vec of all buttons
     | {
         // Clone necessary items for the handler closure
-        let config_rc_handler = config_rc.clone(); // Clone config Rc
-        let text_rc = original_clipboard_text.clone();
-        let key_rc = api_key_rc.clone();
-        let label_clone = label.clone();
-        // Clone the Rc to the button vector for use inside the closure
-        let all_buttons_rc_clone = all_buttons_rc.clone();
+        let state_handler = state.clone();
+        let output_clone = output.clone();
+        let live_debouncer_handler = live_debouncer.clone();
+        let debug_label_handler = debug_label.clone();
+        let app_handler = app.clone();
+        let translation_counts_handler = translation_counts_label.clone();
+        let multi_output_handler = multi_output.clone();
+        let multi_debouncer_handler = multi_debouncer.clone();
+        let compare_output_handler = compare_output.clone();
+        let undo_button_handler = undo_button.clone();
 
         move |toggled_button: &ToggleButton| {
+            // Multi-target mode: any number of buttons may be active, each one gets a row
+            if let Some(multi_box) = &multi_output_handler {
+                if toggled_button.is_active() && button_lang != settings::load_last_language() {
+                    if let Err(e) = settings::save_manual_target(button_lang) {
+                        eprintln!("Failed to save last language after user selection: {}", e);
+                    }
+                    if let Err(e) = settings::record_language_use(button_lang) {
+                        eprintln!("Failed to save language usage: {}", e);
+                    }
+                }
+
+                let maybe_text = state_handler.original_text();
+                let maybe_key = state_handler.api_key();
+                let (Some(text), Some(key)) = (maybe_text, maybe_key) else {
+                    return; // Nothing to translate yet, the output already says why
+                };
+
+                // Button order, so the rows don't jump around when toggling
+                let active_langs: Vec<Language> = state_handler
+                    .language_buttons()
+                    .iter()
+                    .filter(|(_, button_rc)| button_rc.borrow().is_active())
+                    .map(|(lang, _)| *lang)
+                    .collect();
+                if active_langs.is_empty() {
+                    multi_debouncer_handler.cancel();
+                    show_multi_translations::<Language>(
+                        multi_box,
+                        &[],
+                        |_| false,
+                        state_handler.config().label_max_width_chars,
+                    );
+                    state_handler.set_multi_text(None);
+                    output_clone.set_text("Select one or more languages.");
+                    return;
+                }
+
+                live_debouncer_handler.cancel();
+                output_clone.set_text(&state_handler.config().ui_text(
+                    ui_strings::TRANSLATING_MULTIPLE,
+                    &[("count", active_langs.len().to_string())],
+                ));
+                let config = state_handler.config().clone();
+                let rate_limiter = state_handler.rate_limiter();
+                let debouncer = multi_debouncer_handler.clone();
+                let multi_box = multi_box.clone();
+                let state = state_handler.clone();
+                let output = output_clone.clone();
+                glib::spawn_future_local(async move {
+                    let translations = translate_into_targets(
+                        &text,
+                        state.source_language(),
+                        &active_langs,
+                        &key,
+                        &config,
+                        Some(&rate_limiter),
+                    );
+                    // None if the active languages changed again in the meantime
+                    if let Some(results) = debouncer.run(translations).await {
+                        show_multi_translations(
+                            &multi_box,
+                            &results,
+                            |lang| is_rtl(*lang),
+                            config.label_max_width_chars,
+                        );
+                        state.set_multi_text(Some(format_multi_translations(&results)));
+                        output.set_text(&format!(
+                            "Translated into {} language(s).",
+                            results.len()
+                        ));
+                    }
+                });
+                return;
+            }
+
+            // Translate the stored text into this button's language
+            let start_translation = || {
+                // Snapshot the config for the translation request, with the pair's model
+                let config = config_for_pair(
+                    state_handler.source_language(),
+                    button_lang,
+                    state_handler.config(),
+                );
+
+                // Get stored text and key
+                let maybe_text = state_handler.original_text();
+                let maybe_key = state_handler.api_key();
+
+                if let (Some(text), Some(key)) = (maybe_text, maybe_key) {
+                    // A pending live preview would overwrite this translation
+                    live_debouncer_handler.cancel();
+                    if let Some(rows) = &compare_output_handler {
+                        glib::spawn_future_local(run_model_comparison(
+                            state_handler.clone(),
+                            rows.clone(),
+                            output_clone.clone(),
+                            multi_debouncer_handler.clone(),
+                            text,
+                            button_lang,
+                            key,
+                        ));
+                        return;
+                    }
+                    // Spawn a new future for the translation request
+                    let notify_on_complete = config.notify_on_complete;
+                    let private_mode = config.private_mode;
+                    let translation = request_translation(
+                        text, state_handler.source_language(),
+                        button_lang, // Use newly set language (lingua::Language)
+                        key,
+                        config,
+                        state_handler.rate_limiter(),
+                        output_clone.clone(),
+                    );
+                    let debug_label = debug_label_handler.clone();
+                    let app = app_handler.clone();
+                    let translation_counts = translation_counts_handler.clone();
+                    glib::spawn_future_local(async move {
+                        let (outcome, metrics) = translation.await;
+                        update_debug_panel(debug_label.as_ref(), outcome.details(), &metrics);
+                        update_translation_counts(&translation_counts, outcome.details());
+                        notify_translation_complete(
+                            &app,
+                            notify_on_complete,
+                            private_mode,
+                            outcome.details(),
+                        );
+                    });
+                } else {
+                    println!("No original text or API key available to translate.");
+                    output_clone.set_text("Cannot translate: Missing original text or API key.");
+                }
+            };
+
             // Check if the button *became* active.
             if toggled_button.is_active() {
-                // Get the previously selected language from settings
-                let previously_selected_lang = settings::load_last_language();
+                // The language the output is in, which a pinned target isn't saved as
+                let previously_selected_lang = current_target(&state_handler);
 
                 // Only trigger if the language actually changed by user click
                 if button_lang != previously_selected_lang {
-                    // Save the new language to settings
-                    if let Err(e) = settings::save_last_language(button_lang) {
+                    state_handler.set_target_language(Some(button_lang));
+                    // Save the new language to settings, remembering it was picked by hand
+                    if let Err(e) = settings::save_manual_target(button_lang) {
                         eprintln!("Failed to save last language after user selection: {}", e);
                     } else {
-                        println!("Target language set by user to: {:?} and saved.", button_lang);
+                        println!("Target language set by user to: {:?}, saving it.", button_lang);
+                    }
+                    // Count the pick for ordering the buttons by usage
+                    if let Err(e) = settings::record_language_use(button_lang) {
+                        eprintln!("Failed to save language usage: {}", e);
                     }
 
-                    // Get API URL and model version from config
-                    let (api_url, model_version) = {
-                        let config = config_rc_handler.borrow();
-                        (config.api_url.clone(), config.model_version.clone())
-                    };
+                    // The text is translated into the new language even if it was skipped at startup
+                    state_handler.set_skipped_same_language(false);
+                    // A language picked by hand (or by Undo) leaves no automatic switch to undo
+                    state_handler.take_auto_switch();
+                    undo_button_handler.set_visible(false);
 
                     // Deactivate other buttons (visually)
-                    let all_buttons = all_buttons_rc_clone.borrow(); // Borrow immutably
+                    let all_buttons = state_handler.language_buttons(); // Borrow immutably
                     for (lang, other_btn_rc) in all_buttons.iter() {
                         if *lang != button_lang && other_btn_rc.borrow().is_active() {
                             other_btn_rc.borrow().set_active(false);
@@ -435,29 +1653,11 @@ pub fn build_ui(app: &Application, initial_config: Config) {
                          toggled_button.set_active(true);
                     }
 
-
-                    // Get stored text and key
-                    let maybe_text = text_rc.borrow().clone();
-                    let maybe_key = key_rc.borrow().clone();
-
-                    if let (Some(text), Some(key)) = (maybe_text, maybe_key) {
-                         // Spawn a new future for the translation request
-                         glib::spawn_future_local(request_translation(
-                             text,
-                             button_lang, // Use newly set language (lingua::Language)
-                             key,
-                             api_url,
-                             model_version,
-                             label_clone.clone(),
-                         ));
-                    } else {
-                         println!("No original text or API key available to translate.");
-                         label_clone.set_text("Cannot translate: Missing original text or API key.");
-                    }
+                    start_translation();
                 } else {
                     // This handles the case where the button was already active (e.g., set by initial load or auto-switch)
                     // and the user clicks it again. We still need to ensure other buttons are off.
-                    let all_buttons = all_buttons_rc_clone.borrow();
+                    let all_buttons = state_handler.language_buttons();
                     for (lang, other_btn_rc) in all_buttons.iter() {
                         if *lang != button_lang && other_btn_rc.borrow().is_active() {
                             other_btn_rc.borrow().set_active(false);
@@ -471,9 +1671,9 @@ pub fn build_ui(app: &Application, initial_config: Config) {
             } else {
                 // This block handles the case where the user tries to deactivate the *currently active* button.
                 // We want to prevent this, ensuring one button is always selected.
-                 if button_lang == settings::load_last_language() {
+                 if button_lang == current_target(&state_handler) {
                      // Find the Rc for *this* button to re-activate it
-                     let maybe_button_rc = all_buttons_rc_clone.borrow().iter()
+                     let maybe_button_rc = state_handler.language_buttons().iter()
                          .find(|(lang, _)| *lang == button_lang)
                          .map(|(_, rc)| rc.clone());
 
@@ -487,35 +1687,280 @@ pub fn build_ui(app: &Application, initial_config: Config) {
                             }
                          }));
                      }
+
+                     // The text was already in this language at startup; clicking its button
+                     // asks for the translation anyway
+                     if state_handler.take_skipped_same_language() {
+                         start_translation();
+                     }
                  }
             }
         }
     };
 
     // Connect the handler to each button
-    {
-        // Scope for borrowing language_buttons_rc
-        let buttons = language_buttons_rc.borrow();
-        for (lang, button_rc) in buttons.iter() {
-            button_rc.borrow().connect_toggled(
-                // Create a unique handler closure for each button
-                create_lang_button_handler(*lang, language_buttons_rc.clone()),
+    for (lang, button_rc) in state.language_buttons().iter() {
+        button_rc.borrow().connect_toggled(
+            // Create a unique handler closure for each button
+            create_lang_button_handler(*lang),
+        );
+    }
+
+    // --- Live Preview Handler Setup ---
+    if let Some(input) = &live_input {
+        let state_live = state.clone();
+        let output_clone_live = output.clone();
+        let live_debouncer_live = live_debouncer.clone();
+        let debug_label_live = debug_label.clone();
+        let original_counts_live = original_counts_label.clone();
+        let translation_counts_live = translation_counts_label.clone();
+
+        input.buffer().connect_changed(move |buffer| {
+            let text = buffer
+                .text(&buffer.start_iter(), &buffer.end_iter(), false)
+                .to_string();
+            if state_live.original_text().as_deref() == Some(text.as_str()) {
+                return; // Set from the clipboard, already being translated
+            }
+            // Language buttons translate the edited text from now on
+            state_live.set_original_text(Some(text.clone()));
+            original_counts_live.set_text(&format!("Original: {}", text_counts(&text)));
+            // The edited text may be in another language, so every button is useful again
+            update_button_sensitivity(
+                None,
+                locked_target_language(state_live.config()),
+                &state_live.language_buttons(),
+            );
+
+            if text.trim().is_empty() {
+                live_debouncer_live.cancel();
+                output_clone_live.set_text("");
+                translation_counts_live.set_text("");
+                return;
+            }
+            let max_clipboard_bytes = state_live.config().max_clipboard_bytes;
+            if let Err(message) = check_clipboard_size(text.len(), max_clipboard_bytes) {
+                live_debouncer_live.cancel();
+                output_clone_live.set_text(&message);
+                return;
+            }
+            let Some(key) = state_live.api_key() else {
+                return; // Error about the missing key is already shown
+            };
+
+            let target_lang = state_live
+                .active_language()
+                .unwrap_or_else(|| current_target(&state_live));
+            // The edited text keeps the detected language of the clipboard text
+            let source_lang = state_live.source_language();
+            let config = config_for_pair(source_lang, target_lang, state_live.config());
+            let rate_limiter = state_live.rate_limiter();
+            let output = output_clone_live.clone();
+            let debouncer = live_debouncer_live.clone();
+            let debug_label = debug_label_live.clone();
+            let translation_counts = translation_counts_live.clone();
+            glib::spawn_future_local(async move {
+                let translation = request_translation(
+                    text,
+                    source_lang,
+                    target_lang,
+                    key,
+                    config,
+                    rate_limiter,
+                    output,
+                );
+                // None if a newer edit cancelled this one
+                if let Some((outcome, metrics)) = debouncer.run(translation).await {
+                    update_debug_panel(debug_label.as_ref(), outcome.details(), &metrics);
+                    update_translation_counts(&translation_counts, outcome.details());
+                }
+            });
+        });
+    }
+
+    // --- Keyboard Shortcuts ---
+    // Alt+Left / Alt+Right cycle through the target languages
+    let key_controller = EventControllerKey::new();
+    // Capture phase so focused widgets (e.g. the editable output) don't swallow the keys
+    key_controller.set_propagation_phase(gtk::PropagationPhase::Capture);
+    let state_keys = state.clone();
+    key_controller.connect_key_pressed(move |_controller, keyval, _keycode, state| {
+        if !state.contains(gdk::ModifierType::ALT_MASK) {
+            return glib::Propagation::Proceed;
+        }
+        let forward = match keyval {
+            gdk::Key::Right => true,
+            gdk::Key::Left => false,
+            _ => return glib::Propagation::Proceed,
+        };
+
+        let next_button = {
+            let buttons = state_keys.language_buttons();
+            let current = buttons
+                .iter()
+                .position(|(_, button_rc)| button_rc.borrow().is_active());
+            let mut next = cycle_language_index(current, buttons.len(), forward);
+            // Skip greyed-out buttons, e.g. the source language
+            for _ in 0..buttons.len() {
+                match next {
+                    Some(index) if !buttons[index].1.borrow().is_sensitive() => {
+                        next = cycle_language_index(Some(index), buttons.len(), forward);
+                    }
+                    _ => break,
+                }
+            }
+            next.map(|index| buttons[index].1.clone())
+        }; // Borrow drops before the toggle handler runs
+
+        if let Some(button_rc) = next_button {
+            // Switching languages by keyboard keeps the window open, as a click does
+            state_keys.cancel_auto_close();
+            // Goes through the regular toggle handler, which saves and translates
+            button_rc.borrow().set_active(true);
+        }
+        glib::Propagation::Stop
+    });
+    window.add_controller(key_controller);
+
+    // --- Undo Button Click Handler Setup ---
+    let state_undo = state.clone();
+    undo_button.connect_clicked(move |button| {
+        button.set_visible(false);
+        let Some(previous_lang) = state_undo.take_auto_switch() else {
+            return;
+        };
+        println!(
+            "Undoing the automatic switch -> Back to {:?}",
+            previous_lang
+        );
+        // Activating the button saves the language and translates into it, as a click would
+        let maybe_button_rc = state_undo
+            .language_buttons()
+            .iter()
+            .find(|(lang, _)| *lang == previous_lang)
+            .map(|(_, rc)| rc.clone());
+        if let Some(button_rc) = maybe_button_rc {
+            button_rc.borrow().set_active(true);
+        }
+    });
+
+    // --- Settings Button Click Handler Setup ---
+    let output_clone_settings = output.clone();
+    settings_button.connect_clicked(move |_button| {
+        if let Err(e) = open_config_file() {
+            eprintln!("{}", e);
+            output_clone_settings.set_text(&e);
+        }
+    });
+
+    // --- Copy Original Button Click Handler Setup ---
+    let state_copy_original = state.clone();
+    let clipboard_copy_original = display.clipboard();
+
+    copy_original_button.connect_clicked(move |_button| {
+        // Keeps the window open, e.g. to copy the translation afterwards
+        if let Some(text) = state_copy_original.original_text() {
+            write_clipboard_text(
+                &clipboard_copy_original,
+                &text,
+                state_copy_original.config().private_mode,
             );
+            println!("Copied original text to clipboard");
         }
-    } // Borrow drops here
+    });
 
     // --- Copy Button Click Handler Setup ---
-    let label_clone_copy = label.clone();
+    let output_clone_copy = output.clone();
+    let state_copy = state.clone();
     let window_clone_copy = window.clone();
     let clipboard_copy = display.clipboard();
 
     copy_button.connect_clicked(move |_button| {
-        let text_to_copy = label_clone_copy.text();
-        clipboard_copy.set_text(&text_to_copy);
-        println!("Copied to clipboard and closing: {}", text_to_copy);
-        window_clone_copy.close();
+        // Read from the output so edits made in the editable view are copied, only the
+        // selected part if there is a selection, or all rows in multi-target mode
+        let copied_text = state_copy.multi_text().unwrap_or_else(|| {
+            text_to_copy(
+                &output_clone_copy.text(),
+                output_clone_copy.selection_bounds(),
+            )
+        });
+        let private_mode = state_copy.config().private_mode;
+        write_clipboard_text(&clipboard_copy, &copied_text, private_mode);
+        let logged_text = loggable_text(&copied_text, private_mode);
+        match copy_action {
+            AfterCopy::Close => {
+                println!("Copied to clipboard and closing: {}", logged_text);
+                window_clone_copy.close();
+            }
+            AfterCopy::KeepOpen => println!("Copied to clipboard: {}", logged_text),
+        }
+    });
+
+    // --- Persist state on exit ---
+    // Runs when the window closes for any reason, including SIGINT/SIGTERM (see
+    // install_signal_handlers)
+    let state_exit = state.clone();
+    window.connect_close_request(move |window| {
+        flush_on_exit(&state_exit, window);
+        glib::Propagation::Proceed
     });
 
     // Present window
     window.present();
 }
+
+/// Closes the application's windows on SIGINT and SIGTERM, so their settings are written
+///
+/// Session managers stop apps with SIGTERM, terminals with SIGINT. The handlers run on the
+/// main loop, not in signal context, so no state can be mid-borrow. Install them once, at
+/// startup: the window is rebuilt on every activation, and handlers added per window would
+/// pile up and keep the closed windows alive.
+///
+/// # Arguments
+///
+/// * `app` - The application whose windows are closed
+#[cfg(unix)]
+pub fn install_signal_handlers(app: &Application) {
+    for signum in [SIGINT, SIGTERM] {
+        let app = app.downgrade();
+        glib::unix_signal_add_local(signum, move || {
+            println!("Received signal {}, closing", signum);
+            if let Some(app) = app.upgrade() {
+                for window in app.windows() {
+                    window.close();
+                }
+            }
+            glib::ControlFlow::Break
+        });
+    }
+}
+
+// Signal numbers, the same on every Unix GLib supports
+#[cfg(unix)]
+const SIGINT: i32 = 2;
+#[cfg(unix)]
+const SIGTERM: i32 = 15;
+
+// --- Write the active language and window size, once ---
+fn flush_on_exit(state: &AppState, window: &ApplicationWindow) {
+    if !state.begin_exit_flush() {
+        return;
+    }
+    let (width, height) = window.default_size();
+    let exit_state = ExitState {
+        // A pinned target stays out of the settings, unless it was picked by hand (which saved
+        // it already)
+        last_language: state
+            .active_language()
+            .filter(|lang| Some(*lang) != state.config().pinned_target_language),
+        // Not the maximized size, so unmaximizing after the next launch still works
+        window_size: (!window.is_maximized() && width > 0 && height > 0).then_some((width, height)),
+    };
+    // Language changes waiting to be saved first, so the exit state is compared with them
+    if let Err(e) = settings::flush_settings() {
+        eprintln!("Failed to save settings on exit: {}", e);
+    }
+    if let Err(e) = settings::flush_exit_state(&exit_state) {
+        eprintln!("Failed to save settings on exit: {}", e);
+    }
+}
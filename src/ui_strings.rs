@@ -0,0 +1,54 @@
+// Texts shown while the window waits for the clipboard or the API. They can be replaced
+// through the ui_strings config map, e.g. for a localized build; placeholders in braces
+// are filled in where the text is shown.
+use std::collections::HashMap;
+
+pub const READING_CLIPBOARD: &str = "reading_clipboard";
+pub const TRANSLATING: &str = "translating"; // {language}
+pub const TRANSLATING_IN_PARTS: &str = "translating_in_parts"; // {language}, {max_chars}
+pub const TRANSLATING_MULTIPLE: &str = "translating_multiple"; // {count}
+pub const RATE_LIMITED: &str = "rate_limited";
+
+// Every key with its English default
+pub const DEFAULTS: &[(&str, &str)] = &[
+    (READING_CLIPBOARD, "Reading clipboard..."),
+    (TRANSLATING, "Translating to {language}..."),
+    (
+        TRANSLATING_IN_PARTS,
+        "Text is longer than {max_chars} characters, translating to {language} in parts...",
+    ),
+    (
+        TRANSLATING_MULTIPLE,
+        "Translating to {count} language(s)...",
+    ),
+    (RATE_LIMITED, "Rate limited, waiting..."),
+];
+
+// Whether `key` is one of the texts that can be replaced
+pub fn is_known(key: &str) -> bool {
+    DEFAULTS.iter().any(|(name, _)| *name == key)
+}
+
+// The configured text for `key`, or its English default if there is no override.
+// Unknown keys resolve to the key itself, so a typo shows up in the window.
+pub fn resolve<'a>(overrides: &'a HashMap<String, String>, key: &'a str) -> &'a str {
+    overrides
+        .get(key)
+        .map(String::as_str)
+        .or_else(|| {
+            DEFAULTS
+                .iter()
+                .find(|(name, _)| *name == key)
+                .map(|(_, text)| *text)
+        })
+        .unwrap_or(key)
+}
+
+// Replace each {name} placeholder in `template` with its value
+pub fn fill(template: &str, values: &[(&str, String)]) -> String {
+    values
+        .iter()
+        .fold(template.to_string(), |text, (name, value)| {
+            text.replace(&format!("{{{}}}", name), value)
+        })
+}
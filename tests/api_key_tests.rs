@@ -0,0 +1,100 @@
+use std::fs;
+use std::path::PathBuf;
+
+// Import the crate to test
+use translator::api_key::{api_key_source_with, resolve_api_key_with};
+use translator::config::Config;
+
+// Config with the given key file and inline key
+fn key_config(api_key_file: Option<PathBuf>, api_key: Option<&str>) -> Config {
+    Config {
+        api_key_file,
+        api_key: api_key.map(str::to_string),
+        ..Config::default()
+    }
+}
+
+#[test]
+fn test_env_key_takes_precedence() {
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp directory");
+    let key_file = temp_dir.path().join("api_key");
+    fs::write(&key_file, "file-key\n").expect("Failed to write key file");
+
+    let config = key_config(Some(key_file), Some("inline-key"));
+    assert_eq!(
+        resolve_api_key_with(Some("env-key".to_string()), &config),
+        Ok("env-key".to_string())
+    );
+}
+
+#[test]
+fn test_key_file_used_without_env_key() {
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp directory");
+    let key_file = temp_dir.path().join("api_key");
+    fs::write(&key_file, "  file-key\n").expect("Failed to write key file");
+
+    // Blank env values count as unset; the file content is trimmed
+    let config = key_config(Some(key_file), Some("inline-key"));
+    assert_eq!(
+        resolve_api_key_with(Some("   ".to_string()), &config),
+        Ok("file-key".to_string())
+    );
+}
+
+#[test]
+fn test_inline_key_used_last() {
+    let config = key_config(None, Some("inline-key"));
+    assert_eq!(
+        resolve_api_key_with(None, &config),
+        Ok("inline-key".to_string())
+    );
+}
+
+#[test]
+fn test_unreadable_or_empty_key_file_is_an_error() {
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp directory");
+
+    let missing_file = temp_dir.path().join("missing");
+    let config = key_config(Some(missing_file), Some("inline-key"));
+    let error = resolve_api_key_with(None, &config).unwrap_err();
+    assert!(
+        error.starts_with("Error: Could not read API key file"),
+        "{}",
+        error
+    );
+
+    let empty_file = temp_dir.path().join("empty");
+    fs::write(&empty_file, "\n").expect("Failed to write key file");
+    let config = key_config(Some(empty_file), None);
+    let error = resolve_api_key_with(None, &config).unwrap_err();
+    assert!(error.ends_with("is empty."), "{}", error);
+}
+
+#[test]
+fn test_no_api_key_anywhere() {
+    assert_eq!(
+        resolve_api_key_with(None, &key_config(None, None)),
+        Err("Error: No API key found. Set the OPENROUTER_API_KEY environment variable, or 'api_key_file' or 'api_key' in the config.".to_string())
+    );
+}
+
+#[test]
+fn test_api_key_source() {
+    let key_file = PathBuf::from("/etc/translator/api_key");
+    let config = key_config(Some(key_file), Some("sk-inline"));
+
+    // Same order as resolve_api_key
+    assert_eq!(
+        api_key_source_with(Some("sk-env".to_string()), &config),
+        "the OPENROUTER_API_KEY environment variable"
+    );
+    assert_eq!(
+        api_key_source_with(Some(" ".to_string()), &config),
+        "the file \"/etc/translator/api_key\" ('api_key_file')"
+    );
+    assert_eq!(
+        api_key_source_with(None, &key_config(None, Some("sk-inline"))),
+        "'api_key' in the config"
+    );
+    assert!(api_key_source_with(None, &key_config(None, None)).starts_with("nowhere"));
+}
@@ -0,0 +1,130 @@
+use lingua::Language;
+use std::time::Duration;
+use translator::app_state::AppState;
+use translator::config::Config;
+
+#[test]
+fn test_app_state_starts_empty() {
+    let state = AppState::new(Config {
+        primary_language: Language::German,
+        ..Config::default()
+    });
+
+    assert_eq!(state.config().primary_language, Language::German);
+    assert_eq!(state.original_text(), None);
+    assert_eq!(state.api_key(), None);
+    assert_eq!(state.multi_text(), None);
+    assert!(state.language_buttons().is_empty());
+    assert!(!state.take_skipped_same_language());
+    assert_eq!(state.active_language(), None);
+}
+
+#[test]
+fn test_app_state_setters() {
+    let state = AppState::new(Config::default());
+
+    state.set_original_text(Some("Hallo".to_string()));
+    state.set_api_key(Some("test-key".to_string()));
+    state.set_multi_text(Some("German:\nHallo".to_string()));
+    assert_eq!(state.original_text().as_deref(), Some("Hallo"));
+    assert_eq!(state.api_key().as_deref(), Some("test-key"));
+    assert_eq!(state.multi_text().as_deref(), Some("German:\nHallo"));
+
+    state.set_original_text(None);
+    assert_eq!(state.original_text(), None);
+}
+
+#[test]
+fn test_skipped_same_language_is_taken_once() {
+    let state = AppState::new(Config::default());
+
+    state.set_skipped_same_language(true);
+    assert!(state.take_skipped_same_language());
+    // Cleared by taking it, so only the first click forces a translation
+    assert!(!state.take_skipped_same_language());
+}
+
+#[test]
+fn test_exit_flush_happens_once() {
+    let state = AppState::new(Config::default());
+
+    // A signal closing the window and the close itself both try to flush
+    assert!(state.begin_exit_flush());
+    assert!(!state.begin_exit_flush());
+}
+
+#[tokio::test]
+async fn test_rate_limiter_is_shared() {
+    let state = AppState::new(Config {
+        requests_per_minute: 1,
+        ..Config::default()
+    });
+
+    // Every handler gets a clone of the same limiter, so they share its budget
+    state.rate_limiter().acquire().await;
+    assert!(!state.rate_limiter().has_token());
+}
+
+#[test]
+fn test_auto_close_decision() {
+    // Not configured: never close
+    let state = AppState::new(Config::default());
+    assert_eq!(state.auto_close_delay(true), None);
+    let state = AppState::new(Config {
+        auto_close_ms: Some(0),
+        ..Config::default()
+    });
+    assert_eq!(state.auto_close_delay(true), None);
+
+    let state = AppState::new(Config {
+        auto_close_ms: Some(3000),
+        ..Config::default()
+    });
+    assert_eq!(
+        state.auto_close_delay(true),
+        Some(Duration::from_millis(3000))
+    );
+    // An error stays on screen until the user closes it
+    assert_eq!(state.auto_close_delay(false), None);
+}
+
+#[test]
+fn test_auto_close_cancelled_by_interaction() {
+    let state = AppState::new(Config {
+        auto_close_ms: Some(3000),
+        ..Config::default()
+    });
+
+    // Nothing pending yet, cancelling still keeps later translations from closing the window
+    state.cancel_auto_close();
+    assert_eq!(state.auto_close_delay(true), None);
+    state.clear_auto_close();
+    assert_eq!(state.auto_close_delay(true), None);
+}
+
+#[test]
+fn test_auto_switch_undo() {
+    let state = AppState::new(Config::default());
+    assert_eq!(state.take_auto_switch(), None);
+
+    // Undo restores the language from before the switch, once
+    assert_eq!(
+        state.record_auto_switch(Language::French, Language::English),
+        Some(Language::French)
+    );
+    assert_eq!(state.take_auto_switch(), Some(Language::French));
+    assert_eq!(state.take_auto_switch(), None);
+
+    // A second switch, e.g. by the detection on the whole text, keeps the first language
+    state.record_auto_switch(Language::French, Language::English);
+    assert_eq!(
+        state.record_auto_switch(Language::English, Language::German),
+        Some(Language::French)
+    );
+    // Switching back to it leaves nothing to undo
+    assert_eq!(
+        state.record_auto_switch(Language::German, Language::French),
+        None
+    );
+    assert_eq!(state.take_auto_switch(), None);
+}
@@ -0,0 +1,94 @@
+use translator::cli::{help_text, parse_args, version_text, Command};
+
+#[test]
+fn test_no_args_starts_gui() {
+    assert_eq!(parse_args(Vec::<String>::new()), Command::Gui);
+    // Arguments we don't know about are left to GTK
+    assert_eq!(parse_args(["--gapplication-service"]), Command::Gui);
+}
+
+#[test]
+fn test_version_and_help_flags() {
+    assert_eq!(parse_args(["--version"]), Command::Version);
+    assert_eq!(parse_args(["-V"]), Command::Version);
+    assert_eq!(parse_args(["--help"]), Command::Help);
+    assert_eq!(parse_args(["-h"]), Command::Help);
+    assert_eq!(parse_args(["--silent"]), Command::Silent);
+    assert_eq!(parse_args(["--list-languages"]), Command::ListLanguages);
+    assert_eq!(parse_args(["--selftest"]), Command::SelfTest);
+    assert_eq!(parse_args(["--reset-language"]), Command::ResetLanguage);
+    assert_eq!(parse_args(["--trigger"]), Command::Trigger);
+}
+
+#[test]
+fn test_flag_precedence() {
+    // --help wins regardless of position, then --version, --list-languages,
+    // --reset-language, --selftest, --silent and --trigger
+    assert_eq!(parse_args(["--version", "--help"]), Command::Help);
+    assert_eq!(parse_args(["--help", "--version"]), Command::Help);
+    assert_eq!(parse_args(["--silent", "--version"]), Command::Version);
+    assert_eq!(parse_args(["--version", "--silent"]), Command::Version);
+    assert_eq!(parse_args(["--silent", "-h"]), Command::Help);
+    assert_eq!(
+        parse_args(["--list-languages", "--version"]),
+        Command::Version
+    );
+    assert_eq!(
+        parse_args(["--version", "--list-languages"]),
+        Command::Version
+    );
+    assert_eq!(
+        parse_args(["--silent", "--list-languages"]),
+        Command::ListLanguages
+    );
+    assert_eq!(
+        parse_args(["--list-languages", "--silent"]),
+        Command::ListLanguages
+    );
+    assert_eq!(
+        parse_args(["--selftest", "--list-languages"]),
+        Command::ListLanguages
+    );
+    assert_eq!(parse_args(["--selftest", "--silent"]), Command::SelfTest);
+    assert_eq!(
+        parse_args(["--reset-language", "--list-languages"]),
+        Command::ListLanguages
+    );
+    assert_eq!(
+        parse_args(["--selftest", "--reset-language"]),
+        Command::ResetLanguage
+    );
+    assert_eq!(
+        parse_args(["--reset-language", "--silent"]),
+        Command::ResetLanguage
+    );
+    assert_eq!(parse_args(["--silent", "--selftest"]), Command::SelfTest);
+    assert_eq!(parse_args(["--trigger", "--silent"]), Command::Silent);
+    assert_eq!(parse_args(["--silent", "--trigger"]), Command::Silent);
+    assert_eq!(parse_args(["--trigger", "--selftest"]), Command::SelfTest);
+    assert_eq!(
+        parse_args(["--trigger", "--reset-language"]),
+        Command::ResetLanguage
+    );
+    assert_eq!(parse_args(["--trigger", "--version"]), Command::Version);
+}
+
+#[test]
+fn test_version_and_help_text() {
+    let version = version_text();
+    assert_eq!(version, format!("translator {}", env!("CARGO_PKG_VERSION")));
+
+    let help = help_text();
+    assert!(help.starts_with(&version));
+    for flag in [
+        "--silent",
+        "--list-languages",
+        "--reset-language",
+        "--selftest",
+        "--trigger",
+        "--version",
+        "--help",
+    ] {
+        assert!(help.contains(flag), "help is missing {}", flag);
+    }
+}
@@ -0,0 +1,13 @@
+// Helpers shared by the test files that mock the chat completions API
+use wiremock::ResponseTemplate;
+
+// Chat completion reply with the given content and finish reason ("stop", or "length" for a
+// reply cut off at max_tokens)
+pub fn chat_completion_response(content: &str, finish_reason: &str) -> ResponseTemplate {
+    let body = format!(
+        r#"{{"id":"chatcmpl-test","object":"chat.completion","created":1700000000,"model":"test-model","choices":[{{"index":0,"message":{{"role":"assistant","content":{}}},"finish_reason":{}}}]}}"#,
+        serde_json::Value::from(content), // Quoted and escaped
+        serde_json::Value::from(finish_reason)
+    );
+    ResponseTemplate::new(200).set_body_raw(body, "application/json")
+}
@@ -1,10 +1,17 @@
 use lingua::{IsoCode639_1, Language};
 use std::env;
 use std::fs;
+use std::path::Path;
 use std::str::FromStr;
+use std::time::{Duration, SystemTime};
 
 // Import the crate to test
-use translator::config::{load_config, save_config, Config};
+use translator::config::{
+    config_file_uri, get_config_path, load_config, parse_with_local_overrides,
+    prune_config_backups, save_config, supported_language_codes, BackupPolicy, Config,
+    ConfigFormat, ConfigWarning, MAX_FONT_SCALE, MIN_FONT_SCALE,
+};
+use translator::paths::is_read_only_dir;
 
 #[test]
 fn test_config_default() {
@@ -113,6 +120,11 @@ fn test_config_missing_file() {
     assert_eq!(config.primary_language, Language::English);
     assert_eq!(config.secondary_language, Language::French);
 
+    // The commented example is written for the user to edit
+    let written = fs::read_to_string(temp_dir.path().join("translator").join("config.toml"))
+        .expect("Config file should be created");
+    assert_eq!(written, Config::example_toml());
+
     // Restore original environment
     if let Some(original) = original_config_home {
         env::set_var("XDG_CONFIG_HOME", original);
@@ -151,6 +163,41 @@ fn test_config_invalid_toml() {
     }
 }
 
+#[test]
+fn test_invalid_config_kept_without_backups() {
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp directory");
+    let config_dir = temp_dir.path().join("translator");
+    fs::create_dir_all(&config_dir).expect("Failed to create config directory");
+
+    let original_config_home = env::var("XDG_CONFIG_HOME").ok();
+    env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+
+    // Valid TOML, but a field has the wrong type
+    let contents =
+        "model_version = \"my-model\"\nmax_tokens = \"many\"\nbackup_invalid_config = false\n";
+    let config_file = config_dir.join("config.toml");
+    fs::write(&config_file, contents).expect("Failed to write invalid config");
+
+    let config = load_config();
+    assert_eq!(config.model_version, Config::default().model_version);
+
+    // Neither backed up nor replaced by the defaults
+    assert_eq!(
+        fs::read_to_string(&config_file).expect("Config file should still exist"),
+        contents
+    );
+    let files = fs::read_dir(&config_dir)
+        .expect("Failed to read config directory")
+        .count();
+    assert_eq!(files, 1);
+
+    if let Some(original) = original_config_home {
+        env::set_var("XDG_CONFIG_HOME", original);
+    } else {
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+}
+
 #[test]
 fn test_config_all_target_languages() {
     let config = Config::default();
@@ -248,6 +295,47 @@ fn test_config_save_permissions_error() {
     }
 }
 
+#[test]
+fn test_read_only_config_dir_falls_back_to_defaults() {
+    if cfg!(target_os = "windows") {
+        return;
+    }
+
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp directory");
+    let config_dir = temp_dir.path().join("translator");
+    fs::create_dir_all(&config_dir).expect("Failed to create config directory");
+
+    let original_config_home = env::var("XDG_CONFIG_HOME").ok();
+    env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(&config_dir, fs::Permissions::from_mode(0o555))
+        .expect("Failed to set permissions");
+
+    // No config file and none can be written: the app runs with the defaults
+    let config = load_config();
+    assert_eq!(config.primary_language, Config::default().primary_language);
+    assert!(is_read_only_dir(&config_dir));
+
+    // Saves after the first failure are skipped quietly instead of failing again
+    assert!(save_config(&config).is_ok());
+    assert!(translator::settings::save_last_language(Language::German).is_ok());
+    assert!(translator::settings::save_last_language(Language::French).is_ok());
+    assert_eq!(
+        fs::read_dir(&config_dir)
+            .expect("Failed to read config directory")
+            .count(),
+        0
+    );
+
+    fs::set_permissions(&config_dir, fs::Permissions::from_mode(0o755)).ok();
+    if let Some(original) = original_config_home {
+        env::set_var("XDG_CONFIG_HOME", original);
+    } else {
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+}
+
 #[test]
 fn test_config_serialization_with_empty_languages() {
     let mut config = Config::default();
@@ -262,3 +350,1148 @@ fn test_config_serialization_with_empty_languages() {
     // Check that empty list is preserved
     assert_eq!(deserialized.all_target_languages.len(), 0);
 }
+
+#[test]
+fn test_config_validate_default_has_no_warnings() {
+    let config = Config::default();
+    assert!(config.validate().is_empty());
+}
+
+#[test]
+fn test_config_validate_empty_targets() {
+    let mut config = Config::default();
+    config.all_target_languages = vec![];
+
+    assert_eq!(config.validate(), vec![ConfigWarning::EmptyTargets]);
+}
+
+#[test]
+fn test_config_validate_primary_not_in_targets() {
+    let mut config = Config::default();
+    config.primary_language = Language::German;
+
+    assert_eq!(
+        config.validate(),
+        vec![ConfigWarning::PrimaryNotInTargets(Language::German)]
+    );
+}
+
+#[test]
+fn test_config_validate_secondary_not_in_targets() {
+    let mut config = Config::default();
+    config.secondary_language = Language::Spanish;
+
+    assert_eq!(
+        config.validate(),
+        vec![ConfigWarning::SecondaryNotInTargets(Language::Spanish)]
+    );
+}
+
+#[test]
+fn test_config_validate_invalid_api_url() {
+    for url in ["", "openrouter.ai/api/v1", "ftp://example.com", "https://"] {
+        let mut config = Config::default();
+        config.api_url = url.to_string();
+
+        assert_eq!(
+            config.validate(),
+            vec![ConfigWarning::InvalidApiUrl(url.to_string())],
+            "Expected invalid URL warning for: '{}'",
+            url
+        );
+    }
+}
+
+#[test]
+fn test_config_validate_empty_model_version() {
+    let mut config = Config::default();
+    config.model_version = "   ".to_string();
+
+    assert_eq!(config.validate(), vec![ConfigWarning::EmptyModelVersion]);
+}
+
+#[test]
+fn test_config_validate_same_languages() {
+    let mut config = Config::default();
+    config.secondary_language = config.primary_language;
+
+    assert_eq!(
+        config.validate(),
+        vec![ConfigWarning::SameLanguages(Language::English)]
+    );
+}
+
+#[test]
+fn test_config_mode() {
+    let mut config = Config::default();
+    assert_eq!(config.mode, "translate");
+    assert!(!config.proofread());
+
+    for mode in ["proofread", " Proofread "] {
+        config.mode = mode.to_string();
+        assert!(config.proofread(), "{:?}", mode);
+        assert!(config.validate().is_empty(), "{:?}", mode);
+    }
+
+    // Unknown modes translate, with a warning
+    config.mode = "summarize".to_string();
+    assert!(!config.proofread());
+    assert_eq!(
+        config.validate(),
+        vec![ConfigWarning::UnknownMode("summarize".to_string())]
+    );
+}
+
+#[test]
+fn test_config_validate_button_label_case() {
+    let mut config = Config::default();
+    assert_eq!(config.button_label_case, "upper");
+
+    for case in ["lower", "Title", " upper "] {
+        config.button_label_case = case.to_string();
+        assert!(config.validate().is_empty(), "{:?}", case);
+    }
+
+    config.button_label_case = "camel".to_string();
+    assert_eq!(
+        config.validate(),
+        vec![ConfigWarning::UnknownButtonLabelCase("camel".to_string())]
+    );
+}
+
+#[test]
+fn test_config_validate_register() {
+    let mut config = Config::default();
+    assert_eq!(config.register, "neutral");
+
+    for register in ["formal", "Informal", " neutral "] {
+        config.register = register.to_string();
+        assert!(config.validate().is_empty(), "{:?}", register);
+    }
+
+    config.register = "polite".to_string();
+    assert_eq!(
+        config.validate(),
+        vec![ConfigWarning::UnknownRegister("polite".to_string())]
+    );
+}
+
+#[test]
+fn test_config_provider_routing() {
+    let config: Config = toml::from_str(
+        r#"provider_routing = { order = ["openai", "azure"], allow_fallbacks = false }"#,
+    )
+    .expect("Config with provider_routing should parse");
+    assert_eq!(
+        config.provider_routing,
+        Some(serde_json::json!({"order": ["openai", "azure"], "allow_fallbacks": false}))
+    );
+    assert!(config.validate().is_empty());
+
+    // Anything but a table is reported
+    let config: Config = toml::from_str(r#"provider_routing = "openai""#)
+        .expect("Config with a string provider_routing should parse");
+    assert_eq!(
+        config.validate(),
+        vec![ConfigWarning::ProviderRoutingNotObject]
+    );
+
+    // JSON configs take the object as it is
+    let config: Config =
+        serde_json::from_str(r#"{"provider_routing": {"data_collection": "deny"}}"#)
+            .expect("JSON config with provider_routing should parse");
+    assert_eq!(
+        config.provider_routing,
+        Some(serde_json::json!({"data_collection": "deny"}))
+    );
+}
+
+#[test]
+fn test_config_file_uri() {
+    // Create a temporary directory for the test
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp directory");
+
+    // Set the config directory for this test
+    let original_config_home = env::var("XDG_CONFIG_HOME").ok();
+    env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+
+    let uri = config_file_uri().expect("Should build a URI for an absolute path");
+    assert!(uri.starts_with("file://"), "Unexpected URI: {}", uri);
+    assert!(
+        uri.ends_with("/translator/config.toml"),
+        "Unexpected URI: {}",
+        uri
+    );
+
+    // Restore original environment
+    if let Some(original) = original_config_home {
+        env::set_var("XDG_CONFIG_HOME", original);
+    } else {
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+}
+
+#[test]
+fn test_config_fallback_target_language() {
+    // Defaults to the primary language when not set
+    let mut config = Config::default();
+    assert_eq!(config.fallback_target_language, None);
+    assert_eq!(config.fallback_language(), Language::English);
+
+    // Not written to the file when unset
+    let toml_string = toml::to_string_pretty(&config).expect("Failed to serialize config");
+    assert!(!toml_string.contains("fallback_target_language"));
+
+    // Serialized as an ISO code when set
+    config.fallback_target_language = Some(Language::German);
+    let toml_string = toml::to_string_pretty(&config).expect("Failed to serialize config");
+    assert!(toml_string.contains("fallback_target_language = \"DE\""));
+
+    let deserialized: Config = toml::from_str(&toml_string).expect("Failed to deserialize config");
+    assert_eq!(
+        deserialized.fallback_target_language,
+        Some(Language::German)
+    );
+    assert_eq!(deserialized.fallback_language(), Language::German);
+}
+
+#[test]
+fn test_config_load_deduplicates_target_languages() {
+    // Create a temporary directory for the test
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp directory");
+    let config_dir = temp_dir.path().join("translator");
+    fs::create_dir_all(&config_dir).expect("Failed to create config directory");
+
+    // Set the config directory for this test
+    let original_config_home = env::var("XDG_CONFIG_HOME").ok();
+    env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+
+    // Write a config listing some languages more than once
+    let config_file = config_dir.join("config.toml");
+    fs::write(
+        &config_file,
+        r#"
+api_url = "https://openrouter.ai/api/v1"
+model_version = "openai/gpt-4o"
+primary_language = "EN"
+secondary_language = "FR"
+all_target_languages = ["EN", "FR", "EN", "IT", "FR", "PL", "IT"]
+"#,
+    )
+    .expect("Failed to write config");
+
+    // Duplicates are dropped, first-seen order is kept
+    let config = load_config();
+    assert_eq!(
+        config.all_target_languages,
+        vec![
+            Language::English,
+            Language::French,
+            Language::Italian,
+            Language::Polish
+        ]
+    );
+
+    // Restore original environment
+    if let Some(original) = original_config_home {
+        env::set_var("XDG_CONFIG_HOME", original);
+    } else {
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+}
+
+#[test]
+fn test_config_save_deduplicates_target_languages() {
+    // Create a temporary directory for the test
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp directory");
+
+    // Set the config directory for this test
+    let original_config_home = env::var("XDG_CONFIG_HOME").ok();
+    env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+
+    let mut config = Config::default();
+    config.all_target_languages = vec![
+        Language::Polish,
+        Language::English,
+        Language::Polish,
+        Language::French,
+        Language::English,
+    ];
+    save_config(&config).expect("Failed to save config");
+
+    // The written file only lists each language once, in first-seen order
+    let contents = fs::read_to_string(temp_dir.path().join("translator").join("config.toml"))
+        .expect("Failed to read config");
+    let saved: Config = toml::from_str(&contents).expect("Failed to parse saved config");
+    assert_eq!(
+        saved.all_target_languages,
+        vec![Language::Polish, Language::English, Language::French]
+    );
+
+    // Restore original environment
+    if let Some(original) = original_config_home {
+        env::set_var("XDG_CONFIG_HOME", original);
+    } else {
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+}
+
+#[test]
+fn test_config_add_missing_primary_secondary_order() {
+    let mut config = Config {
+        primary_language: Language::German,
+        secondary_language: Language::Spanish,
+        all_target_languages: vec![Language::Polish, Language::Italian],
+        ..Config::default()
+    };
+
+    // Primary first, then secondary, after the languages already listed
+    assert_eq!(
+        config.add_missing_primary_secondary(),
+        vec![Language::German, Language::Spanish]
+    );
+    assert_eq!(
+        config.all_target_languages,
+        vec![
+            Language::Polish,
+            Language::Italian,
+            Language::German,
+            Language::Spanish
+        ]
+    );
+    assert!(config.add_missing_primary_secondary().is_empty());
+
+    // A listed primary keeps its position
+    let mut config = Config {
+        primary_language: Language::Italian,
+        secondary_language: Language::Spanish,
+        all_target_languages: vec![Language::Polish, Language::Italian],
+        ..Config::default()
+    };
+    assert_eq!(
+        config.add_missing_primary_secondary(),
+        vec![Language::Spanish]
+    );
+    assert_eq!(
+        config.all_target_languages,
+        vec![Language::Polish, Language::Italian, Language::Spanish]
+    );
+}
+
+#[test]
+fn test_config_save_is_idempotent_in_ordering() {
+    // Create a temporary directory for the test
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp directory");
+    let config_file = temp_dir.path().join("translator").join("config.toml");
+
+    // Set the config directory for this test
+    let original_config_home = env::var("XDG_CONFIG_HOME").ok();
+    env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+
+    let config = Config {
+        primary_language: Language::German,
+        secondary_language: Language::Spanish,
+        all_target_languages: vec![Language::Polish, Language::Italian, Language::Polish],
+        ..Config::default()
+    };
+    save_config(&config).expect("Failed to save config");
+    let first_contents = fs::read_to_string(&config_file).expect("Failed to read config");
+    let first: Config = toml::from_str(&first_contents).expect("Failed to parse saved config");
+    assert_eq!(
+        first.all_target_languages,
+        vec![
+            Language::Polish,
+            Language::Italian,
+            Language::German,
+            Language::Spanish
+        ]
+    );
+
+    // Saving what was loaded writes the same file
+    save_config(&first).expect("Failed to save config");
+    let second_contents = fs::read_to_string(&config_file).expect("Failed to read config");
+    assert_eq!(second_contents, first_contents);
+    let second: Config = toml::from_str(&second_contents).expect("Failed to parse saved config");
+    assert_eq!(second, first);
+
+    // Restore original environment
+    if let Some(original) = original_config_home {
+        env::set_var("XDG_CONFIG_HOME", original);
+    } else {
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+}
+
+#[test]
+fn test_config_save_skips_unchanged_file() {
+    // Create a temporary directory for the test
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp directory");
+    let config_file = temp_dir.path().join("translator").join("config.toml");
+
+    // Set the config directory for this test
+    let original_config_home = env::var("XDG_CONFIG_HOME").ok();
+    env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+
+    let config = Config::default();
+    save_config(&config).expect("Failed to save config");
+
+    // Backdate the file so any rewrite would show in its modification time
+    let backdated = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+    fs::File::options()
+        .write(true)
+        .open(&config_file)
+        .and_then(|file| file.set_modified(backdated))
+        .expect("Failed to backdate config");
+
+    // Saving the same config leaves the file alone
+    save_config(&config).expect("Failed to save config");
+    let modified = fs::metadata(&config_file)
+        .and_then(|metadata| metadata.modified())
+        .expect("Failed to read modification time");
+    assert_eq!(modified, backdated);
+
+    // A changed config is written
+    let changed = Config {
+        max_tokens: config.max_tokens + 1,
+        ..config
+    };
+    save_config(&changed).expect("Failed to save config");
+    let modified = fs::metadata(&config_file)
+        .and_then(|metadata| metadata.modified())
+        .expect("Failed to read modification time");
+    assert_ne!(modified, backdated);
+    let saved: Config = toml::from_str(&fs::read_to_string(&config_file).unwrap())
+        .expect("Failed to parse saved config");
+    assert_eq!(saved.max_tokens, changed.max_tokens);
+
+    // Restore original environment
+    if let Some(original) = original_config_home {
+        env::set_var("XDG_CONFIG_HOME", original);
+    } else {
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+}
+
+#[test]
+fn test_config_load_splits_identical_primary_and_secondary() {
+    // Create a temporary directory for the test
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp directory");
+    let config_dir = temp_dir.path().join("translator");
+    fs::create_dir_all(&config_dir).expect("Failed to create config directory");
+
+    // Set the config directory for this test
+    let original_config_home = env::var("XDG_CONFIG_HOME").ok();
+    env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+
+    // Write a config where primary and secondary are the same language
+    let config_file = config_dir.join("config.toml");
+    fs::write(
+        &config_file,
+        r#"
+api_url = "https://openrouter.ai/api/v1"
+model_version = "openai/gpt-4o"
+primary_language = "DE"
+secondary_language = "DE"
+all_target_languages = ["DE", "ES", "IT"]
+"#,
+    )
+    .expect("Failed to write config");
+
+    // The first other target language becomes the secondary
+    let config = load_config();
+    assert_eq!(config.primary_language, Language::German);
+    assert_eq!(config.secondary_language, Language::Spanish);
+
+    // Restore original environment
+    if let Some(original) = original_config_home {
+        env::set_var("XDG_CONFIG_HOME", original);
+    } else {
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+}
+
+#[test]
+fn test_config_ensure_distinct_secondary_without_other_targets() {
+    let mut config = Config::default();
+    config.primary_language = Language::German;
+    config.secondary_language = Language::German;
+    config.all_target_languages = vec![Language::German];
+
+    // Falls back to English
+    assert!(config.ensure_distinct_secondary());
+    assert_eq!(config.secondary_language, Language::English);
+
+    // Nothing to do once they differ
+    assert!(!config.ensure_distinct_secondary());
+    assert_eq!(config.secondary_language, Language::English);
+}
+
+#[test]
+fn test_config_per_language_prompt_from_toml() {
+    let config: Config = toml::from_str(
+        r#"
+api_url = "https://openrouter.ai/api/v1"
+model_version = "openai/gpt-4o"
+primary_language = "EN"
+secondary_language = "FR"
+all_target_languages = ["EN", "FR", "DE"]
+
+[per_language_prompt]
+DE = "Translate into informal {target_language}."
+"#,
+    )
+    .expect("Failed to parse config");
+
+    assert_eq!(
+        config.per_language_prompt.get("DE").map(String::as_str),
+        Some("Translate into informal {target_language}.")
+    );
+
+    // Serialized as a table, omitted entirely when empty
+    let serialized = toml::to_string_pretty(&config).expect("Failed to serialize config");
+    assert!(
+        serialized.contains("[per_language_prompt]"),
+        "{}",
+        serialized
+    );
+    let default_serialized =
+        toml::to_string_pretty(&Config::default()).expect("Failed to serialize config");
+    assert!(!default_serialized.contains("per_language_prompt"));
+}
+
+#[test]
+fn test_config_font_scale_from_toml() {
+    let base = r#"
+api_url = "https://openrouter.ai/api/v1"
+model_version = "openai/gpt-4o"
+primary_language = "EN"
+secondary_language = "FR"
+"#;
+
+    // Missing field uses the default
+    let config: Config = toml::from_str(base).expect("Failed to parse config");
+    assert_eq!(config.font_scale, 1.0);
+
+    let config: Config =
+        toml::from_str(&format!("{}font_scale = 1.5\n", base)).expect("Failed to parse config");
+    assert_eq!(config.font_scale, 1.5);
+}
+
+#[test]
+fn test_config_clamp_font_scale() {
+    let mut config = Config::default();
+
+    // In range values are kept
+    config.font_scale = 2.0;
+    assert!(!config.clamp_font_scale());
+    assert_eq!(config.font_scale, 2.0);
+
+    config.font_scale = 10.0;
+    assert!(config.clamp_font_scale());
+    assert_eq!(config.font_scale, MAX_FONT_SCALE);
+
+    config.font_scale = 0.1;
+    assert!(config.clamp_font_scale());
+    assert_eq!(config.font_scale, MIN_FONT_SCALE);
+
+    config.font_scale = -1.0;
+    assert!(config.clamp_font_scale());
+    assert_eq!(config.font_scale, MIN_FONT_SCALE);
+
+    // Not a number falls back to the default
+    config.font_scale = f64::NAN;
+    assert!(config.clamp_font_scale());
+    assert_eq!(config.font_scale, 1.0);
+}
+
+#[test]
+fn test_config_example_toml_round_trips_to_default() {
+    let example = Config::example_toml();
+
+    let parsed: Config = toml::from_str(&example).expect("Example config should parse");
+    assert_eq!(
+        toml::to_string(&parsed).unwrap(),
+        toml::to_string(&Config::default()).unwrap()
+    );
+
+    // Every field written with a value has a description right above it
+    let lines: Vec<&str> = example.lines().collect();
+    let defaults: toml::Table = toml::from_str(&toml::to_string(&Config::default()).unwrap())
+        .expect("Default config should parse as a table");
+    for field in defaults.keys() {
+        let index = lines
+            .iter()
+            .position(|line| line.starts_with(&format!("{} = ", field)))
+            .unwrap_or_else(|| panic!("Missing field {} in:\n{}", field, example));
+        assert!(
+            index > 0 && lines[index - 1].starts_with("# "),
+            "Field {} has no description",
+            field
+        );
+    }
+
+    // Optional fields are listed, commented out
+    for field in [
+        "api_key_file",
+        "api_key",
+        "fallback_target_language",
+        "forced_source_language",
+        "proxy_url",
+        "user_agent",
+    ] {
+        assert!(
+            example.contains(&format!("# {} = ", field)),
+            "Missing optional field {}",
+            field
+        );
+    }
+    assert!(example.contains("# [per_language_prompt]"));
+    assert!(example.contains("# [ui_strings]"));
+}
+
+#[test]
+fn test_prune_config_backups_keeps_newest() {
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp directory");
+    for timestamp in [
+        1700000100u64,
+        1700000500,
+        1700000200,
+        1700000400,
+        1700000300,
+    ] {
+        let backup = temp_dir
+            .path()
+            .join(format!("config.toml.invalid_{}", timestamp));
+        fs::write(backup, "invalid").expect("Failed to write backup");
+    }
+    // Backups of other formats count too
+    fs::write(temp_dir.path().join("config.json.invalid_1700000600"), "").unwrap();
+    // Other files are never touched
+    fs::write(temp_dir.path().join("config.toml"), "").unwrap();
+    fs::write(temp_dir.path().join("notes.txt.invalid_1700000700"), "").unwrap();
+    fs::write(temp_dir.path().join("settings.toml"), "").unwrap();
+
+    let removed = prune_config_backups(temp_dir.path(), 2).expect("Failed to prune backups");
+    assert_eq!(removed, 4);
+
+    let mut remaining: Vec<String> = fs::read_dir(temp_dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    remaining.sort();
+    assert_eq!(
+        remaining,
+        vec![
+            "config.json.invalid_1700000600",
+            "config.toml",
+            "config.toml.invalid_1700000500",
+            "notes.txt.invalid_1700000700",
+            "settings.toml",
+        ]
+    );
+
+    // 0 keeps everything
+    assert_eq!(prune_config_backups(temp_dir.path(), 0).unwrap(), 0);
+}
+
+#[test]
+fn test_backup_policy_from_invalid_config() {
+    // Not TOML at all: defaults
+    assert_eq!(
+        BackupPolicy::from_invalid_config(ConfigFormat::Toml, "invalid toml content ["),
+        BackupPolicy {
+            enabled: true,
+            max_backups: 5
+        }
+    );
+
+    // Valid TOML with a bad field elsewhere still honors the backup settings
+    assert_eq!(
+        BackupPolicy::from_invalid_config(
+            ConfigFormat::Toml,
+            "primary_language = 42\nbackup_invalid_config = false\nmax_config_backups = 2\n"
+        ),
+        BackupPolicy {
+            enabled: false,
+            max_backups: 2
+        }
+    );
+
+    // The same in a JSON config
+    assert_eq!(
+        BackupPolicy::from_invalid_config(
+            ConfigFormat::Json,
+            r#"{ "primary_language": 42, "backup_invalid_config": false, "max_config_backups": 3 }"#
+        ),
+        BackupPolicy {
+            enabled: false,
+            max_backups: 3
+        }
+    );
+}
+
+#[test]
+fn test_invalid_json_config_backed_up_as_json() {
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp directory");
+    let config_dir = temp_dir.path().join("translator");
+    fs::create_dir_all(&config_dir).expect("Failed to create config directory");
+
+    let original_config_home = env::var("XDG_CONFIG_HOME").ok();
+    env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+
+    let config_file = config_dir.join("config.json");
+    fs::write(&config_file, "{ \"max_tokens\": ").expect("Failed to write invalid config");
+    // An old backup of a TOML config counts towards max_config_backups too
+    fs::write(config_dir.join("config.toml.invalid_1"), "").unwrap();
+
+    let config = load_config();
+    assert_eq!(config.max_tokens, Config::default().max_tokens);
+
+    let mut files: Vec<String> = fs::read_dir(&config_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    files.sort();
+    // The defaults are written as JSON in its place, no config.toml shadows it
+    assert_eq!(files.len(), 3, "{:?}", files);
+    assert_eq!(files[0], "config.json");
+    assert!(files[1].starts_with("config.json.invalid_"), "{:?}", files);
+    assert_eq!(files[2], "config.toml.invalid_1");
+    assert_eq!(get_config_path(), Some(config_file.clone()));
+    let written = fs::read_to_string(&config_file).expect("Config file should be written");
+    assert_eq!(ConfigFormat::Json.parse(&written), Ok(Config::default()));
+
+    if let Some(original) = original_config_home {
+        env::set_var("XDG_CONFIG_HOME", original);
+    } else {
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+}
+
+#[test]
+fn test_supported_language_codes_round_trip() {
+    let languages = supported_language_codes();
+    assert!(languages.contains(&("EN".to_string(), "English".to_string())));
+    assert!(languages.contains(&("ZH".to_string(), "Chinese".to_string())));
+    assert!(
+        languages.windows(2).all(|pair| pair[0].0 < pair[1].0),
+        "sorted by code"
+    );
+
+    // Every listed code is accepted by the config and reads back as the named language
+    let codes: Vec<toml::Value> = languages
+        .iter()
+        .map(|(code, _)| toml::Value::String(code.clone()))
+        .collect();
+    let mut value = toml::Value::try_from(Config::default()).expect("Failed to serialize config");
+    value.as_table_mut().expect("Config is a table").insert(
+        "all_target_languages".to_string(),
+        toml::Value::Array(codes),
+    );
+    let config: Config = value.try_into().expect("Failed to deserialize config");
+
+    assert_eq!(config.all_target_languages.len(), languages.len());
+    for (language, (code, name)) in config.all_target_languages.iter().zip(&languages) {
+        assert_eq!(&language.to_string(), name, "code {}", code);
+    }
+}
+
+#[test]
+fn test_config_format_from_extension() {
+    assert_eq!(
+        ConfigFormat::from_path(Path::new("config.toml")),
+        Some(ConfigFormat::Toml)
+    );
+    assert_eq!(
+        ConfigFormat::from_path(Path::new("/a/config.JSON")),
+        Some(ConfigFormat::Json)
+    );
+    assert_eq!(
+        ConfigFormat::from_path(Path::new("config.yaml")),
+        Some(ConfigFormat::Yaml)
+    );
+    assert_eq!(
+        ConfigFormat::from_path(Path::new("config.yml")),
+        Some(ConfigFormat::Yaml)
+    );
+    assert_eq!(ConfigFormat::from_path(Path::new("config.ini")), None);
+    assert_eq!(ConfigFormat::from_path(Path::new("config")), None);
+}
+
+#[test]
+fn test_config_round_trips_through_every_format() {
+    let mut config = Config {
+        primary_language: Language::German,
+        secondary_language: Language::Spanish,
+        all_target_languages: vec![Language::German, Language::Spanish, Language::Polish],
+        forced_source_language: Some(Language::Italian),
+        proxy_url: Some("http://proxy.example.com:8080".to_string()),
+        font_scale: 1.25,
+        ..Config::default()
+    };
+    config.per_language_prompt.insert(
+        "DE".to_string(),
+        "Translate into {target_language}.".to_string(),
+    );
+
+    for format in [ConfigFormat::Toml, ConfigFormat::Json, ConfigFormat::Yaml] {
+        let contents = format
+            .serialize(&config)
+            .expect("Failed to serialize config");
+        // Languages are stored as ISO codes in every format
+        assert!(
+            contents.contains("\"DE\"") || contents.contains("- DE"),
+            "{}",
+            contents
+        );
+        let parsed = format.parse(&contents).expect("Failed to parse config");
+        assert_eq!(parsed, config, "{:?} round trip", format);
+    }
+
+    // Each format rejects the others' syntax rather than misreading it
+    let json = ConfigFormat::Json.serialize(&config).unwrap();
+    assert!(ConfigFormat::Toml.parse(&json).is_err());
+}
+
+#[test]
+fn test_config_pinned_target_language_round_trip() {
+    let config = Config {
+        pinned_target_language: Some(Language::Polish),
+        lock_pinned_target: true,
+        ..Config::default()
+    };
+    assert!(config.validate().is_empty());
+
+    for format in [ConfigFormat::Toml, ConfigFormat::Json, ConfigFormat::Yaml] {
+        let contents = format
+            .serialize(&config)
+            .expect("Failed to serialize config");
+        assert!(contents.contains("PL"), "{}", contents);
+        let parsed = format.parse(&contents).expect("Failed to parse config");
+        assert_eq!(parsed.pinned_target_language, Some(Language::Polish));
+        assert!(parsed.lock_pinned_target);
+    }
+
+    // Unset by default and left out of the file
+    let toml = ConfigFormat::Toml.serialize(&Config::default()).unwrap();
+    assert!(!toml.contains("pinned_target_language"), "{}", toml);
+    let config: Config = toml::from_str(r#"pinned_target_language = "pl""#).unwrap();
+    assert_eq!(config.pinned_target_language, Some(Language::Polish));
+
+    // A pinned language without a button is reported
+    let config = Config {
+        pinned_target_language: Some(Language::German),
+        ..Config::default()
+    };
+    assert_eq!(
+        config.validate(),
+        vec![ConfigWarning::PinnedNotInTargets(Language::German)]
+    );
+}
+
+#[test]
+fn test_config_file_dispatch_by_extension() {
+    // Create a temporary directory for the test
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp directory");
+    let config_dir = temp_dir.path().join("translator");
+    fs::create_dir_all(&config_dir).expect("Failed to create config directory");
+
+    // Set the config directory for this test
+    let original_config_home = env::var("XDG_CONFIG_HOME").ok();
+    env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+
+    // Without any file, config.toml is the one to create
+    assert_eq!(get_config_path(), Some(config_dir.join("config.toml")));
+
+    // A JSON config is found, loaded and saved back as JSON
+    let json_path = config_dir.join("config.json");
+    let json_config = Config {
+        primary_language: Language::Italian,
+        ..Config::default()
+    };
+    fs::write(
+        &json_path,
+        ConfigFormat::Json.serialize(&json_config).unwrap(),
+    )
+    .expect("Failed to write config");
+    assert_eq!(get_config_path(), Some(json_path.clone()));
+    assert_eq!(load_config().primary_language, Language::Italian);
+
+    save_config(&Config {
+        primary_language: Language::Polish,
+        ..json_config
+    })
+    .expect("Failed to save config");
+    let contents = fs::read_to_string(&json_path).expect("Failed to read config");
+    assert!(contents.trim_start().starts_with('{'), "{}", contents);
+    assert_eq!(
+        ConfigFormat::Json
+            .parse(&contents)
+            .unwrap()
+            .primary_language,
+        Language::Polish
+    );
+    assert!(!config_dir.join("config.toml").exists());
+
+    // A YAML config is used when it's the only one
+    fs::remove_file(&json_path).expect("Failed to remove config");
+    let yaml_path = config_dir.join("config.yaml");
+    fs::write(
+        &yaml_path,
+        ConfigFormat::Yaml
+            .serialize(&Config {
+                primary_language: Language::Russian,
+                ..Config::default()
+            })
+            .unwrap(),
+    )
+    .expect("Failed to write config");
+    assert_eq!(get_config_path(), Some(yaml_path));
+    assert_eq!(load_config().primary_language, Language::Russian);
+
+    // TOML wins when there are several
+    fs::write(
+        config_dir.join("config.toml"),
+        ConfigFormat::Toml.serialize(&Config::default()).unwrap(),
+    )
+    .expect("Failed to write config");
+    assert_eq!(get_config_path(), Some(config_dir.join("config.toml")));
+
+    // Restore original environment
+    if let Some(original) = original_config_home {
+        env::set_var("XDG_CONFIG_HOME", original);
+    } else {
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+}
+
+#[test]
+fn test_config_language_names_are_normalized_to_codes() {
+    // Codes and names in any case can be mixed; saving writes codes
+    let config: Config = toml::from_str(
+        r#"
+api_url = "https://openrouter.ai/api/v1"
+model_version = "openai/gpt-4o"
+primary_language = "english"
+secondary_language = "fr"
+all_target_languages = ["EN", "French", " GERMAN ", "es"]
+"#,
+    )
+    .expect("Failed to parse config");
+
+    assert_eq!(config.primary_language, Language::English);
+    assert_eq!(config.secondary_language, Language::French);
+    assert_eq!(
+        config.all_target_languages,
+        vec![
+            Language::English,
+            Language::French,
+            Language::German,
+            Language::Spanish
+        ]
+    );
+    let saved = toml::to_string(&config).expect("Failed to serialize config");
+    assert!(saved.contains(r#"all_target_languages = ["EN", "FR", "DE", "ES"]"#));
+    assert!(saved.contains(r#"primary_language = "EN""#));
+}
+
+#[test]
+fn test_config_invalid_language_name_suggests_closest() {
+    let config_with_targets = |targets: &str| {
+        toml::from_str::<Config>(&format!(
+            r#"
+api_url = "https://openrouter.ai/api/v1"
+model_version = "openai/gpt-4o"
+primary_language = "EN"
+secondary_language = "FR"
+all_target_languages = {}
+"#,
+            targets
+        ))
+    };
+
+    let error = config_with_targets(r#"["EN", "Portugese"]"#)
+        .expect_err("Misspelled name should be rejected")
+        .to_string();
+    assert!(
+        error.contains("invalid language code or name in list: Portugese"),
+        "{}",
+        error
+    );
+    assert!(error.contains("did you mean Portuguese?"), "{}", error);
+
+    // Nothing close enough: no suggestion
+    let error = config_with_targets(r#"["EN", "Klingonese"]"#)
+        .expect_err("Unknown name should be rejected")
+        .to_string();
+    assert!(error.contains("Klingonese"), "{}", error);
+    assert!(!error.contains("did you mean"), "{}", error);
+
+    // The single-language fields suggest too
+    let error = toml::from_str::<Config>(
+        r#"
+api_url = "https://openrouter.ai/api/v1"
+model_version = "openai/gpt-4o"
+primary_language = "Ukranian"
+secondary_language = "FR"
+"#,
+    )
+    .expect_err("Misspelled name should be rejected")
+    .to_string();
+    assert!(error.contains("did you mean Ukrainian?"), "{}", error);
+}
+
+#[test]
+fn test_config_label_max_width_chars() {
+    assert_eq!(Config::default().label_max_width_chars, 60);
+
+    let toml_with = |extra: &str| {
+        format!(
+            r#"
+api_url = "https://openrouter.ai/api/v1"
+model_version = "openai/gpt-4o"
+primary_language = "EN"
+secondary_language = "FR"
+{}
+"#,
+            extra
+        )
+    };
+
+    // Missing from older config files: the default
+    let config: Config = toml::from_str(&toml_with("")).expect("Failed to parse config");
+    assert_eq!(config.label_max_width_chars, 60);
+
+    let config: Config =
+        toml::from_str(&toml_with("label_max_width_chars = 0")).expect("Failed to parse config");
+    assert_eq!(config.label_max_width_chars, 0);
+
+    // Negative widths are rejected
+    assert!(toml::from_str::<Config>(&toml_with("label_max_width_chars = -5")).is_err());
+
+    assert!(Config::example_toml().contains("label_max_width_chars = 60"));
+}
+
+#[test]
+fn test_local_config_overrides_only_its_fields() {
+    let base = r#"
+        model_version = "openai/gpt-4o"
+        max_tokens = 1000
+        primary_language = "de"
+        [ui_strings]
+        translating = "Übersetze..."
+        reading_clipboard = "Lese..."
+    "#;
+    let local = r#"
+        model_version = "openai/gpt-4o-mini"
+        [ui_strings]
+        translating = "Translating..."
+    "#;
+
+    let config = parse_with_local_overrides(ConfigFormat::Toml, base, local)
+        .expect("Merged config should parse");
+    // Set in config.local.toml
+    assert_eq!(config.model_version, "openai/gpt-4o-mini");
+    assert_eq!(config.ui_strings["translating"], "Translating...");
+    // Only in the base config
+    assert_eq!(config.max_tokens, 1000);
+    assert_eq!(config.primary_language, Language::German);
+    assert_eq!(config.ui_strings["reading_clipboard"], "Lese...");
+    // In neither
+    assert_eq!(
+        config.secondary_language,
+        Config::default().secondary_language
+    );
+
+    // The base config can be JSON
+    let config = parse_with_local_overrides(
+        ConfigFormat::Json,
+        r#"{ "model_version": "openai/gpt-4o", "max_tokens": 1000 }"#,
+        local,
+    )
+    .expect("Merged config should parse");
+    assert_eq!(config.model_version, "openai/gpt-4o-mini");
+    assert_eq!(config.max_tokens, 1000);
+
+    // An invalid local file is an error, not silently half-applied
+    assert!(parse_with_local_overrides(ConfigFormat::Toml, base, "max_tokens = \"many\"").is_err());
+}
+
+#[test]
+fn test_load_config_applies_local_config() {
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp directory");
+    let original_config_home = env::var("XDG_CONFIG_HOME").ok();
+    env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+
+    let config_dir = temp_dir.path().join("translator");
+    fs::create_dir_all(&config_dir).expect("Failed to create config dir");
+    fs::write(
+        config_dir.join("config.toml"),
+        "model_version = \"openai/gpt-4o\"\nmax_tokens = 1000\n",
+    )
+    .expect("Failed to write config");
+    fs::write(
+        config_dir.join("config.local.toml"),
+        "model_version = \"openai/gpt-4o-mini\"\n",
+    )
+    .expect("Failed to write local config");
+
+    let config = load_config();
+    assert_eq!(config.model_version, "openai/gpt-4o-mini");
+    assert_eq!(config.max_tokens, 1000);
+
+    // An unparsable local file is ignored
+    fs::write(config_dir.join("config.local.toml"), "model_version = ")
+        .expect("Failed to write local config");
+    let config = load_config();
+    assert_eq!(config.model_version, "openai/gpt-4o");
+
+    // Restore original environment
+    if let Some(original) = original_config_home {
+        env::set_var("XDG_CONFIG_HOME", original);
+    } else {
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+}
+
+#[test]
+fn test_save_config_writes_local_overrides_it_is_given() {
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp directory");
+    let original_config_home = env::var("XDG_CONFIG_HOME").ok();
+    env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+
+    let config_dir = temp_dir.path().join("translator");
+    fs::create_dir_all(&config_dir).expect("Failed to create config dir");
+    let config_path = config_dir.join("config.toml");
+    fs::write(
+        config_dir.join("config.local.toml"),
+        "model_version = \"openai/gpt-4o-mini\"\n",
+    )
+    .expect("Failed to write local config");
+
+    // Saving the config without the overrides keeps them out of config.toml, and loading
+    // still applies them
+    let base = Config {
+        model_version: "openai/gpt-4o".to_string(),
+        max_tokens: 1000,
+        ..Config::default()
+    };
+    save_config(&base).expect("Failed to save config");
+    let saved = fs::read_to_string(&config_path).expect("Failed to read config");
+    assert!(!saved.contains("gpt-4o-mini"), "{}", saved);
+    let loaded = load_config();
+    assert_eq!(loaded.model_version, "openai/gpt-4o-mini");
+    assert_eq!(loaded.max_tokens, 1000);
+
+    // The loaded config has them applied, so saving it writes them into config.toml
+    save_config(&loaded).expect("Failed to save config");
+    let saved = fs::read_to_string(&config_path).expect("Failed to read config");
+    assert!(saved.contains("gpt-4o-mini"), "{}", saved);
+
+    // Restore original environment
+    if let Some(original) = original_config_home {
+        env::set_var("XDG_CONFIG_HOME", original);
+    } else {
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+}
@@ -0,0 +1,77 @@
+use tokio::time::{sleep, Duration, Instant};
+
+// Import the crate to test
+use translator::debounce::Debouncer;
+
+const DELAY: Duration = Duration::from_millis(600);
+
+#[tokio::test(start_paused = true)]
+async fn test_debouncer_runs_after_delay() {
+    let debouncer = Debouncer::new(DELAY);
+    let start = Instant::now();
+
+    let result = debouncer.run(async { "done" }).await;
+
+    assert_eq!(result, Some("done"));
+    assert_eq!(
+        start.elapsed(),
+        DELAY,
+        "Task should run once the delay has passed"
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_debouncer_only_runs_last_call() {
+    let debouncer = Debouncer::new(DELAY);
+    let start = Instant::now();
+
+    // A second keystroke 100ms later restarts the wait
+    let first = debouncer.run(async { 1 });
+    let second = async {
+        sleep(Duration::from_millis(100)).await;
+        debouncer.run(async { 2 }).await
+    };
+    let (first, second) = tokio::join!(first, second);
+
+    assert_eq!(first, None, "Superseded call should not run");
+    assert_eq!(second, Some(2));
+    assert_eq!(start.elapsed(), Duration::from_millis(700));
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_debouncer_cancels_in_flight_task() {
+    let debouncer = Debouncer::new(DELAY);
+
+    // The first task is already running (e.g. waiting for the API) when the next call comes in
+    let first = debouncer.run(async {
+        sleep(Duration::from_secs(5)).await;
+        1
+    });
+    let second = async {
+        sleep(Duration::from_secs(1)).await;
+        debouncer.run(async { 2 }).await
+    };
+    let start = Instant::now();
+    let (first, second) = tokio::join!(first, second);
+
+    assert_eq!(first, None, "In-flight task should be cancelled");
+    assert_eq!(second, Some(2));
+    assert_eq!(start.elapsed(), Duration::from_millis(1600));
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_debouncer_cancel() {
+    let debouncer = Debouncer::new(DELAY);
+
+    let pending = debouncer.run(async { 1 });
+    let cancel = async {
+        sleep(Duration::from_millis(300)).await;
+        debouncer.cancel();
+    };
+    let (pending, ()) = tokio::join!(pending, cancel);
+
+    assert_eq!(pending, None, "Cancelled call should not run");
+
+    // Later calls are unaffected
+    assert_eq!(debouncer.run(async { 2 }).await, Some(2));
+}
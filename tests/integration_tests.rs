@@ -3,11 +3,20 @@ use std::env;
 use tempfile;
 use translator::{
     config::{load_config, save_config, Config},
+    detection::choose_target_language,
     settings::{load_last_language, save_last_language},
     translate_text,
-    ui::choose_target_language,
 };
 
+// Default config pointed at the given API endpoint and model
+fn api_config(api_url: &str, model_version: &str) -> Config {
+    Config {
+        api_url: api_url.to_string(),
+        model_version: model_version.to_string(),
+        ..Config::default()
+    }
+}
+
 #[test]
 fn test_config_and_settings_integration() {
     // Create a temporary directory for all configuration
@@ -60,10 +69,11 @@ async fn test_translation_workflow() {
     for (text, target_language, should_fail) in test_cases {
         let result = translate_text(
             text,
+            None,
             target_language,
             "test-key".to_string(),
-            "http://127.0.0.1:9999".to_string(),
-            "test-model".to_string(),
+            &api_config("http://127.0.0.1:9999", "test-model"),
+            None,
         )
         .await;
 
@@ -107,6 +117,8 @@ fn test_language_selection_with_config() {
             config.primary_language,
             config.secondary_language,
             load_last_language(),
+            config.fallback_language(),
+            &config.all_target_languages,
         );
         assert_eq!(target, expected, "Failed for source: {:?}", source);
     }
@@ -148,6 +160,8 @@ fn test_full_application_flow() {
         updated_config.primary_language,
         updated_config.secondary_language,
         load_last_language(),
+        updated_config.fallback_language(),
+        &config.all_target_languages,
     );
 
     // Since source is not primary (German), should choose primary
@@ -172,10 +186,11 @@ async fn test_error_handling_integration() {
     // 1. Translation with invalid API configuration
     let result = translate_text(
         "Test text",
+        None,
         Language::Spanish,
         "".to_string(), // Empty API key
-        "http://invalid.url".to_string(),
-        "invalid-model".to_string(),
+        &api_config("http://invalid.url", "invalid-model"),
+        None,
     )
     .await;
 
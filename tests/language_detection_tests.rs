@@ -1,4 +1,23 @@
 use lingua::{Language, LanguageDetectorBuilder};
+use std::cell::Cell;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::time::Duration;
+
+// Import the crate to test
+use translator::config::Config;
+use translator::detection::{
+    apply_primary_bias, build_detector, cap_detection_languages, detect_source_language,
+    detection_is_conclusive, detection_languages, detection_needed, detector_for_languages,
+    needs_full_detection, preload_detector, primary_bias, resolve_source_language, sample_text,
+    script_hint, spawn_full_detection, startup_target_language, Detection, DETECTION_AMBIGUITY_GAP,
+    SCRIPT_HINT_CONFIDENCE,
+};
+use translator::settings::Settings;
+use translator::ui::{is_already_in_target, refined_target_language};
+
+const ENGLISH_TEXT: &str = "Hello world, this is a test of the language detection system.";
+const FRENCH_TEXT: &str = " Bonjour le monde, ceci est un test du système de détection de langue.";
 
 #[test]
 fn test_language_detection() {
@@ -72,3 +91,579 @@ fn test_language_detection_with_short_text() {
         "Should return some language even for short text"
     );
 }
+
+#[test]
+fn test_sample_text_boundaries() {
+    // Cuts at character boundaries, not bytes
+    assert_eq!(sample_text("héllo wörld", 2), "hé");
+    assert_eq!(sample_text("héllo wörld", 7), "héllo w");
+
+    // Text no longer than the sample is used as is
+    assert_eq!(sample_text("héllo", 5), "héllo");
+    assert_eq!(sample_text("héllo", 6), "héllo");
+    assert_eq!(sample_text("", 10), "");
+
+    // 0 disables sampling
+    assert_eq!(sample_text("héllo wörld", 0), "héllo wörld");
+}
+
+#[tokio::test]
+async fn test_detect_source_language_uses_sample_only() {
+    let detector = Arc::new(
+        LanguageDetectorBuilder::from_languages(&[Language::English, Language::French]).build(),
+    );
+    // English first, then a lot more French
+    let text = format!("{}{}", ENGLISH_TEXT, FRENCH_TEXT.repeat(5));
+    let timeout = Duration::from_secs(30);
+
+    // Sample ends exactly where the English sentence does
+    let sample_chars = ENGLISH_TEXT.chars().count();
+    let detected =
+        detect_source_language(detector.clone(), &text, sample_chars, timeout, 0.0, None).await;
+    assert_eq!(detected.map(|d| d.language), Some(Language::English));
+
+    // The whole text is mostly French
+    let detected = detect_source_language(detector, &text, 0, timeout, 0.0, None).await;
+    assert_eq!(detected.map(|d| d.language), Some(Language::French));
+}
+
+#[tokio::test]
+async fn test_two_phase_detection_corrects_target() {
+    let detector = Arc::new(
+        LanguageDetectorBuilder::from_languages(&[Language::English, Language::French]).build(),
+    );
+    // English first, then a lot more French
+    let text = format!("{}{}", ENGLISH_TEXT, FRENCH_TEXT.repeat(5));
+    let timeout = Duration::from_secs(30);
+    let sample_chars = ENGLISH_TEXT.chars().count();
+    assert!(needs_full_detection(&text, sample_chars));
+    assert!(!needs_full_detection(ENGLISH_TEXT, sample_chars));
+
+    // Primary English, secondary French, no remembered choice
+    let config = Config::default();
+    let settings = Settings::default();
+
+    // First phase: the sample looks English, so the text goes into the secondary language
+    let first = detect_source_language(detector.clone(), &text, sample_chars, timeout, 0.0, None)
+        .await
+        .expect("Sample should be detected");
+    assert_eq!(first.language, Language::English);
+    let first_target = startup_target_language(Some(first.language), &config, &settings);
+    assert_eq!(first_target, Language::French);
+
+    // Second phase: the whole text is French, so the target becomes the primary language
+    let full = spawn_full_detection(detector, text, timeout, 0.0, None)
+        .await
+        .expect("Full detection task should not panic");
+    assert_eq!(full.map(|d| d.language), Some(Language::French));
+    assert_eq!(
+        refined_target_language(full.as_ref(), first_target, &config, &settings),
+        Some(Language::English)
+    );
+
+    // Agreeing or failed second phases change nothing
+    assert_eq!(
+        refined_target_language(Some(&first), first_target, &config, &settings),
+        None
+    );
+    assert_eq!(
+        refined_target_language(None, first_target, &config, &settings),
+        None
+    );
+}
+
+#[tokio::test]
+async fn test_detect_source_language_min_confidence() {
+    let detector = Arc::new(
+        LanguageDetectorBuilder::from_languages(&[Language::English, Language::French]).build(),
+    );
+    let timeout = Duration::from_secs(30);
+
+    // A clear English sentence passes a moderate threshold
+    let detected =
+        detect_source_language(detector.clone(), ENGLISH_TEXT, 100, timeout, 0.5, None).await;
+    assert_eq!(detected.map(|d| d.language), Some(Language::English));
+
+    // Confidence never exceeds 1.0, so nothing passes this threshold
+    let detected = detect_source_language(detector, ENGLISH_TEXT, 100, timeout, 1.1, None).await;
+    assert_eq!(detected, None);
+}
+
+#[tokio::test]
+async fn test_detect_source_language_timeout() {
+    let detector = Arc::new(LanguageDetectorBuilder::from_all_languages().build());
+    // Large enough that detection can't finish instantly
+    let text = FRENCH_TEXT.repeat(2000);
+
+    let detected = detect_source_language(detector, &text, 0, Duration::ZERO, 0.0, None).await;
+    assert_eq!(detected, None, "Detection should give up after the timeout");
+}
+
+#[tokio::test]
+async fn test_forced_source_language_skips_detection() {
+    let detector_called = Cell::new(false);
+    let detect = || async {
+        detector_called.set(true);
+        Some(Detection::certain(Language::English))
+    };
+
+    let source = resolve_source_language(Some(Language::German), true, detect).await;
+
+    assert_eq!(source, Some(Detection::certain(Language::German)));
+    assert!(
+        !detector_called.get(),
+        "Detector must not run when a source is forced"
+    );
+}
+
+#[tokio::test]
+async fn test_unforced_source_language_runs_detection() {
+    let detector = Arc::new(
+        LanguageDetectorBuilder::from_languages(&[Language::English, Language::French]).build(),
+    );
+
+    let source = resolve_source_language(None, true, || {
+        detect_source_language(
+            detector,
+            ENGLISH_TEXT,
+            100,
+            Duration::from_secs(30),
+            0.0,
+            None,
+        )
+    })
+    .await;
+
+    assert_eq!(source.map(|d| d.language), Some(Language::English));
+}
+
+#[tokio::test]
+async fn test_disabled_detection_skips_detector() {
+    let detector_called = Cell::new(false);
+    let detect = || async {
+        detector_called.set(true);
+        Some(Detection::certain(Language::English))
+    };
+
+    let source = resolve_source_language(None, false, detect).await;
+
+    assert_eq!(source, None);
+    assert!(
+        !detector_called.get(),
+        "Detector must not run when detection is disabled"
+    );
+
+    // A forced source is still used
+    let source = resolve_source_language(Some(Language::German), false, || async {
+        panic!("Detector must not run when a source is forced")
+    })
+    .await;
+    assert_eq!(source, Some(Detection::certain(Language::German)));
+}
+
+#[test]
+fn test_cap_detection_languages() {
+    use Language::*;
+    let configured = [German, Spanish, English, French, Italian, German];
+
+    // Under the limit: primary and secondary first, then the rest in order, without duplicates
+    let (kept, dropped) = cap_detection_languages(&configured, English, French, 15);
+    assert_eq!(kept, vec![English, French, German, Spanish, Italian]);
+    assert!(dropped.is_empty());
+    let (kept, dropped) = cap_detection_languages(&configured, English, French, 0);
+    assert_eq!(kept.len(), 5);
+    assert!(dropped.is_empty());
+
+    // Over it: the first others are kept, the rest reported
+    let (kept, dropped) = cap_detection_languages(&configured, English, French, 3);
+    assert_eq!(kept, vec![English, French, German]);
+    assert_eq!(dropped, vec![Spanish, Italian]);
+
+    // Primary and secondary survive even a limit below two
+    let (kept, dropped) = cap_detection_languages(&configured, English, French, 1);
+    assert_eq!(kept, vec![English, French]);
+    assert_eq!(dropped, vec![German, Spanish, Italian]);
+
+    // Only configured languages are detected
+    let (kept, dropped) = cap_detection_languages(&[German, Spanish], English, Spanish, 1);
+    assert_eq!(kept, vec![Spanish]);
+    assert_eq!(dropped, vec![German]);
+}
+
+#[test]
+fn test_detection_needed() {
+    assert!(detection_needed(&Config::default()));
+    assert!(!detection_needed(&Config {
+        detection_enabled: false,
+        ..Config::default()
+    }));
+    assert!(!detection_needed(&Config {
+        forced_source_language: Some(Language::German),
+        ..Config::default()
+    }));
+}
+
+#[test]
+fn test_detection_gap_from_confidence_values() {
+    let detector = LanguageDetectorBuilder::from_languages(&[
+        Language::English,
+        Language::French,
+        Language::Spanish,
+    ])
+    .build();
+    let values = detector.compute_language_confidence_values(ENGLISH_TEXT);
+
+    let detection = Detection::from_confidence_values(&values).expect("Should detect English");
+    assert_eq!(detection.language, Language::English);
+    assert_eq!(detection.confidence, values[0].1);
+    assert_eq!(detection.runner_up, Some(values[1]));
+    assert_eq!(detection.confidence_gap(), values[0].1 - values[1].1);
+    // A full English sentence isn't a close call
+    assert!(!detection.is_ambiguous(), "{}", detection.summary());
+}
+
+#[test]
+fn test_detection_ambiguity() {
+    // Runner-up close behind
+    let close = Detection::from_confidence_values(&[
+        (Language::Spanish, 0.52),
+        (Language::Portuguese, 0.46),
+        (Language::Italian, 0.02),
+    ])
+    .unwrap();
+    assert_eq!(close.runner_up, Some((Language::Portuguese, 0.46)));
+    assert!(close.confidence_gap() < DETECTION_AMBIGUITY_GAP);
+    assert!(close.is_ambiguous());
+    assert_eq!(
+        close.summary(),
+        "Spanish (0.52), runner-up Portuguese (0.46)"
+    );
+
+    // Clear lead
+    let clear =
+        Detection::from_confidence_values(&[(Language::German, 0.9), (Language::English, 0.1)])
+            .unwrap();
+    assert!(!clear.is_ambiguous());
+
+    // Without a runner-up the gap is the confidence itself
+    let single = Detection::from_confidence_values(&[(Language::German, 1.0)]).unwrap();
+    assert_eq!(single.confidence_gap(), 1.0);
+    assert!(!single.is_ambiguous());
+}
+
+#[test]
+fn test_detection_from_confidence_values_none() {
+    // Like detect_language_of: nothing, zero confidence or a tie is no detection
+    assert_eq!(Detection::from_confidence_values(&[]), None);
+    assert_eq!(
+        Detection::from_confidence_values(&[(Language::English, 0.0), (Language::French, 0.0)]),
+        None
+    );
+    assert_eq!(
+        Detection::from_confidence_values(&[(Language::English, 0.5), (Language::French, 0.5)]),
+        None
+    );
+}
+
+#[test]
+fn test_detector_build_time_and_cache() {
+    // Language sets not used by other tests, so the first call really builds
+    let one = [Language::Swedish];
+    let many = [
+        Language::Dutch,
+        Language::Danish,
+        Language::Czech,
+        Language::Hungarian,
+        Language::Finnish,
+        Language::Greek,
+        Language::Turkish,
+        Language::Romanian,
+    ];
+
+    let start = Instant::now();
+    let single = detector_for_languages(&one);
+    let single_time = start.elapsed();
+
+    let start = Instant::now();
+    let multi = detector_for_languages(&many);
+    let multi_time = start.elapsed();
+
+    let start = Instant::now();
+    let multi_again = detector_for_languages(&many);
+    let cached_time = start.elapsed();
+
+    // Run with --nocapture to see why building ahead of time pays off
+    println!(
+        "Detector build: 1 language {:?}, {} languages {:?}, cached {:?}",
+        single_time,
+        many.len(),
+        multi_time,
+        cached_time
+    );
+
+    assert!(
+        Arc::ptr_eq(&multi, &multi_again),
+        "Second call should be cached"
+    );
+    // The order of the languages doesn't matter for the cache
+    let mut reversed = many;
+    reversed.reverse();
+    assert!(Arc::ptr_eq(&multi, &detector_for_languages(&reversed)));
+    assert!(!Arc::ptr_eq(&single, &multi));
+}
+
+#[tokio::test]
+async fn test_preload_detector_for_config() {
+    let config = Config {
+        primary_language: Language::Ukrainian,
+        ..Config::default()
+    };
+
+    let detector = preload_detector(&config)
+        .await
+        .expect("Preloading should not fail");
+
+    assert!(Arc::ptr_eq(
+        &detector,
+        &detector_for_languages(&detection_languages(&config))
+    ));
+}
+
+#[test]
+fn test_detection_languages_from_config() {
+    use Language::*;
+    let config = Config {
+        primary_language: Ukrainian,
+        secondary_language: English,
+        all_target_languages: vec![French, English, Italian, Polish],
+        max_detection_languages: 0,
+        ..Config::default()
+    };
+    // Primary and secondary first, then the targets in their order, without repeats
+    assert_eq!(
+        detection_languages(&config),
+        vec![Ukrainian, English, French, Italian, Polish]
+    );
+
+    let capped = Config {
+        max_detection_languages: 3,
+        ..config
+    };
+    assert_eq!(
+        detection_languages(&capped),
+        vec![Ukrainian, English, French]
+    );
+}
+
+#[tokio::test]
+async fn test_one_language_detector_is_not_conclusive() {
+    use Language::*;
+    // Only English configured, e.g. a pinned primary with no other targets
+    let config = Config {
+        primary_language: English,
+        secondary_language: English,
+        all_target_languages: vec![English],
+        ..Config::default()
+    };
+    let languages = detection_languages(&config);
+    assert_eq!(languages, vec![English]);
+
+    // Lingua answers its only language with full confidence, even for French text
+    let detection = detect_source_language(
+        detector_for_languages(&languages),
+        FRENCH_TEXT,
+        100,
+        Duration::from_secs(5),
+        0.0,
+        None,
+    )
+    .await
+    .expect("A one-language detector always answers");
+    assert_eq!(detection.language, English);
+    assert_eq!(detection.confidence, 1.0);
+    assert_eq!(detection.runner_up, None);
+
+    assert!(!detection_is_conclusive(&config));
+    assert!(!is_already_in_target(
+        Some(&detection),
+        English,
+        detection_is_conclusive(&config)
+    ));
+
+    // Detected among several languages the French text is not taken as English
+    let several = Config {
+        secondary_language: French,
+        all_target_languages: vec![English, French, Italian],
+        ..config
+    };
+    assert!(detection_is_conclusive(&several));
+    let detection = detect_source_language(
+        detector_for_languages(&detection_languages(&several)),
+        FRENCH_TEXT,
+        100,
+        Duration::from_secs(5),
+        0.0,
+        None,
+    )
+    .await;
+    assert_eq!(detection.map(|detection| detection.language), Some(French));
+}
+
+#[tokio::test]
+async fn test_app_detector_reports_runner_up() {
+    use Language::*;
+    let config = Config {
+        primary_language: English,
+        secondary_language: French,
+        all_target_languages: vec![English, French, Italian, Polish],
+        ..Config::default()
+    };
+
+    // The app's detector knows every target, so there is a runner-up to report
+    let detection = detect_source_language(
+        build_detector(&config),
+        FRENCH_TEXT,
+        100,
+        Duration::from_secs(5),
+        0.0,
+        primary_bias(&config),
+    )
+    .await
+    .expect("Should detect French");
+    assert_eq!(detection.language, French);
+    let (runner_up, _) = detection.runner_up.expect("Should have a runner-up");
+    assert!(detection_languages(&config).contains(&runner_up));
+    assert_ne!(runner_up, French);
+    assert!(
+        detection.summary().contains("runner-up"),
+        "{}",
+        detection.summary()
+    );
+}
+
+#[test]
+fn test_primary_bias_flips_near_tie() {
+    // Short text where Spanish barely beats the primary language
+    let values = vec![
+        (Language::Spanish, 0.51),
+        (Language::Portuguese, 0.30),
+        (Language::English, 0.19),
+    ];
+    let unbiased = Detection::from_confidence_values(&values).unwrap();
+    assert_eq!(unbiased.language, Language::Spanish);
+
+    let values = vec![(Language::Spanish, 0.51), (Language::English, 0.49)];
+    let biased = apply_primary_bias(&values, Language::English, 0.05);
+    assert_eq!(biased[0].0, Language::English);
+    assert!((biased[0].1 - 0.54).abs() < 1e-9);
+    let detection = Detection::from_confidence_values(&biased).unwrap();
+    assert_eq!(detection.language, Language::English);
+    assert_eq!(detection.runner_up, Some((Language::Spanish, 0.51)));
+
+    // A clear lead is not overturned by a small bias
+    let values = vec![(Language::Spanish, 0.9), (Language::English, 0.1)];
+    let biased = apply_primary_bias(&values, Language::English, 0.05);
+    assert_eq!(biased[0].0, Language::Spanish);
+}
+
+#[tokio::test]
+async fn test_primary_bias_with_app_detector() {
+    use Language::*;
+    let config = Config {
+        primary_language: English,
+        secondary_language: French,
+        all_target_languages: vec![English, French, Italian, Polish],
+        detection_primary_bias: 1.0,
+        ..Config::default()
+    };
+    // A word that is both English and French, so neither gets all the confidence
+    let detection = detect_source_language(
+        build_detector(&config),
+        "information",
+        100,
+        Duration::from_secs(5),
+        0.0,
+        primary_bias(&config),
+    )
+    .await
+    .expect("Should detect");
+
+    // With the largest bias the primary language wins, ahead of another configured language
+    assert_eq!(detection.language, English, "{}", detection.summary());
+    assert_eq!(detection.confidence, 1.0);
+    let (runner_up, _) = detection.runner_up.expect("Should have a runner-up");
+    assert!(detection_languages(&config).contains(&runner_up));
+}
+
+#[test]
+fn test_primary_bias_is_clamped() {
+    let values = vec![(Language::Spanish, 0.6), (Language::English, 0.4)];
+
+    // Negative and NaN biases change nothing
+    assert_eq!(apply_primary_bias(&values, Language::English, -0.5), values);
+    assert_eq!(
+        apply_primary_bias(&values, Language::English, f64::NAN),
+        values
+    );
+
+    // Large biases count as 1.0, and confidence stays at most 1.0
+    let biased = apply_primary_bias(&values, Language::English, 5.0);
+    assert_eq!(biased[0], (Language::English, 1.0));
+
+    // Off by default
+    assert_eq!(primary_bias(&Config::default()), None);
+    let config = Config {
+        detection_primary_bias: 0.1,
+        ..Config::default()
+    };
+    assert_eq!(primary_bias(&config), Some((Language::English, 0.1)));
+}
+
+#[test]
+fn test_script_hint_on_short_text() {
+    assert_eq!(script_hint("Привет"), Some(Language::Russian));
+    assert_eq!(script_hint("Привіт"), Some(Language::Ukrainian));
+    assert_eq!(script_hint("Γεια"), Some(Language::Greek));
+    assert_eq!(script_hint("שלום"), Some(Language::Hebrew));
+    assert_eq!(script_hint("مرحبا"), Some(Language::Arabic));
+    assert_eq!(script_hint("چطوری"), Some(Language::Persian));
+    assert_eq!(script_hint("नमस्ते"), Some(Language::Hindi));
+    assert_eq!(script_hint("สวัสดี"), Some(Language::Thai));
+    assert_eq!(script_hint("안녕"), Some(Language::Korean));
+    assert_eq!(script_hint("你好"), Some(Language::Chinese));
+    // Kana makes text with Han characters Japanese
+    assert_eq!(script_hint("日本語です"), Some(Language::Japanese));
+    assert_eq!(script_hint("こんにちは"), Some(Language::Japanese));
+
+    // The script with the most letters wins; digits and punctuation don't count
+    assert_eq!(script_hint("OK, Привет!"), Some(Language::Russian));
+    assert_eq!(script_hint("123 !?"), None);
+
+    // Latin script is shared by too many languages to guess
+    assert_eq!(script_hint("Hi"), None);
+    assert_eq!(script_hint(""), None);
+}
+
+#[tokio::test]
+async fn test_detect_source_language_falls_back_to_script() {
+    // Neither language is written in Cyrillic, so lingua has no answer
+    let detector = Arc::new(
+        LanguageDetectorBuilder::from_languages(&[Language::English, Language::French]).build(),
+    );
+    let timeout = Duration::from_secs(30);
+
+    let detected =
+        detect_source_language(detector.clone(), "Привет", 100, timeout, 0.0, None).await;
+    assert_eq!(
+        detected,
+        Some(Detection {
+            language: Language::Russian,
+            confidence: SCRIPT_HINT_CONFIDENCE,
+            runner_up: None,
+        })
+    );
+
+    // The guess is still subject to the confidence threshold
+    let detected = detect_source_language(detector, "Привет", 100, timeout, 0.9, None).await;
+    assert_eq!(detected, None);
+}
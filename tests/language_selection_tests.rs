@@ -1,7 +1,15 @@
 use lingua::Language;
 
 // Import the function that implements the language selection algorithm
-use translator::ui::choose_target_language;
+use translator::detection::choose_target_language;
+
+// Target languages configured in the UI
+const TARGETS: &[Language] = &[
+    Language::English,
+    Language::French,
+    Language::German,
+    Language::Spanish,
+];
 
 #[test]
 fn test_language_selection_algorithm() {
@@ -13,6 +21,8 @@ fn test_language_selection_algorithm() {
         Language::English,      // Primary language
         Language::French,       // Secondary language
         Language::Spanish,      // Last language
+        Language::English,      // Fallback language
+        TARGETS,                // Target languages
     );
     assert_eq!(
         result,
@@ -26,6 +36,8 @@ fn test_language_selection_algorithm() {
         Language::English,       // Primary language
         Language::French,        // Secondary language
         Language::Spanish,       // Last language (not primary)
+        Language::English,       // Fallback language
+        TARGETS,                 // Target languages
     );
     assert_eq!(
         result,
@@ -39,6 +51,8 @@ fn test_language_selection_algorithm() {
         Language::English,       // Primary language
         Language::French,        // Secondary language
         Language::English,       // Last language (same as primary, not meaningful)
+        Language::English,       // Fallback language
+        TARGETS,                 // Target languages
     );
     assert_eq!(
         result,
@@ -52,6 +66,8 @@ fn test_language_selection_algorithm() {
         Language::English, // Primary language
         Language::French,  // Secondary language
         Language::Spanish, // Last language
+        Language::English, // Fallback language
+        TARGETS,           // Target languages
     );
     assert_eq!(
         result,
@@ -59,3 +75,51 @@ fn test_language_selection_algorithm() {
         "Should default to primary when source detection fails"
     );
 }
+
+#[test]
+fn test_language_selection_fallback() {
+    // Source detection failed -> use the configured fallback
+    let result = choose_target_language(
+        None,              // Source language detection failed
+        Language::English, // Primary language
+        Language::French,  // Secondary language
+        Language::Spanish, // Last language
+        Language::German,  // Fallback language
+        TARGETS,           // Target languages
+    );
+    assert_eq!(
+        result,
+        Language::German,
+        "Should use fallback when source detection fails"
+    );
+
+    // Source is not one of the configured languages -> use the configured fallback
+    let result = choose_target_language(
+        Some(Language::Japanese), // Source language (not a target)
+        Language::English,        // Primary language
+        Language::French,         // Secondary language
+        Language::Spanish,        // Last language
+        Language::German,         // Fallback language
+        TARGETS,                  // Target languages
+    );
+    assert_eq!(
+        result,
+        Language::German,
+        "Should use fallback when source is not a target language"
+    );
+
+    // Source is a configured language -> fallback isn't used
+    let result = choose_target_language(
+        Some(Language::Spanish), // Source language (a target)
+        Language::English,       // Primary language
+        Language::French,        // Secondary language
+        Language::Spanish,       // Last language
+        Language::German,        // Fallback language
+        TARGETS,                 // Target languages
+    );
+    assert_eq!(
+        result,
+        Language::English,
+        "Should translate to primary when source is a target language"
+    );
+}
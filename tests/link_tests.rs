@@ -0,0 +1,141 @@
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+// Import the crate to test
+use translator::config::Config;
+use translator::link::{
+    cap_text, clipboard_url, fetch_page_text, page_text_limit, resolve_link, MAX_PAGE_TEXT_BYTES,
+};
+
+#[test]
+fn test_clipboard_url_detects_bare_links() {
+    assert_eq!(
+        clipboard_url("https://example.com/article?id=1"),
+        Some("https://example.com/article?id=1")
+    );
+    // Whitespace around the link, e.g. a copied line, is ignored
+    assert_eq!(
+        clipboard_url("  http://example.com\n"),
+        Some("http://example.com")
+    );
+
+    // Text around the link, or more than one link, is translated as text
+    assert_eq!(clipboard_url("See https://example.com for details"), None);
+    assert_eq!(
+        clipboard_url("https://example.com https://example.org"),
+        None
+    );
+    // Only web pages
+    assert_eq!(clipboard_url("ftp://example.com/file.txt"), None);
+    assert_eq!(clipboard_url("mailto:someone@example.com"), None);
+    assert_eq!(clipboard_url("https://"), None);
+    assert_eq!(clipboard_url("example.com"), None);
+    assert_eq!(clipboard_url(""), None);
+}
+
+#[test]
+fn test_cap_text() {
+    // Short text is kept whole
+    assert_eq!(cap_text("Hello world", 100), "Hello world");
+    assert_eq!(cap_text("Hello world", 11), "Hello world");
+
+    // Cut at the last line break in the second half
+    assert_eq!(cap_text("First line\nSecond line", 15), "First line");
+    // Without one, at the limit
+    assert_eq!(cap_text("Hello world", 5), "Hello");
+    assert_eq!(cap_text("A\nlong second line", 10), "A\nlong sec");
+    // Never in the middle of a character
+    assert_eq!(cap_text("ééé", 3), "é");
+    assert_eq!(cap_text("日本語", 5), "日");
+}
+
+#[test]
+fn test_page_text_limit() {
+    assert_eq!(page_text_limit(0), MAX_PAGE_TEXT_BYTES);
+    assert_eq!(page_text_limit(1_000), 1_000);
+    assert_eq!(page_text_limit(10_000_000), MAX_PAGE_TEXT_BYTES);
+}
+
+#[tokio::test]
+async fn test_fetch_page_text_strips_html() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/article"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            "<html><head><title>Title</title><style>p { color: red; }</style></head>\
+             <body><h1>Bonjour</h1><p>Le monde &amp; la mer.</p>\
+             <script>track();</script></body></html>",
+            "text/html; charset=utf-8",
+        ))
+        .mount(&mock_server)
+        .await;
+
+    let url = format!("{}/article", mock_server.uri());
+    let text = fetch_page_text(&url, &Config::default(), 1_000)
+        .await
+        .expect("Page should be fetched");
+    assert_eq!(text, "Bonjour\n\nLe monde & la mer.");
+
+    // Capped to the given size
+    let text = fetch_page_text(&url, &Config::default(), 7)
+        .await
+        .expect("Page should be fetched");
+    assert_eq!(text, "Bonjour");
+}
+
+#[tokio::test]
+async fn test_fetch_page_text_reports_errors() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/missing"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/empty"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw("<p> </p>", "text/html"))
+        .mount(&mock_server)
+        .await;
+
+    let url = format!("{}/missing", mock_server.uri());
+    let error = fetch_page_text(&url, &Config::default(), 1_000)
+        .await
+        .expect_err("A missing page should fail");
+    assert!(error.contains("404"), "{}", error);
+
+    let url = format!("{}/empty", mock_server.uri());
+    let error = fetch_page_text(&url, &Config::default(), 1_000)
+        .await
+        .expect_err("A page without text should fail");
+    assert!(error.contains("No text"), "{}", error);
+}
+
+#[tokio::test]
+async fn test_resolve_link_only_with_translate_urls() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw("Guten Tag", "text/plain"))
+        .mount(&mock_server)
+        .await;
+    let url = mock_server.uri();
+
+    // Off by default: the link is the text
+    assert_eq!(
+        resolve_link(url.clone(), &Config::default()).await,
+        Ok(url.clone())
+    );
+
+    let config = Config {
+        translate_urls: true,
+        ..Config::default()
+    };
+    assert_eq!(
+        resolve_link(url, &config).await,
+        Ok("Guten Tag".to_string())
+    );
+    // Other text is left alone
+    assert_eq!(
+        resolve_link("Hello".to_string(), &config).await,
+        Ok("Hello".to_string())
+    );
+}
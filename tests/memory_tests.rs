@@ -0,0 +1,87 @@
+use lingua::Language;
+
+// Import the crate to test
+use translator::memory::{memory_instruction, relevant_entries, MemoryEntry};
+
+fn entry(target: Language, original: &str, translation: &str) -> MemoryEntry {
+    MemoryEntry {
+        target,
+        original: original.to_string(),
+        translation: translation.to_string(),
+    }
+}
+
+#[test]
+fn test_relevant_entries_selection() {
+    let memory = vec![
+        entry(
+            Language::German,
+            "The invoice is due",
+            "Die Rechnung ist fällig",
+        ),
+        entry(
+            Language::German,
+            "Weather is nice today",
+            "Das Wetter ist heute schön",
+        ),
+        entry(Language::French, "The invoice is due", "La facture est due"),
+        entry(
+            Language::German,
+            "Send the invoice today",
+            "Schicke die Rechnung heute",
+        ),
+        entry(
+            Language::German,
+            "The invoice is late",
+            "Die Rechnung ist verspätet",
+        ),
+    ];
+
+    // Most shared words first, the most recent first among equals; other targets and
+    // unrelated texts are left out
+    let selected = relevant_entries(&memory, "Is the INVOICE due today?", Language::German, 5);
+    let originals: Vec<&str> = selected.iter().map(|e| e.original.as_str()).collect();
+    assert_eq!(
+        originals,
+        vec![
+            "Send the invoice today",
+            "The invoice is due",
+            "The invoice is late",
+            "Weather is nice today",
+        ]
+    );
+
+    // Bounded by count
+    let selected = relevant_entries(&memory, "Is the invoice due today?", Language::German, 2);
+    assert_eq!(selected.len(), 2);
+    assert_eq!(selected[0].original, "Send the invoice today");
+
+    // Short words alone don't make an entry relevant, nor does the same text
+    assert!(relevant_entries(&memory, "It is", Language::German, 5).is_empty());
+    let selected = relevant_entries(&memory, " The invoice is due ", Language::German, 5);
+    assert!(selected.iter().all(|e| e.original != "The invoice is due"));
+    assert!(relevant_entries(&[], "The invoice", Language::German, 5).is_empty());
+}
+
+#[test]
+fn test_memory_instruction() {
+    assert_eq!(memory_instruction(&[]), None);
+
+    let instruction = memory_instruction(&[entry(
+        Language::German,
+        "The invoice is due",
+        "Die Rechnung ist fällig",
+    )])
+    .unwrap();
+    assert!(
+        instruction.contains("earlier translations"),
+        "{}",
+        instruction
+    );
+    assert!(
+        instruction
+            .contains("Original:\nThe invoice is due\nTranslation:\nDie Rechnung ist fällig"),
+        "{}",
+        instruction
+    );
+}
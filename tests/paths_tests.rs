@@ -0,0 +1,92 @@
+use std::cell::Cell;
+use std::env;
+use std::io;
+use std::path::Path;
+
+// Import the crate to test
+use translator::config::get_config_path;
+use translator::paths::{
+    app_config_dir, is_read_only_dir, is_read_only_error, write_unless_read_only, APP_DIR_NAME,
+    CONFIG_DIR_ENV,
+};
+
+// Env vars are process-wide, so all override cases run in one test
+#[test]
+fn test_config_dir_env_override() {
+    // Create a temporary directory for the test
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp directory");
+
+    // Set the config directory for this test
+    let original_config_home = env::var("XDG_CONFIG_HOME").ok();
+    let original_dir_override = env::var(CONFIG_DIR_ENV).ok();
+    env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+
+    // Without the override the default directory name is used
+    env::remove_var(CONFIG_DIR_ENV);
+    assert_eq!(app_config_dir(), Some(temp_dir.path().join(APP_DIR_NAME)));
+
+    // A plain name replaces the directory name inside the config dir
+    env::set_var(CONFIG_DIR_ENV, "translator-fork");
+    assert_eq!(
+        get_config_path(),
+        Some(temp_dir.path().join("translator-fork").join("config.toml"))
+    );
+
+    // An absolute path is used as-is
+    let custom_dir = temp_dir.path().join("custom").join("dir");
+    env::set_var(CONFIG_DIR_ENV, &custom_dir);
+    assert_eq!(get_config_path(), Some(custom_dir.join("config.toml")));
+
+    // An empty value is ignored
+    env::set_var(CONFIG_DIR_ENV, "  ");
+    assert_eq!(app_config_dir(), Some(temp_dir.path().join(APP_DIR_NAME)));
+
+    // Restore original environment
+    if let Some(original) = original_dir_override {
+        env::set_var(CONFIG_DIR_ENV, original);
+    } else {
+        env::remove_var(CONFIG_DIR_ENV);
+    }
+    if let Some(original) = original_config_home {
+        env::set_var("XDG_CONFIG_HOME", original);
+    } else {
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+}
+
+#[test]
+fn test_write_unless_read_only_reports_once() {
+    let dir = Path::new("/nonexistent/read-only-test");
+    let writes = Cell::new(0);
+    let read_only_write = || {
+        writes.set(writes.get() + 1);
+        Err(io::Error::new(io::ErrorKind::PermissionDenied, "denied"))
+    };
+
+    // The first failure is reported, saying why
+    let error = write_unless_read_only(dir, read_only_write).expect_err("First write fails");
+    assert!(error.to_string().contains("read-only"), "{}", error);
+    // Both settings and the config are written through it, so it names neither
+    assert!(
+        error.to_string().contains("not writing to this directory"),
+        "{}",
+        error
+    );
+    assert!(is_read_only_dir(dir));
+
+    // Later saves don't try again
+    for _ in 0..3 {
+        assert!(write_unless_read_only(dir, read_only_write).is_ok());
+    }
+    assert_eq!(writes.get(), 1);
+
+    // Other failures don't mark the directory
+    let other_dir = Path::new("/nonexistent/full-disk-test");
+    let result = write_unless_read_only(other_dir, || Err(io::Error::other("disk full")));
+    assert!(result.is_err());
+    assert!(!is_read_only_dir(other_dir));
+    assert!(!is_read_only_error(&io::Error::other("disk full")));
+    assert!(is_read_only_error(&io::Error::from(
+        io::ErrorKind::ReadOnlyFilesystem
+    )));
+}
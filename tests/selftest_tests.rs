@@ -0,0 +1,74 @@
+use lingua::Language;
+use wiremock::matchers::{body_string_contains, header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+// Import the crate to test
+use translator::config::Config;
+use translator::selftest::{run_selftest, run_selftest_with_key};
+
+// Default config pointed at the mock server, translating into Spanish
+fn selftest_config(api_url: &str) -> Config {
+    Config {
+        api_url: api_url.to_string(),
+        model_version: "test-model".to_string(),
+        secondary_language: Language::Spanish,
+        ..Config::default()
+    }
+}
+
+#[tokio::test]
+async fn test_selftest_success() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(header("authorization", "Bearer test-key"))
+        .and(body_string_contains("Hello"))
+        .and(body_string_contains("Spanish"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            r#"{"id":"chatcmpl-test","object":"chat.completion","created":1700000000,"model":"test-model","choices":[{"index":0,"message":{"role":"assistant","content":"Hola"},"finish_reason":"stop"}]}"#,
+            "application/json",
+        ))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let result =
+        run_selftest_with_key(&selftest_config(&mock_server.uri()), "test-key".to_string()).await;
+
+    let message = result.expect("Self-test should pass");
+    assert!(message.starts_with("OK"), "{}", message);
+    assert!(message.contains("Hola"), "{}", message);
+}
+
+#[tokio::test]
+async fn test_selftest_auth_failure() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(401).set_body_raw(
+            r#"{"error":{"message":"No auth credentials found","code":401}}"#,
+            "application/json",
+        ))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let result =
+        run_selftest_with_key(&selftest_config(&mock_server.uri()), "bad-key".to_string()).await;
+
+    let error = result.expect_err("Self-test should fail");
+    assert!(error.contains("HTTP 401"), "{}", error);
+    assert!(error.contains("No auth credentials found"), "{}", error);
+}
+
+#[tokio::test]
+async fn test_selftest_in_mock_mode_needs_no_key() {
+    let config = Config {
+        mock: true,
+        api_key_file: Some("/nonexistent/api_key".into()),
+        ..Config::default()
+    };
+    // The unreadable key file is ignored in mock mode
+    let message = run_selftest(&config).await.expect("Self-test should pass");
+    assert!(message.contains("[French] Hello"), "{}", message);
+}
@@ -3,7 +3,12 @@ use std::env;
 use std::fs;
 
 // Import the crate to test
-use translator::settings::{load_last_language, save_last_language};
+use tokio::time::{sleep, Duration};
+use translator::settings::{
+    apply_exit_state, clear_last_language, flush_exit_state, flush_settings, load_last_language,
+    load_settings, load_usage, record_language_use, save_last_language, save_manual_target,
+    save_settings, ExitState, Settings, SETTINGS_SAVE_DELAY,
+};
 
 #[test]
 fn test_save_load_last_language() {
@@ -145,7 +150,7 @@ fn test_save_language_permissions_error() {
 
     // Create the config directory but make it read-only
     fs::create_dir_all(&config_dir).expect("Failed to create config directory");
-    let lang_file = config_dir.join("last_language.txt");
+    let lang_file = config_dir.join("settings.toml");
 
     // Create the file and make it read-only
     fs::write(&lang_file, "").expect("Failed to create file");
@@ -239,3 +244,406 @@ fn test_unsupported_language() {
         env::remove_var("XDG_CONFIG_HOME");
     }
 }
+
+#[test]
+fn test_migrate_legacy_last_language_file() {
+    // Create a temporary directory for the test
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp directory");
+    let config_dir = temp_dir.path().join("translator");
+    fs::create_dir_all(&config_dir).expect("Failed to create config directory");
+
+    // Set the config directory for this test
+    let original_config_home = env::var("XDG_CONFIG_HOME").ok();
+    env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+
+    // Write a last language in the old bare-ISO-code format
+    let legacy_file = config_dir.join("last_language.txt");
+    fs::write(&legacy_file, "de\n").expect("Failed to write legacy file");
+
+    // Loading picks up the legacy value...
+    assert_eq!(load_last_language(), Language::German);
+
+    // ...and moves it into settings.toml
+    let settings_file = config_dir.join("settings.toml");
+    assert!(!legacy_file.exists(), "Legacy file should be removed");
+    let contents = fs::read_to_string(&settings_file).expect("settings.toml should exist");
+    assert!(contents.contains("last_language = \"DE\""));
+
+    // Subsequent loads read from settings.toml
+    assert_eq!(load_last_language(), Language::German);
+
+    // Restore original environment
+    if let Some(original) = original_config_home {
+        env::set_var("XDG_CONFIG_HOME", original);
+    } else {
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+}
+
+#[test]
+fn test_last_language_iso_round_trip_all_languages() {
+    // Create a temporary directory for the test
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp directory");
+
+    // Set the config directory for this test
+    let original_config_home = env::var("XDG_CONFIG_HOME").ok();
+    env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+
+    let settings_file = temp_dir.path().join("translator").join("settings.toml");
+    for lang in Language::all() {
+        save_last_language(lang).expect("Failed to save language");
+
+        // Stored as the uppercase ISO 639-1 code
+        let iso_code = lang.iso_code_639_1().to_string().to_uppercase();
+        let contents = fs::read_to_string(&settings_file).expect("Failed to read settings");
+        assert!(
+            contents.contains(&format!("\"{}\"", iso_code)),
+            "Expected ISO code {} in settings file: {}",
+            iso_code,
+            contents
+        );
+
+        assert_eq!(
+            load_last_language(),
+            lang,
+            "Round trip failed for {:?}",
+            lang
+        );
+    }
+
+    // Restore original environment
+    if let Some(original) = original_config_home {
+        env::set_var("XDG_CONFIG_HOME", original);
+    } else {
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+}
+
+#[test]
+fn test_hand_edited_last_language_name_and_iso_code() {
+    // Create a temporary directory for the test
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp directory");
+    let config_dir = temp_dir.path().join("translator");
+    fs::create_dir_all(&config_dir).expect("Failed to create config directory");
+
+    // Set the config directory for this test
+    let original_config_home = env::var("XDG_CONFIG_HOME").ok();
+    env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+
+    // Not just the few languages the old hand-written fallback knew about
+    let languages = [
+        Language::German,
+        Language::Ukrainian,
+        Language::Portuguese,
+        Language::Japanese,
+        Language::Swedish,
+        Language::Turkish,
+    ];
+    let settings_file = config_dir.join("settings.toml");
+    for lang in languages {
+        let iso_code = lang.iso_code_639_1().to_string();
+        // Display name as written by older versions, and ISO codes in either case
+        for value in [lang.to_string(), iso_code.to_uppercase(), iso_code] {
+            fs::write(&settings_file, format!("last_language = \"{}\"\n", value))
+                .expect("Failed to write settings");
+            assert_eq!(
+                load_last_language(),
+                lang,
+                "Failed to load {:?} from '{}'",
+                lang,
+                value
+            );
+        }
+    }
+
+    // Restore original environment
+    if let Some(original) = original_config_home {
+        env::set_var("XDG_CONFIG_HOME", original);
+    } else {
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+}
+
+#[test]
+fn test_prefer_manual_target_persistence() {
+    // Create a temporary directory for the test
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp directory");
+    let config_dir = temp_dir.path().join("translator");
+    fs::create_dir_all(&config_dir).expect("Failed to create config directory");
+
+    // Set the config directory for this test
+    let original_config_home = env::var("XDG_CONFIG_HOME").ok();
+    env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+
+    // Settings written before the flag existed default to automatic selection
+    let settings_file = config_dir.join("settings.toml");
+    fs::write(&settings_file, "last_language = \"IT\"\n").expect("Failed to write settings");
+    let settings = load_settings();
+    assert_eq!(settings.last_language, Language::Italian);
+    assert!(!settings.prefer_manual_target);
+
+    // Automatic switches don't turn on the manual preference
+    save_last_language(Language::French).expect("Failed to save language");
+    assert!(!load_settings().prefer_manual_target);
+
+    // A manual pick does, and it survives later automatic saves
+    save_manual_target(Language::German).expect("Failed to save manual target");
+    let settings = load_settings();
+    assert_eq!(settings.last_language, Language::German);
+    assert!(settings.prefer_manual_target);
+
+    save_last_language(Language::Spanish).expect("Failed to save language");
+    assert!(load_settings().prefer_manual_target);
+
+    // Resetting the flag in the file brings back automatic selection
+    fs::write(
+        &settings_file,
+        "last_language = \"ES\"\nprefer_manual_target = false\n",
+    )
+    .expect("Failed to write settings");
+    assert!(!load_settings().prefer_manual_target);
+
+    // Restore original environment
+    if let Some(original) = original_config_home {
+        env::set_var("XDG_CONFIG_HOME", original);
+    } else {
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+}
+
+#[test]
+fn test_concurrent_saves_do_not_clobber_each_other() {
+    // Create a temporary directory for the test
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp directory");
+
+    // Set the config directory for this test
+    let original_config_home = env::var("XDG_CONFIG_HOME").ok();
+    env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+
+    // Two writers saving at the same time, like two running instances
+    let barrier = std::sync::Arc::new(std::sync::Barrier::new(2));
+    let writers: Vec<_> = [Language::German, Language::Spanish]
+        .into_iter()
+        .map(|lang| {
+            let barrier = barrier.clone();
+            std::thread::spawn(move || {
+                barrier.wait();
+                for _ in 0..50 {
+                    save_settings(&Settings {
+                        last_language: lang,
+                        ..Settings::default()
+                    })?;
+                }
+                Ok::<(), std::io::Error>(())
+            })
+        })
+        .collect();
+    for writer in writers {
+        writer
+            .join()
+            .expect("Writer thread panicked")
+            .expect("Every save should succeed");
+    }
+
+    // The file holds one of the two complete versions and no temp files are left behind
+    let config_dir = temp_dir.path().join("translator");
+    let contents =
+        fs::read_to_string(config_dir.join("settings.toml")).expect("Failed to read settings");
+    let settings: Settings = toml::from_str(&contents).expect("Settings file should be valid");
+    assert!([Language::German, Language::Spanish].contains(&settings.last_language));
+    let leftovers: Vec<_> = fs::read_dir(&config_dir)
+        .expect("Failed to list config directory")
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().ends_with(".tmp"))
+        .collect();
+    assert!(leftovers.is_empty(), "Leftover temp files: {:?}", leftovers);
+
+    // Restore original environment
+    if let Some(original) = original_config_home {
+        env::set_var("XDG_CONFIG_HOME", original);
+    } else {
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+}
+
+#[test]
+fn test_record_language_use_counts() {
+    // Create a temporary directory for the test
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp directory");
+
+    // Set the config directory for this test
+    let original_config_home = env::var("XDG_CONFIG_HOME").ok();
+    env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+
+    // Nothing used yet
+    assert_eq!(load_usage().count(Language::German), 0);
+
+    record_language_use(Language::German).expect("Failed to record usage");
+    record_language_use(Language::French).expect("Failed to record usage");
+    record_language_use(Language::German).expect("Failed to record usage");
+
+    let usage = load_usage();
+    assert_eq!(usage.count(Language::German), 2);
+    assert_eq!(usage.count(Language::French), 1);
+    assert_eq!(usage.count(Language::Spanish), 0);
+
+    // Stored by ISO code in usage.toml
+    let contents = fs::read_to_string(temp_dir.path().join("translator").join("usage.toml"))
+        .expect("usage.toml should exist");
+    assert!(contents.contains("DE = 2"), "{}", contents);
+
+    // Restore original environment
+    if let Some(original) = original_config_home {
+        env::set_var("XDG_CONFIG_HOME", original);
+    } else {
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+}
+
+#[test]
+fn test_apply_exit_state() {
+    let settings = Settings {
+        last_language: Language::French,
+        prefer_manual_target: true,
+        ..Settings::default()
+    };
+
+    // Nothing known at exit, or nothing different: no write needed
+    assert!(apply_exit_state(&settings, &ExitState::default()).is_none());
+    let same_language = ExitState {
+        last_language: Some(Language::French),
+        window_size: None,
+    };
+    assert!(apply_exit_state(&settings, &same_language).is_none());
+
+    let exit_state = ExitState {
+        last_language: Some(Language::Italian),
+        window_size: Some((800, 600)),
+    };
+    let updated = apply_exit_state(&settings, &exit_state).expect("Settings should change");
+    assert_eq!(updated.last_language, Language::Italian);
+    assert_eq!(updated.window_width, Some(800));
+    assert_eq!(updated.window_height, Some(600));
+    assert!(updated.prefer_manual_target, "Other settings are kept");
+}
+
+#[test]
+fn test_flush_exit_state_writes_once() {
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp directory");
+    let config_dir = temp_dir.path().join("translator");
+
+    // Set the config directory for this test
+    let original_config_home = env::var("XDG_CONFIG_HOME").ok();
+    env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+
+    save_manual_target(Language::German).expect("Failed to save manual target");
+    let exit_state = ExitState {
+        last_language: Some(Language::Spanish),
+        window_size: Some((640, 480)),
+    };
+
+    assert!(flush_exit_state(&exit_state).expect("Failed to flush settings"));
+    let settings = load_settings();
+    assert_eq!(settings.last_language, Language::Spanish);
+    assert_eq!(settings.window_width, Some(640));
+    assert_eq!(settings.window_height, Some(480));
+    assert!(settings.prefer_manual_target);
+
+    // Flushing the same state again leaves the file alone
+    let settings_file = config_dir.join("settings.toml");
+    let written = fs::read_to_string(&settings_file).expect("Failed to read settings");
+    assert!(!flush_exit_state(&exit_state).expect("Failed to flush settings"));
+    assert_eq!(
+        fs::read_to_string(&settings_file).expect("Failed to read settings"),
+        written
+    );
+
+    // Restore original environment
+    if let Some(original) = original_config_home {
+        env::set_var("XDG_CONFIG_HOME", original);
+    } else {
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+}
+
+#[test]
+fn test_clear_last_language() {
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp directory");
+    let config_dir = temp_dir.path().join("translator");
+
+    // Set the config directory for this test
+    let original_config_home = env::var("XDG_CONFIG_HOME").ok();
+    env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+
+    save_manual_target(Language::German).expect("Failed to save manual target");
+    flush_exit_state(&ExitState {
+        last_language: None,
+        window_size: Some((640, 480)),
+    })
+    .expect("Failed to flush settings");
+    // A legacy file left behind is removed too
+    fs::write(config_dir.join("last_language.txt"), "PL").expect("Failed to write legacy file");
+
+    clear_last_language().expect("Failed to clear last language");
+    let settings = load_settings();
+    assert_eq!(settings.last_language, Language::English);
+    assert!(!settings.prefer_manual_target);
+    assert_eq!(load_last_language(), Language::English);
+    // Unrelated settings are kept
+    assert_eq!(settings.window_width, Some(640));
+    assert!(!config_dir.join("last_language.txt").exists());
+
+    // Clearing without any settings file works as well
+    fs::remove_file(config_dir.join("settings.toml")).expect("Failed to remove settings");
+    clear_last_language().expect("Failed to clear last language");
+    assert_eq!(load_last_language(), Language::English);
+
+    // Restore original environment
+    if let Some(original) = original_config_home {
+        env::set_var("XDG_CONFIG_HOME", original);
+    } else {
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_rapid_language_saves_are_batched() {
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp directory");
+    let original_config_home = env::var("XDG_CONFIG_HOME").ok();
+    env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+    let settings_file = temp_dir.path().join("translator").join("settings.toml");
+
+    // Cycling through the buttons
+    for lang in [Language::German, Language::French, Language::Spanish] {
+        save_manual_target(lang).expect("Failed to save language");
+        sleep(Duration::from_millis(100)).await;
+    }
+    // Not written yet, but loading already sees the last choice
+    assert!(!settings_file.exists());
+    assert_eq!(load_last_language(), Language::Spanish);
+    assert!(load_settings().prefer_manual_target);
+
+    // Written once, with the last choice, after the delay
+    sleep(SETTINGS_SAVE_DELAY).await;
+    let contents = fs::read_to_string(&settings_file).expect("Failed to read settings");
+    assert!(contents.contains("\"ES\""), "{}", contents);
+    let written_at = fs::metadata(&settings_file).unwrap().modified().unwrap();
+    sleep(SETTINGS_SAVE_DELAY * 3).await;
+    assert_eq!(
+        fs::metadata(&settings_file).unwrap().modified().unwrap(),
+        written_at
+    );
+    assert!(!flush_settings().expect("Nothing to flush"));
+
+    // Flushing writes a pending change right away, e.g. on exit
+    save_last_language(Language::Italian).expect("Failed to save language");
+    assert!(flush_settings().expect("Failed to flush settings"));
+    let contents = fs::read_to_string(&settings_file).expect("Failed to read settings");
+    assert!(contents.contains("\"IT\""), "{}", contents);
+
+    if let Some(original) = original_config_home {
+        env::set_var("XDG_CONFIG_HOME", original);
+    } else {
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+}
@@ -0,0 +1,156 @@
+use lingua::{Language, LanguageDetector, LanguageDetectorBuilder};
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+// Import the crate to test
+use translator::config::Config;
+use translator::settings::Settings;
+use translator::silent::translate_silently;
+
+// Mock config so nothing goes over the network
+fn mock_config() -> Config {
+    Config {
+        mock: true,
+        ..Config::default()
+    }
+}
+
+#[tokio::test]
+async fn test_translate_silently_uses_auto_selected_target() {
+    let detector = Arc::new(
+        LanguageDetectorBuilder::from_languages(&[Language::English, Language::French]).build(),
+    );
+
+    // French source -> primary language (English)
+    let result = translate_silently(
+        "Bonjour le monde, ceci est un test du système de détection de langue.",
+        &mock_config(),
+        &Settings::default(),
+        String::new(),
+        || detector,
+    )
+    .await;
+
+    assert_eq!(
+        result,
+        Ok((
+            Language::English,
+            "[English] Bonjour le monde, ceci est un test du système de détection de langue."
+                .to_string()
+        ))
+    );
+}
+
+#[tokio::test]
+async fn test_translate_silently_refuses_oversized_text() {
+    let detector = Arc::new(
+        LanguageDetectorBuilder::from_languages(&[Language::English, Language::French]).build(),
+    );
+    let config = Config {
+        max_clipboard_bytes: 10,
+        ..mock_config()
+    };
+
+    let result = translate_silently(
+        "This text is longer than ten bytes.",
+        &config,
+        &Settings::default(),
+        String::new(),
+        || detector,
+    )
+    .await;
+
+    let error = result.expect_err("Oversized text should be refused");
+    assert!(error.contains("too large"), "{}", error);
+}
+
+#[tokio::test]
+async fn test_translate_silently_without_detection() {
+    let config = Config {
+        detection_enabled: false,
+        ..mock_config()
+    };
+
+    // Unknown source -> fallback (the primary language); the detector is never built
+    let result = translate_silently(
+        "Bonjour le monde",
+        &config,
+        &Settings::default(),
+        String::new(),
+        || -> Arc<LanguageDetector> {
+            panic!("Detector must not be built when detection is disabled")
+        },
+    )
+    .await;
+
+    assert_eq!(
+        result,
+        Ok((Language::English, "[English] Bonjour le monde".to_string()))
+    );
+}
+
+// Every file below `dir` whose contents include `text`
+fn files_containing(dir: &Path, text: &str) -> Vec<String> {
+    let mut found = Vec::new();
+    for entry in fs::read_dir(dir)
+        .expect("Failed to read directory")
+        .flatten()
+    {
+        let path = entry.path();
+        if path.is_dir() {
+            found.extend(files_containing(&path, text));
+        } else if fs::read(&path)
+            .map(|bytes| String::from_utf8_lossy(&bytes).contains(text))
+            .unwrap_or(false)
+        {
+            found.push(path.display().to_string());
+        }
+    }
+    found
+}
+
+#[tokio::test]
+async fn test_private_mode_writes_no_text_to_disk() {
+    // Everything the app could write under the XDG directories lands in the temp directory
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp directory");
+    let xdg_vars = [
+        "XDG_CONFIG_HOME",
+        "XDG_CACHE_HOME",
+        "XDG_DATA_HOME",
+        "XDG_STATE_HOME",
+    ];
+    let originals: Vec<_> = xdg_vars.iter().map(|var| env::var(var).ok()).collect();
+    for var in xdg_vars {
+        env::set_var(var, temp_dir.path());
+    }
+
+    let config = Config {
+        private_mode: true,
+        detection_enabled: false,
+        ..mock_config()
+    };
+    let text = "Confidential: the merger closes on Friday";
+    let result = translate_silently(
+        text,
+        &config,
+        &Settings::default(),
+        String::new(),
+        || -> Arc<LanguageDetector> { panic!("Detection is disabled") },
+    )
+    .await;
+
+    // There is no history to append to: the text only lives in memory
+    let found = files_containing(temp_dir.path(), text);
+
+    for (var, original) in xdg_vars.iter().zip(originals) {
+        match original {
+            Some(value) => env::set_var(var, value),
+            None => env::remove_var(var),
+        }
+    }
+
+    assert!(result.is_ok(), "{:?}", result);
+    assert!(found.is_empty(), "Text written to {:?}", found);
+}
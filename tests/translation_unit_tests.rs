@@ -1,15 +1,44 @@
 use lingua::Language;
-use tokio::time::{timeout, Duration};
-use translator::{translate_text, TranslationResult};
+use tokio::time::{timeout, Duration, Instant};
+use translator::config::Config;
+use translator::translation::{
+    auto_max_tokens, build_chat_request, cap_max_tokens, config_for_pair, domain_hint,
+    fix_capitalization, is_auth_failure, is_rtl, model_for_pair, normalize_text,
+    register_instruction, request_body, source_language_hint, split_into_chunks, strip_preamble,
+    translate_into_targets, translate_long_text, translate_text_with_details,
+    translate_with_models, truncate_input, user_agent, user_message, with_input_truncated_note,
+    TextChunk, TokenUsage, TranslationDetails, TranslationError, CONTEXT_AFTER_MARKER,
+    CONTEXT_BEFORE_MARKER, CONTEXT_INSTRUCTION, DEFAULT_USER_AGENT, INPUT_TRUNCATED_NOTE,
+    MIN_AUTO_MAX_TOKENS, TEXT_MARKER, TRUNCATION_MARKER,
+};
+use translator::{
+    request_translation, translate_text, Metrics, OutputSink, RateLimiter, TranslationOutcome,
+    TranslationResult,
+};
+use wiremock::matchers::{body_string_contains, header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+mod common;
+use common::chat_completion_response;
+
+// Default config pointed at the given API endpoint and model
+fn api_config(api_url: &str, model_version: &str) -> Config {
+    Config {
+        api_url: api_url.to_string(),
+        model_version: model_version.to_string(),
+        ..Config::default()
+    }
+}
 
 #[tokio::test]
 async fn test_empty_text() {
     let result = translate_text(
         "",
+        None,
         Language::Spanish,
         "test-key".to_string(),
-        "http://127.0.0.1:9999".to_string(), // Use local unreachable address
-        "gpt-3.5-turbo".to_string(),
+        &api_config("http://127.0.0.1:9999", "gpt-3.5-turbo"), // Use local unreachable address
+        None,
     )
     .await;
 
@@ -21,10 +50,11 @@ async fn test_empty_text() {
 async fn test_whitespace_only_text() {
     let result = translate_text(
         "   \t\n   ",
+        None,
         Language::French,
         "test-key".to_string(),
-        "http://127.0.0.1:9999".to_string(),
-        "gpt-3.5-turbo".to_string(),
+        &api_config("http://127.0.0.1:9999", "gpt-3.5-turbo"),
+        None,
     )
     .await;
 
@@ -36,10 +66,10 @@ async fn test_whitespace_only_text() {
 async fn test_network_error_with_timeout() {
     let future = translate_text(
         "Hello, world!",
+        None,
         Language::German,
         "test-key".to_string(),
-        "http://127.0.0.1:9999".to_string(), // Local unreachable address
-        "gpt-3.5-turbo".to_string(),
+        &api_config("http://127.0.0.1:9999", "gpt-3.5-turbo"), // Local unreachable address
     );
 
     let result = timeout(Duration::from_secs(5), future).await;
@@ -65,10 +95,10 @@ async fn test_network_error_with_timeout() {
 async fn test_single_language() {
     let future = translate_text(
         "Hello",
+        None,
         Language::Spanish,
         "test-key".to_string(),
-        "http://127.0.0.1:9999".to_string(),
-        "gpt-3.5-turbo".to_string(),
+        &api_config("http://127.0.0.1:9999", "gpt-3.5-turbo"),
     );
 
     let result = timeout(Duration::from_secs(5), future).await;
@@ -91,10 +121,10 @@ async fn test_multiple_languages_with_timeout() {
     for language in languages {
         let future = translate_text(
             "Hello",
+            None,
             language,
             "test-key".to_string(),
-            "http://127.0.0.1:9999".to_string(),
-            "gpt-3.5-turbo".to_string(),
+            &api_config("http://127.0.0.1:9999", "gpt-3.5-turbo"),
         );
 
         let result = timeout(Duration::from_secs(2), future).await;
@@ -116,10 +146,10 @@ async fn test_long_text() {
     let long_text = "Lorem ipsum ".repeat(50); // Reduced repetitions
     let future = translate_text(
         &long_text,
+        None,
         Language::Spanish,
         "test-key".to_string(),
-        "http://127.0.0.1:9999".to_string(),
-        "gpt-3.5-turbo".to_string(),
+        &api_config("http://127.0.0.1:9999", "gpt-3.5-turbo"),
     );
 
     let result = timeout(Duration::from_secs(5), future).await;
@@ -134,3 +164,1889 @@ async fn test_long_text() {
         }
     }
 }
+
+#[tokio::test(start_paused = true)]
+async fn test_rate_limiter_waits_for_refill() {
+    // 2 requests per minute: one token every 30 seconds
+    let limiter = RateLimiter::per_minute(2);
+    let start = Instant::now();
+
+    // The bucket starts full, so the first two requests go through immediately
+    limiter.acquire().await;
+    limiter.acquire().await;
+    assert_eq!(start.elapsed(), Duration::ZERO);
+    assert!(!limiter.has_token());
+
+    // The third request has to wait for a token to be refilled
+    limiter.acquire().await;
+    let waited = start.elapsed();
+    assert!(
+        waited >= Duration::from_secs(30),
+        "Waited only {:?}",
+        waited
+    );
+    assert!(
+        waited < Duration::from_secs(31),
+        "Waited too long: {:?}",
+        waited
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_rate_limiter_clones_share_bucket() {
+    let limiter = RateLimiter::per_minute(1);
+    let clone = limiter.clone();
+
+    limiter.acquire().await;
+    assert!(!clone.has_token());
+
+    tokio::time::advance(Duration::from_secs(60)).await;
+    assert!(clone.has_token());
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_rate_limiter_zero_is_unlimited() {
+    let limiter = RateLimiter::per_minute(0);
+    let start = Instant::now();
+
+    for _ in 0..100 {
+        limiter.acquire().await;
+    }
+    assert!(limiter.has_token());
+    assert_eq!(start.elapsed(), Duration::ZERO);
+}
+
+// Build a minimal OpenAI-compatible chat completion response
+
+#[test]
+fn test_split_into_chunks_on_paragraph_boundaries() {
+    let text = "Alpha one.\n\nBravo two.\n\n\nCharlie three.";
+    let chunks = split_into_chunks(text, 25);
+
+    // The first two paragraphs fit together, the third starts a new chunk
+    assert_eq!(
+        chunks,
+        vec![
+            TextChunk {
+                text: "Alpha one.\n\nBravo two.".to_string(),
+                separator: "\n\n\n".to_string(),
+            },
+            TextChunk {
+                text: "Charlie three.".to_string(),
+                separator: "".to_string(),
+            },
+        ]
+    );
+
+    // Reassembling the chunks gives back the original text
+    let reassembled: String = chunks
+        .iter()
+        .map(|chunk| format!("{}{}", chunk.text, chunk.separator))
+        .collect();
+    assert_eq!(reassembled, text);
+}
+
+#[test]
+fn test_split_into_chunks_oversized_paragraph() {
+    let text = "один два три четыре пять шесть семь восемь девять десять";
+    let chunks = split_into_chunks(text, 15);
+
+    assert!(chunks.len() > 1);
+    for chunk in &chunks {
+        assert!(
+            chunk.text.chars().count() <= 15,
+            "Chunk too long: {:?}",
+            chunk.text
+        );
+    }
+
+    let reassembled: String = chunks
+        .iter()
+        .map(|chunk| format!("{}{}", chunk.text, chunk.separator))
+        .collect();
+    assert_eq!(reassembled, text);
+}
+
+#[test]
+fn test_split_into_chunks_short_text() {
+    let chunks = split_into_chunks("Hello, world!", 8000);
+    assert_eq!(chunks.len(), 1);
+    assert_eq!(chunks[0].text, "Hello, world!");
+}
+
+#[tokio::test]
+async fn test_translate_long_text_reassembles_in_order() {
+    let mock_server = MockServer::start().await;
+    for (source, translation) in [("Alpha", "UNO"), ("Bravo", "DOS"), ("Charlie", "TRES")] {
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_string_contains(source))
+            .respond_with(chat_completion_response(translation, "stop"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+    }
+
+    let result = translate_long_text(
+        "Alpha one.\n\nBravo two.\n\n\nCharlie three.",
+        None,
+        Language::Spanish,
+        "test-key".to_string(),
+        &Config {
+            max_input_chars: 14,
+            ..api_config(&mock_server.uri(), "test-model")
+        },
+        None,
+    )
+    .await;
+
+    assert_eq!(result.unwrap(), "UNO\n\nDOS\n\n\nTRES");
+}
+
+#[tokio::test]
+async fn test_translate_into_targets_concurrently_in_order() {
+    let mock_server = MockServer::start().await;
+    // The first language answers last, so completion order differs from the requested order
+    for (language, translation, delay_ms) in [
+        ("German", "Hallo", 600),
+        ("French", "Bonjour", 0),
+        ("Spanish", "Hola", 300),
+    ] {
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_string_contains(format!(
+                "translates text into {}",
+                language
+            )))
+            .respond_with(
+                chat_completion_response(translation, "stop")
+                    .set_delay(Duration::from_millis(delay_ms)),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+    }
+    let config = api_config(&mock_server.uri(), "test-model");
+
+    let start = Instant::now();
+    let results = translate_into_targets(
+        "Hello",
+        Some(Language::English),
+        &[Language::German, Language::French, Language::Spanish],
+        "test-key",
+        &config,
+        None,
+    )
+    .await;
+
+    // Concurrent: about as long as the slowest request, not the sum of all of them
+    assert!(
+        start.elapsed() < Duration::from_millis(850),
+        "Requests should run concurrently, took {:?}",
+        start.elapsed()
+    );
+    assert_eq!(
+        results,
+        vec![
+            (Language::German, Ok("Hallo".to_string())),
+            (Language::French, Ok("Bonjour".to_string())),
+            (Language::Spanish, Ok("Hola".to_string())),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_translate_with_models_concurrently_in_order() {
+    let mock_server = MockServer::start().await;
+    // The first model answers last, so completion order differs from the configured order
+    for (model, translation, delay_ms) in [
+        ("model-slow", "Bonjour", 600),
+        ("model-fast", "Salut", 0),
+        ("model-mid", "Coucou", 300),
+    ] {
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_string_contains(format!(r#""model":"{}""#, model)))
+            .respond_with(
+                chat_completion_response(translation, "stop")
+                    .set_delay(Duration::from_millis(delay_ms)),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+    }
+    // model_for_pair would send every request to one model
+    let mut config = api_config(&mock_server.uri(), "test-model");
+    config
+        .model_for_pair
+        .insert("en-fr".to_string(), "test-model".to_string());
+    let models = vec![
+        "model-slow".to_string(),
+        "model-fast".to_string(),
+        "model-mid".to_string(),
+    ];
+
+    let start = Instant::now();
+    let results = translate_with_models(
+        "Hello",
+        Some(Language::English),
+        Language::French,
+        &models,
+        "test-key",
+        &config,
+        Some(&RateLimiter::per_minute(0)),
+    )
+    .await;
+
+    // Concurrent: about as long as the slowest request, not the sum of all of them
+    assert!(
+        start.elapsed() < Duration::from_millis(850),
+        "Requests should run concurrently, took {:?}",
+        start.elapsed()
+    );
+    assert_eq!(
+        results,
+        vec![
+            ("model-slow".to_string(), Ok("Bonjour".to_string())),
+            ("model-fast".to_string(), Ok("Salut".to_string())),
+            ("model-mid".to_string(), Ok("Coucou".to_string())),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_api_error_includes_status_and_body_detail() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(400).set_body_raw(
+            r#"{"error":{"message":"test-model is not a valid model ID","code":400,"param":"model","metadata":{"provider_name":"OpenAI"}}}"#,
+            "application/json",
+        ))
+        .mount(&mock_server)
+        .await;
+
+    let result = translate_text(
+        "Hello",
+        None,
+        Language::Spanish,
+        "test-key".to_string(),
+        &api_config(&mock_server.uri(), "test-model"),
+        None,
+    )
+    .await;
+
+    let error = result.unwrap_err();
+    assert!(
+        error.starts_with("API Error"),
+        "Unexpected error: {}",
+        error
+    );
+    assert!(error.contains("HTTP 400"), "Missing status: {}", error);
+    assert!(
+        error.contains("test-model is not a valid model ID"),
+        "Missing message: {}",
+        error
+    );
+    assert!(error.contains("model"), "Missing param: {}", error);
+    assert!(
+        error.contains("provider_name"),
+        "Missing metadata: {}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn test_auth_error_for_rejected_key() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(header("authorization", "Bearer expired-key"))
+        .respond_with(ResponseTemplate::new(401).set_body_raw(
+            r#"{"error":{"message":"User not found.","code":401}}"#,
+            "application/json",
+        ))
+        .mount(&mock_server)
+        .await;
+    // OpenAI answers a wrong key with an invalid_api_key code
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(header("authorization", "Bearer wrong-key"))
+        .respond_with(ResponseTemplate::new(403).set_body_raw(
+            r#"{"error":{"message":"Incorrect API key provided","type":"invalid_request_error","code":"invalid_api_key"}}"#,
+            "application/json",
+        ))
+        .mount(&mock_server)
+        .await;
+
+    for (key, status, detail) in [
+        ("expired-key", "HTTP 401", "User not found."),
+        ("wrong-key", "HTTP 403", "Incorrect API key provided"),
+    ] {
+        let error = translate_text(
+            "Hello",
+            None,
+            Language::Spanish,
+            key.to_string(),
+            &api_config(&mock_server.uri(), "test-model"),
+            None,
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            error.starts_with("Invalid or missing API key \u{2014} check your configuration."),
+            "{}",
+            error
+        );
+        assert!(error.contains("The key is read from"), "{}", error);
+        // The API's own message stays, for telling an expired key from a mistyped one
+        assert!(error.contains(status), "{}", error);
+        assert!(error.contains(detail), "{}", error);
+    }
+}
+
+#[test]
+fn test_is_auth_failure() {
+    use reqwest::StatusCode;
+    assert!(is_auth_failure(StatusCode::UNAUTHORIZED, ""));
+    assert!(is_auth_failure(
+        StatusCode::BAD_REQUEST,
+        r#"{"error":{"message":"Bad key","type":"invalid_api_key"}}"#
+    ));
+    assert!(!is_auth_failure(
+        StatusCode::BAD_REQUEST,
+        r#"{"error":{"message":"test-model is not a valid model ID","code":400}}"#
+    ));
+    assert!(!is_auth_failure(StatusCode::TOO_MANY_REQUESTS, "Slow down"));
+}
+
+#[tokio::test]
+async fn test_api_error_with_non_json_body() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(502).set_body_string("upstream connect error"))
+        .mount(&mock_server)
+        .await;
+
+    let result = translate_text(
+        "Hello",
+        None,
+        Language::Spanish,
+        "test-key".to_string(),
+        &api_config(&mock_server.uri(), "test-model"),
+        None,
+    )
+    .await;
+
+    let error = result.unwrap_err();
+    assert!(error.contains("HTTP 502"), "Missing status: {}", error);
+    assert!(
+        error.contains("upstream connect error"),
+        "Missing body: {}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn test_refusal_is_reported_as_refused() {
+    let mock_server = MockServer::start().await;
+    let body = r#"{"id":"chatcmpl-test","object":"chat.completion","created":1700000000,"model":"test-model","choices":[{"index":0,"message":{"role":"assistant","content":null,"refusal":"I can't help with that."},"finish_reason":"stop"}]}"#;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(body, "application/json"))
+        .mount(&mock_server)
+        .await;
+
+    let result = translate_text(
+        "Hello",
+        None,
+        Language::Spanish,
+        "test-key".to_string(),
+        &api_config(&mock_server.uri(), "test-model"),
+        None,
+    )
+    .await;
+
+    // The model's explanation is shown instead of the generic missing-content error
+    assert_eq!(
+        result.unwrap_err(),
+        TranslationError::Refused("I can't help with that.".to_string()).to_string()
+    );
+}
+
+#[tokio::test]
+async fn test_empty_choices_names_model_and_hints_at_cause() {
+    let mock_server = MockServer::start().await;
+    let body = r#"{"id":"chatcmpl-test","object":"chat.completion","created":1700000000,"model":"openai/gpt-4o","choices":[]}"#;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(body, "application/json"))
+        .mount(&mock_server)
+        .await;
+
+    let result = translate_text(
+        "Hello",
+        None,
+        Language::Spanish,
+        "test-key".to_string(),
+        &api_config(&mock_server.uri(), "openai/gpt-4o"),
+        None,
+    )
+    .await;
+
+    let error = result.unwrap_err();
+    assert_eq!(
+        error,
+        TranslationError::NoChoices("openai/gpt-4o".to_string()).to_string()
+    );
+    assert!(error.contains("model 'openai/gpt-4o'"), "{}", error);
+    assert!(error.contains("content filter"), "{}", error);
+    assert!(error.contains("check model_version"), "{}", error);
+
+    // Without a model in the response the message still reads well
+    let error = TranslationError::NoChoices(String::new()).to_string();
+    assert!(
+        error.starts_with("API returned no choices for the model."),
+        "{}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn test_dry_run_returns_prompt_without_network_call() {
+    // Any request reaching the server fails the test when it is dropped
+    let mock_server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(chat_completion_response("should not be called", "stop"))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    let result = translate_text(
+        "Guten Morgen",
+        None,
+        Language::French,
+        "test-key".to_string(),
+        &Config {
+            dry_run: true,
+            ..api_config(&mock_server.uri(), "test-model")
+        },
+        None,
+    )
+    .await;
+
+    let prompt = result.expect("Dry run should succeed");
+    assert!(prompt.contains("translates text into French"), "{}", prompt);
+    assert!(prompt.contains("Guten Morgen"), "{}", prompt);
+    assert!(prompt.contains("test-model"), "{}", prompt);
+}
+
+// System prompt shown by a dry run with the given config
+async fn dry_run_prompt(config: Config) -> String {
+    translate_text(
+        "Wie geht es dir?",
+        None,
+        Language::German,
+        "test-key".to_string(),
+        &Config {
+            dry_run: true,
+            ..config
+        },
+        None,
+    )
+    .await
+    .expect("Dry run should succeed")
+}
+
+#[tokio::test]
+async fn test_register_instruction_in_prompt() {
+    let formal = register_instruction("formal").unwrap();
+    let informal = register_instruction("informal").unwrap();
+    assert!(formal.contains("Sie"));
+    assert!(informal.contains("du"));
+
+    for (register, expected, unexpected) in
+        [("formal", formal, informal), ("Informal", informal, formal)]
+    {
+        let prompt = dry_run_prompt(Config {
+            register: register.to_string(),
+            ..Config::default()
+        })
+        .await;
+        assert!(prompt.contains(expected), "{}", prompt);
+        assert!(!prompt.contains(unexpected), "{}", prompt);
+    }
+
+    // Neutral and unknown registers leave the prompt as it was
+    assert_eq!(register_instruction("neutral"), None);
+    assert_eq!(register_instruction("polite"), None);
+    let neutral = dry_run_prompt(Config::default()).await;
+    assert!(!neutral.contains(formal) && !neutral.contains(informal));
+}
+
+#[tokio::test]
+async fn test_proofread_mode_prompt() {
+    let mut config = Config {
+        mode: "Proofread".to_string(),
+        register: "formal".to_string(),
+        context_prefix: Some("Earlier paragraph".to_string()),
+        ..Config::default()
+    };
+    config.per_language_prompt.insert(
+        "DE".to_string(),
+        "Translate into casual German.".to_string(),
+    );
+
+    let prompt = dry_run_prompt(config).await;
+    let (system, user) = prompt.split_once("\n\nUser:\n").unwrap();
+    assert!(system.contains("proofreads text"), "{}", prompt);
+    assert!(system.contains("keeping its language"), "{}", prompt);
+    // Nothing about the target language, or translating, gets in
+    for leaked in [
+        "German",
+        "translate",
+        "Translate",
+        register_instruction("formal").unwrap(),
+        CONTEXT_INSTRUCTION,
+    ] {
+        assert!(!system.contains(leaked), "{:?} in {}", leaked, prompt);
+    }
+    assert_eq!(user, "Wie geht es dir?");
+
+    // JSON replies are still requested in the format the parser expects
+    let prompt = dry_run_prompt(Config {
+        mode: "proofread".to_string(),
+        json_response: true,
+        ..Config::default()
+    })
+    .await;
+    assert!(prompt.contains("{\"translation\": \"...\"}"), "{}", prompt);
+    assert!(prompt.contains("corrected text"), "{}", prompt);
+    assert!(!prompt.contains("German"), "{}", prompt);
+}
+
+#[tokio::test]
+async fn test_translation_memory_in_prompt() {
+    translator::memory::remember(
+        Language::German,
+        "How is the weather?",
+        "Wie ist das Wetter?",
+    );
+
+    let config = Config {
+        use_translation_memory: true,
+        ..Config::default()
+    };
+    // dry_run_prompt translates "Wie geht es dir?" into German, sharing no words with it
+    let prompt = dry_run_prompt(config.clone()).await;
+    assert!(!prompt.contains("Wie ist das Wetter?"), "{}", prompt);
+
+    let prompt = translate_text(
+        "How is the traffic?",
+        None,
+        Language::German,
+        "test-key".to_string(),
+        &Config {
+            dry_run: true,
+            ..config
+        },
+        None,
+    )
+    .await
+    .expect("Dry run should succeed");
+    let system = prompt.split("\n\nUser:\n").next().unwrap();
+    assert!(
+        system.contains("Original:\nHow is the weather?\nTranslation:\nWie ist das Wetter?"),
+        "{}",
+        prompt
+    );
+
+    // Off by default
+    let prompt = translate_text(
+        "How is the traffic?",
+        None,
+        Language::German,
+        "test-key".to_string(),
+        &Config {
+            dry_run: true,
+            ..Config::default()
+        },
+        None,
+    )
+    .await
+    .expect("Dry run should succeed");
+    assert!(!prompt.contains("Wie ist das Wetter?"), "{}", prompt);
+}
+
+#[tokio::test]
+async fn test_source_language_hint_in_prompt() {
+    let hint = "The source text is in German.";
+    assert_eq!(
+        source_language_hint(Some(Language::German)).as_deref(),
+        Some(hint)
+    );
+    assert_eq!(source_language_hint(None), None);
+
+    // System prompt of a dry run from `source` into English
+    async fn prompt_from(source: Option<Language>, hint_source_language: bool) -> String {
+        let prompt = translate_text(
+            "Wie geht es dir?",
+            source,
+            Language::English,
+            "test-key".to_string(),
+            &Config {
+                dry_run: true,
+                hint_source_language,
+                ..Config::default()
+            },
+            None,
+        )
+        .await
+        .expect("Dry run should succeed");
+        prompt.split("\n\nUser:\n").next().unwrap().to_string()
+    }
+
+    let system = prompt_from(Some(Language::German), true).await;
+    assert!(system.contains(hint), "{}", system);
+
+    // Not without the option, nor for an unknown source
+    let system = prompt_from(Some(Language::German), false).await;
+    assert!(!system.contains("source text"), "{}", system);
+    let system = prompt_from(None, true).await;
+    assert!(!system.contains("source text"), "{}", system);
+}
+
+#[tokio::test]
+async fn test_register_not_added_to_per_language_prompt() {
+    let mut config = Config {
+        register: "formal".to_string(),
+        ..Config::default()
+    };
+    config.per_language_prompt.insert(
+        "DE".to_string(),
+        "Translate into casual German.".to_string(),
+    );
+
+    let prompt = dry_run_prompt(config).await;
+    assert!(
+        prompt.contains("Translate into casual German."),
+        "{}",
+        prompt
+    );
+    assert!(!prompt.contains(register_instruction("formal").unwrap()));
+}
+
+#[tokio::test]
+async fn test_domain_hint_in_system_prompt() {
+    let hint = "This text is from the medical domain.";
+    assert_eq!(domain_hint(Some(" medical ")).as_deref(), Some(hint));
+
+    let prompt = dry_run_prompt(Config {
+        domain_hint: Some("medical".to_string()),
+        ..Config::default()
+    })
+    .await;
+    let system = prompt.split("\n\nUser:\n").next().unwrap();
+    assert!(system.contains(hint), "{}", prompt);
+
+    // Per-language prompts get the hint too
+    let mut config = Config {
+        domain_hint: Some("medical".to_string()),
+        ..Config::default()
+    };
+    config.per_language_prompt.insert(
+        "DE".to_string(),
+        "Translate into casual German.".to_string(),
+    );
+    let prompt = dry_run_prompt(config).await;
+    assert!(
+        prompt.contains(&format!("Translate into casual German. {}", hint)),
+        "{}",
+        prompt
+    );
+}
+
+#[tokio::test]
+async fn test_no_domain_hint_when_unset_or_empty() {
+    assert_eq!(domain_hint(None), None);
+    assert_eq!(domain_hint(Some("  ")), None);
+
+    for domain in [None, Some(String::new()), Some("  ".to_string())] {
+        let prompt = dry_run_prompt(Config {
+            domain_hint: domain,
+            ..Config::default()
+        })
+        .await;
+        assert!(!prompt.contains("domain"), "{}", prompt);
+    }
+}
+
+#[test]
+fn test_normalize_text_pdf_style() {
+    // Hard line breaks inside paragraphs, also with CRLF and stray indentation
+    let pdf = "The quick brown fox jumps over\nthe lazy dog. It was a sunny\r\nday in the park.\n\nSecond paragraph\n  starts here.\n";
+    assert_eq!(
+        normalize_text(pdf),
+        "The quick brown fox jumps over the lazy dog. It was a sunny day in the park.\n\nSecond paragraph starts here.\n"
+    );
+}
+
+#[test]
+fn test_normalize_text_keeps_formatting() {
+    // Paragraph breaks and list items stay; only a wrapped list item is joined
+    let formatted = "Shopping list:\n- milk\n- eggs that are\n  fresh\n1. one\n2) two\n\n\nBye";
+    assert_eq!(
+        normalize_text(formatted),
+        "Shopping list:\n- milk\n- eggs that are fresh\n1. one\n2) two\n\n\nBye"
+    );
+
+    // Text without line breaks is unchanged
+    assert_eq!(normalize_text("One line only."), "One line only.");
+    assert_eq!(normalize_text(""), "");
+}
+
+#[tokio::test]
+async fn test_normalize_newlines_applied_before_translation() {
+    let prompt = translate_text(
+        "Guten\nMorgen",
+        None,
+        Language::French,
+        "test-key".to_string(),
+        &Config {
+            dry_run: true,
+            normalize_newlines: true,
+            ..Config::default()
+        },
+        None,
+    )
+    .await
+    .expect("Dry run should succeed");
+    assert!(prompt.ends_with("User:\nGuten Morgen"), "{}", prompt);
+}
+
+#[test]
+fn test_strip_preamble_removes_lead_ins() {
+    assert_eq!(
+        strip_preamble("Here is the translation:\nBonjour le monde"),
+        "Bonjour le monde"
+    );
+    assert_eq!(
+        strip_preamble("Sure! Here's the French translation:\n\nBonjour le monde"),
+        "Bonjour le monde"
+    );
+    assert_eq!(
+        strip_preamble("Certainly, here is the text translated into German:\nHallo Welt"),
+        "Hallo Welt"
+    );
+    assert_eq!(strip_preamble("Translation (French):\nBonjour"), "Bonjour");
+    assert_eq!(strip_preamble("French translation:\nBonjour"), "Bonjour");
+    // A label on the same line as the translation
+    assert_eq!(
+        strip_preamble("Translation: Bonjour le monde"),
+        "Bonjour le monde"
+    );
+    assert_eq!(
+        strip_preamble("translated text: Hallo\nWelt"),
+        "Hallo\nWelt"
+    );
+    // Only the lead-in goes, later lines stay as they are
+    assert_eq!(
+        strip_preamble("Here's the translation:\nLine one\n\nLine two"),
+        "Line one\n\nLine two"
+    );
+}
+
+#[test]
+fn test_strip_preamble_keeps_real_content() {
+    let clean = [
+        "Bonjour le monde",
+        // Starts like a lead-in, but doesn't announce a translation
+        "Sure, I'll be there at eight.",
+        "Here is the plan:\n1. Pack\n2. Leave",
+        // Mentions translation, but is part of the text
+        "Translation notes:\nKeep the names in English.",
+        "The translation of this word is hard.",
+        "Translation memory tools: a short overview",
+        // A lead-in with nothing after it is all there is
+        "Here is the translation:",
+        "Translation:",
+        "",
+    ];
+    for text in clean {
+        assert_eq!(strip_preamble(text), text, "{:?}", text);
+    }
+}
+
+#[test]
+fn test_fix_capitalization_across_scripts() {
+    // Latin, Cyrillic and Greek have case
+    assert_eq!(
+        fix_capitalization("Hello world", "bonjour le monde"),
+        "Bonjour le monde"
+    );
+    assert_eq!(
+        fix_capitalization("Good morning", "доброе утро"),
+        "Доброе утро"
+    );
+    assert_eq!(fix_capitalization("Привет", "γεια σου"), "Γεια σου");
+    // Leading quotes and punctuation are skipped over
+    assert_eq!(
+        fix_capitalization("\"Hello\"", "« bonjour »"),
+        "« Bonjour »"
+    );
+    assert_eq!(
+        fix_capitalization("¿Qué tal?", "¿wie geht's?"),
+        "¿Wie geht's?"
+    );
+
+    // A lowercase original leaves the translation alone
+    assert_eq!(
+        fix_capitalization("hello world", "bonjour le monde"),
+        "bonjour le monde"
+    );
+    // Scripts without case are a no-op, in either direction
+    assert_eq!(fix_capitalization("Hello", "你好"), "你好");
+    assert_eq!(fix_capitalization("Hello", "مرحبا"), "مرحبا");
+    assert_eq!(fix_capitalization("こんにちは", "hello"), "hello");
+    assert_eq!(fix_capitalization("Hello", ""), "");
+}
+
+#[tokio::test]
+async fn test_fix_capitalization_applied_to_translation() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(chat_completion_response("bonjour le monde", "stop"))
+        .expect(2)
+        .mount(&mock_server)
+        .await;
+    let config = api_config(&mock_server.uri(), "test-model");
+
+    // Off by default
+    let result = translate_text(
+        "Hello world",
+        None,
+        Language::French,
+        "test-key".to_string(),
+        &config,
+        None,
+    )
+    .await;
+    assert_eq!(result, Ok("bonjour le monde".to_string()));
+
+    let result = translate_text(
+        "Hello world",
+        None,
+        Language::French,
+        "test-key".to_string(),
+        &Config {
+            fix_capitalization: true,
+            ..config
+        },
+        None,
+    )
+    .await;
+    assert_eq!(result, Ok("Bonjour le monde".to_string()));
+}
+
+#[test]
+fn test_is_rtl() {
+    for language in [
+        Language::Arabic,
+        Language::Hebrew,
+        Language::Persian,
+        Language::Urdu,
+    ] {
+        assert!(is_rtl(language), "{:?} should be right-to-left", language);
+    }
+
+    for language in [
+        Language::English,
+        Language::French,
+        Language::Russian,
+        Language::Chinese,
+        Language::Turkish,
+        Language::Hindi,
+    ] {
+        assert!(!is_rtl(language), "{:?} should be left-to-right", language);
+    }
+}
+
+#[tokio::test]
+async fn test_requests_go_through_configured_proxy() {
+    // The mock server acts as the proxy; the API host itself doesn't exist
+    let proxy_server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(chat_completion_response("via proxy", "stop"))
+        .expect(1)
+        .mount(&proxy_server)
+        .await;
+
+    let result = translate_text(
+        "Hello",
+        None,
+        Language::Spanish,
+        "test-key".to_string(),
+        &Config {
+            proxy_url: Some(proxy_server.uri()),
+            ..api_config("http://api.translator.invalid", "test-model")
+        },
+        None,
+    )
+    .await;
+
+    assert_eq!(result.unwrap(), "via proxy");
+}
+
+#[test]
+fn test_user_agent_from_config() {
+    let config = Config::default();
+    assert_eq!(user_agent(&config), DEFAULT_USER_AGENT);
+    assert!(DEFAULT_USER_AGENT.starts_with("translator/"));
+
+    let config = Config {
+        user_agent: Some("my-gateway/1.0".to_string()),
+        ..Config::default()
+    };
+    assert_eq!(user_agent(&config), "my-gateway/1.0");
+
+    // Blank means unset
+    let config = Config {
+        user_agent: Some("  ".to_string()),
+        ..Config::default()
+    };
+    assert_eq!(user_agent(&config), DEFAULT_USER_AGENT);
+}
+
+#[tokio::test]
+async fn test_requests_send_user_agent() {
+    let mock_server = MockServer::start().await;
+    // Only answers requests carrying the expected header
+    for (agent, translation) in [
+        (DEFAULT_USER_AGENT, "default"),
+        ("my-gateway/1.0", "custom"),
+    ] {
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(header("user-agent", agent))
+            .respond_with(chat_completion_response(translation, "stop"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+    }
+
+    let config = api_config(&mock_server.uri(), "test-model");
+    let result = translate_text(
+        "Hello",
+        None,
+        Language::Spanish,
+        "test-key".to_string(),
+        &config,
+        None,
+    )
+    .await;
+    assert_eq!(result.unwrap(), "default");
+
+    let config = Config {
+        user_agent: Some("my-gateway/1.0".to_string()),
+        ..config
+    };
+    let result = translate_text(
+        "Hello",
+        None,
+        Language::Spanish,
+        "test-key".to_string(),
+        &config,
+        None,
+    )
+    .await;
+    assert_eq!(result.unwrap(), "custom");
+}
+
+#[tokio::test]
+async fn test_json_response_extracts_translation_field() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(body_string_contains(
+            "\"response_format\":{\"type\":\"json_object\"}",
+        ))
+        .respond_with(chat_completion_response(
+            r#"{"translation": " Bonjour le monde ", "notes": "greeting"}"#,
+            "stop",
+        ))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let result = translate_text(
+        "Hello world",
+        None,
+        Language::French,
+        "test-key".to_string(),
+        &Config {
+            json_response: true,
+            ..api_config(&mock_server.uri(), "test-model")
+        },
+        None,
+    )
+    .await;
+
+    assert_eq!(result, Ok("Bonjour le monde".to_string()));
+}
+
+#[tokio::test]
+async fn test_json_response_with_malformed_json() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(chat_completion_response("Bonjour le monde", "stop"))
+        .mount(&mock_server)
+        .await;
+
+    let result = translate_text(
+        "Hello world",
+        None,
+        Language::French,
+        "test-key".to_string(),
+        &Config {
+            json_response: true,
+            ..api_config(&mock_server.uri(), "test-model")
+        },
+        None,
+    )
+    .await;
+
+    let error = result.expect_err("Plain text should not be accepted in JSON mode");
+    assert!(error.contains("\"translation\" field"), "{}", error);
+    assert!(error.contains("Bonjour le monde"), "{}", error);
+}
+
+#[tokio::test]
+async fn test_per_language_prompt_selection() {
+    let mut config = Config {
+        dry_run: true,
+        ..api_config("http://127.0.0.1:9999", "test-model")
+    };
+    config.per_language_prompt.insert(
+        "de".to_string(),
+        "Translate into formal {target_language} using Sie.".to_string(),
+    );
+
+    // The German prompt is used for German, with the placeholder filled in
+    let prompt = translate_text(
+        "Hello",
+        None,
+        Language::German,
+        "test-key".to_string(),
+        &config,
+        None,
+    )
+    .await
+    .expect("Dry run should succeed");
+    assert!(
+        prompt.contains("System:\nTranslate into formal German using Sie.\n"),
+        "{}",
+        prompt
+    );
+
+    // Other languages fall back to the default prompt
+    let prompt = translate_text(
+        "Hello",
+        None,
+        Language::French,
+        "test-key".to_string(),
+        &config,
+        None,
+    )
+    .await
+    .expect("Dry run should succeed");
+    assert!(
+        prompt.contains("System:\nYou are a helpful assistant that translates text into French."),
+        "{}",
+        prompt
+    );
+    assert!(!prompt.contains("formal"), "{}", prompt);
+}
+
+#[tokio::test]
+async fn test_request_translation_with_mock_provider() {
+    // Mock mode never touches the network, so the API URL and key don't matter
+    let config = Config {
+        mock: true,
+        ..api_config("http://127.0.0.1:9999", "test-model")
+    };
+    let output = OutputSink::memory();
+
+    request_translation(
+        "Hello world".to_string(),
+        None,
+        Language::French,
+        String::new(),
+        config,
+        RateLimiter::per_minute(0),
+        output.clone(),
+    )
+    .await;
+
+    assert_eq!(output.text(), "[French] Hello world");
+}
+
+#[tokio::test]
+async fn test_request_translation_outcome_matches_output() {
+    let config = Config {
+        mock: true,
+        ..api_config("http://127.0.0.1:9999", "test-model")
+    };
+
+    // Success: the translation and its details
+    let output = OutputSink::memory();
+    let (outcome, _) = request_translation(
+        "Hello world".to_string(),
+        None,
+        Language::French,
+        String::new(),
+        config.clone(),
+        RateLimiter::per_minute(0),
+        output.clone(),
+    )
+    .await;
+    assert_eq!(
+        outcome.details().map(|details| details.text.as_str()),
+        Some("[French] Hello world")
+    );
+    assert_eq!(outcome.output_text(), output.text());
+
+    // Failure: the same error message that the output shows
+    let output = OutputSink::memory();
+    let (outcome, _) = request_translation(
+        "   ".to_string(),
+        None,
+        Language::French,
+        String::new(),
+        config,
+        RateLimiter::per_minute(0),
+        output.clone(),
+    )
+    .await;
+    assert_eq!(
+        outcome,
+        TranslationOutcome::Failure("Clipboard text is empty.".to_string())
+    );
+    assert_eq!(outcome.details(), None);
+    assert_eq!(outcome.output_text(), output.text());
+}
+
+#[tokio::test]
+async fn test_request_translation_shows_truncation_marker() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(body_string_contains("\"max_tokens\":1024"))
+        .respond_with(chat_completion_response("Bonjour le mon", "length"))
+        .expect(1) // No retry unless auto_extend_on_truncation is set
+        .mount(&mock_server)
+        .await;
+
+    let output = OutputSink::memory();
+    let (outcome, _) = request_translation(
+        "Hello world".to_string(),
+        None,
+        Language::French,
+        "test-key".to_string(),
+        api_config(&mock_server.uri(), "test-model"),
+        RateLimiter::per_minute(0),
+        output.clone(),
+    )
+    .await;
+    let details = outcome.details().expect("Translation should succeed");
+
+    assert_eq!(details.text, "Bonjour le mon");
+    assert_eq!(
+        output.text(),
+        "Bonjour le mon …(truncated, raise max_tokens)"
+    );
+}
+
+#[tokio::test]
+async fn test_request_translation_returns_metrics() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(
+            chat_completion_response("Bonjour", "stop").set_delay(Duration::from_millis(50)),
+        )
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let (outcome, metrics) = request_translation(
+        "Hello".to_string(),
+        None,
+        Language::French,
+        "test-key".to_string(),
+        api_config(&mock_server.uri(), "test-model"),
+        RateLimiter::per_minute(0),
+        OutputSink::memory(),
+    )
+    .await;
+
+    assert_eq!(outcome.output_text(), "Bonjour");
+    // request_translation doesn't detect, the caller adds that
+    assert_eq!(metrics.detection, None);
+    assert!(
+        metrics.api_latency >= Duration::from_millis(50),
+        "{:?}",
+        metrics
+    );
+    assert!(metrics.total >= metrics.api_latency, "{:?}", metrics);
+
+    // The startup flow adds its detection time and measures the total from its own start
+    let with_detection = metrics.with_detection(
+        Duration::from_millis(5),
+        metrics.total + Duration::from_millis(5),
+    );
+    assert_eq!(with_detection.detection, Some(Duration::from_millis(5)));
+    assert_eq!(with_detection.api_latency, metrics.api_latency);
+    assert_eq!(
+        with_detection.total,
+        metrics.total + Duration::from_millis(5)
+    );
+    assert!(with_detection
+        .summary()
+        .starts_with("detection: 5 ms\napi: "));
+
+    assert_eq!(
+        Metrics::default().summary(),
+        "detection: n/a\napi: 0 ms\ntotal: 0 ms"
+    );
+}
+
+#[tokio::test]
+async fn test_translate_text_with_details_surfaces_finish_reason() {
+    let mock_server = MockServer::start().await;
+    let body = r#"{"id":"chatcmpl-test","object":"chat.completion","created":1700000000,"model":"test-model","choices":[{"index":0,"message":{"role":"assistant","content":"Bonjour le mon"},"finish_reason":"length"}],"usage":{"prompt_tokens":25,"completion_tokens":4,"total_tokens":29}}"#;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(body, "application/json"))
+        .mount(&mock_server)
+        .await;
+
+    let details = translate_text_with_details(
+        "Hello world",
+        None,
+        Language::French,
+        "test-key".to_string(),
+        &api_config(&mock_server.uri(), "test-model"),
+        None,
+    )
+    .await
+    .expect("Translation should succeed");
+
+    assert_eq!(details.text, "Bonjour le mon");
+    assert_eq!(details.finish_reason.as_deref(), Some("length"));
+    assert_eq!(
+        details.usage,
+        Some(TokenUsage {
+            prompt_tokens: 25,
+            completion_tokens: 4,
+            total_tokens: 29,
+        })
+    );
+    assert_eq!(
+        details.summary(),
+        "finish_reason: length (output was truncated)\ntokens: 25 prompt + 4 completion = 29 total"
+    );
+}
+
+#[test]
+fn test_truncation_detection_and_marker() {
+    let truncated = TranslationDetails {
+        text: "Bonjour le mon".to_string(),
+        finish_reason: Some("length".to_string()),
+        usage: None,
+    };
+    assert!(truncated.is_truncated());
+    assert_eq!(
+        truncated.display_text(),
+        format!("Bonjour le mon {}", TRUNCATION_MARKER)
+    );
+
+    let complete = TranslationDetails {
+        text: "Bonjour le monde".to_string(),
+        finish_reason: Some("stop".to_string()),
+        usage: None,
+    };
+    assert!(!complete.is_truncated());
+    assert_eq!(complete.display_text(), "Bonjour le monde");
+    assert!(!TranslationDetails::default().is_truncated());
+}
+
+#[tokio::test]
+async fn test_auto_extend_on_truncation_retries_with_double_max_tokens() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(body_string_contains("\"max_tokens\":100"))
+        .respond_with(chat_completion_response("Bonjour le mon", "length"))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .and(body_string_contains("\"max_tokens\":200"))
+        .respond_with(chat_completion_response("Bonjour le monde", "stop"))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let details = translate_text_with_details(
+        "Hello world",
+        None,
+        Language::French,
+        "test-key".to_string(),
+        &Config {
+            max_tokens: 100,
+            auto_extend_on_truncation: true,
+            ..api_config(&mock_server.uri(), "test-model")
+        },
+        None,
+    )
+    .await
+    .expect("Translation should succeed");
+
+    assert_eq!(details.text, "Bonjour le monde");
+    assert!(!details.is_truncated());
+}
+
+#[test]
+fn test_auto_max_tokens_sizing() {
+    // Short texts get the minimum
+    assert_eq!(auto_max_tokens(0, 4096), MIN_AUTO_MAX_TOKENS);
+    assert_eq!(auto_max_tokens(11, 4096), MIN_AUTO_MAX_TOKENS);
+    // Medium texts: half the character count
+    assert_eq!(auto_max_tokens(2000, 4096), 1000);
+    assert_eq!(auto_max_tokens(5001, 4096), 2500);
+    // Long texts are clamped to the ceiling
+    assert_eq!(auto_max_tokens(20_000, 4096), 4096);
+    assert_eq!(auto_max_tokens(usize::MAX, 4096), 4096);
+    assert_eq!(auto_max_tokens(20_000, 2048), 2048);
+    // The ceiling wins over the minimum
+    assert_eq!(auto_max_tokens(11, 100), 100);
+}
+
+#[tokio::test]
+async fn test_auto_max_tokens_used_in_request() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(body_string_contains("\"max_tokens\":1000"))
+        .respond_with(chat_completion_response("Traduction", "stop"))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let result = translate_text(
+        &"a".repeat(2000),
+        None,
+        Language::French,
+        "test-key".to_string(),
+        &Config {
+            auto_max_tokens: true,
+            ..api_config(&mock_server.uri(), "test-model")
+        },
+        None,
+    )
+    .await;
+
+    assert_eq!(result, Ok("Traduction".to_string()));
+}
+
+#[test]
+fn test_cap_max_tokens_for_mapped_model() {
+    let config = Config {
+        model_version: "openai/gpt-3.5-turbo".to_string(),
+        model_max_tokens: [("openai/gpt-3.5-turbo".to_string(), 500)]
+            .into_iter()
+            .collect(),
+        ..Config::default()
+    };
+
+    assert_eq!(cap_max_tokens(2000, &config), 500);
+    // Values under the cap are kept
+    assert_eq!(cap_max_tokens(300, &config), 300);
+}
+
+#[test]
+fn test_cap_max_tokens_is_noop_for_unmapped_model() {
+    let config = Config {
+        model_version: "openai/gpt-4o".to_string(),
+        model_max_tokens: [("openai/gpt-3.5-turbo".to_string(), 500)]
+            .into_iter()
+            .collect(),
+        ..Config::default()
+    };
+
+    assert_eq!(cap_max_tokens(2000, &config), 2000);
+    assert_eq!(cap_max_tokens(2000, &Config::default()), 2000);
+}
+
+#[tokio::test]
+async fn test_model_max_tokens_used_in_request() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(body_string_contains("\"max_tokens\":500"))
+        .respond_with(chat_completion_response("Traduction", "stop"))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let result = translate_text(
+        "Hello",
+        None,
+        Language::French,
+        "test-key".to_string(),
+        &Config {
+            max_tokens: 2000,
+            model_max_tokens: [("test-model".to_string(), 500)].into_iter().collect(),
+            ..api_config(&mock_server.uri(), "test-model")
+        },
+        None,
+    )
+    .await;
+
+    assert_eq!(result, Ok("Traduction".to_string()));
+}
+
+#[test]
+fn test_user_message_wraps_text_in_context() {
+    // Without context the text is sent as it is
+    assert_eq!(user_message("Hello", None, None), "Hello");
+    assert_eq!(user_message("Hello", Some("  "), Some("")), "Hello");
+
+    assert_eq!(
+        user_message(
+            "the bank",
+            Some("We sat by the river."),
+            Some("It was muddy.")
+        ),
+        "[Context before, do not translate]\nWe sat by the river.\n\n\
+         [Text to translate]\nthe bank\n\n\
+         [Context after, do not translate]\nIt was muddy."
+    );
+    // Only the configured side is added
+    assert_eq!(
+        user_message("the bank", None, Some("It was muddy.")),
+        format!(
+            "{}\nthe bank\n\n{}\nIt was muddy.",
+            TEXT_MARKER, CONTEXT_AFTER_MARKER
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_context_is_sent_with_markers() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(body_string_contains(CONTEXT_BEFORE_MARKER))
+        .and(body_string_contains("We sat by the river."))
+        .and(body_string_contains(TEXT_MARKER))
+        .and(body_string_contains("the bank"))
+        .and(body_string_contains(CONTEXT_INSTRUCTION))
+        .respond_with(chat_completion_response("la rive", "stop"))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let result = translate_text(
+        "the bank",
+        None,
+        Language::French,
+        "test-key".to_string(),
+        &Config {
+            context_prefix: Some("We sat by the river.".to_string()),
+            ..api_config(&mock_server.uri(), "test-model")
+        },
+        None,
+    )
+    .await;
+
+    assert_eq!(result, Ok("la rive".to_string()));
+}
+
+#[tokio::test]
+async fn test_context_only_core_text_is_translated() {
+    let config = Config {
+        context_prefix: Some("We sat by the river.".to_string()),
+        context_suffix: Some("It was muddy.".to_string()),
+        ..Config::default()
+    };
+
+    // The prompt asks for the marked text alone
+    let prompt = dry_run_prompt(config.clone()).await;
+    assert!(prompt.contains(CONTEXT_INSTRUCTION), "{}", prompt);
+    assert!(
+        prompt.contains(&format!(
+            "User:\n{}\nWe sat by the river.",
+            CONTEXT_BEFORE_MARKER
+        )),
+        "{}",
+        prompt
+    );
+    assert!(
+        prompt.contains(&format!("{}\nWie geht es dir?", TEXT_MARKER)),
+        "{}",
+        prompt
+    );
+
+    // Mock translations show what is translated: the text without its context
+    let result = translate_text(
+        "the bank",
+        None,
+        Language::French,
+        String::new(),
+        &Config {
+            mock: true,
+            ..config
+        },
+        None,
+    )
+    .await;
+    assert_eq!(result, Ok("[French] the bank".to_string()));
+
+    // Without context the prompt doesn't mention it
+    let prompt = dry_run_prompt(Config::default()).await;
+    assert!(!prompt.contains(CONTEXT_INSTRUCTION), "{}", prompt);
+    assert!(!prompt.contains(TEXT_MARKER), "{}", prompt);
+}
+
+#[test]
+fn test_stop_sequences_reach_request() {
+    let config = Config {
+        stop_sequences: vec!["\n\nNote:".to_string(), "###".to_string()],
+        ..Config::default()
+    };
+    let request =
+        build_chat_request("Hello", "Translate", 100, &config).expect("Request should build");
+    let body = serde_json::to_value(&request).expect("Request should serialize");
+    assert_eq!(body["stop"], serde_json::json!(["\n\nNote:", "###"]));
+
+    // An empty list leaves the parameter out
+    let request = build_chat_request("Hello", "Translate", 100, &Config::default())
+        .expect("Request should build");
+    assert!(request.stop.is_none());
+    let body = serde_json::to_value(&request).expect("Request should serialize");
+    assert!(body.get("stop").is_none(), "{}", body);
+}
+
+#[tokio::test]
+async fn test_stop_sequences_sent_to_api() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(body_string_contains(r#""stop":["\n\nNote:"]"#))
+        .respond_with(chat_completion_response("Bonjour", "stop"))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let result = translate_text(
+        "Hello",
+        None,
+        Language::French,
+        "test-key".to_string(),
+        &Config {
+            stop_sequences: vec!["\n\nNote:".to_string()],
+            ..api_config(&mock_server.uri(), "test-model")
+        },
+        None,
+    )
+    .await;
+
+    assert_eq!(result, Ok("Bonjour".to_string()));
+}
+
+#[test]
+fn test_provider_routing_in_request_body() {
+    let routing = serde_json::json!({"order": ["openai", "azure"], "allow_fallbacks": false});
+    let config = Config {
+        provider_routing: Some(routing.clone()),
+        ..Config::default()
+    };
+    let request =
+        build_chat_request("Hello", "Translate", 100, &config).expect("Request should build");
+    let body = request_body(&request, &config).expect("Body should build");
+    assert_eq!(body["provider"], routing);
+    // The rest of the request is unchanged
+    assert_eq!(body["model"], serde_json::json!(config.model_version));
+    assert_eq!(body["max_tokens"], serde_json::json!(100));
+
+    // Not sent by default, nor when it isn't an object
+    let body = request_body(&request, &Config::default()).expect("Body should build");
+    assert!(body.get("provider").is_none(), "{}", body);
+    let config = Config {
+        provider_routing: Some(serde_json::json!(["openai"])),
+        ..Config::default()
+    };
+    let body = request_body(&request, &config).expect("Body should build");
+    assert!(body.get("provider").is_none(), "{}", body);
+}
+
+#[tokio::test]
+async fn test_provider_routing_sent_to_api() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(body_string_contains(r#""provider":{"order":["openai"]}"#))
+        .respond_with(chat_completion_response("Bonjour", "stop"))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let result = translate_text(
+        "Hello",
+        None,
+        Language::French,
+        "test-key".to_string(),
+        &Config {
+            provider_routing: Some(serde_json::json!({"order": ["openai"]})),
+            ..api_config(&mock_server.uri(), "test-model")
+        },
+        None,
+    )
+    .await;
+
+    assert_eq!(result, Ok("Bonjour".to_string()));
+}
+
+fn pair_config() -> Config {
+    Config {
+        model_version: "small-model".to_string(),
+        model_for_pair: [
+            ("ZH-EN".to_string(), "big-model".to_string()),
+            ("ja-en".to_string(), "japanese-model".to_string()),
+        ]
+        .into_iter()
+        .collect(),
+        ..Config::default()
+    }
+}
+
+#[test]
+fn test_model_for_matching_pair() {
+    let config = pair_config();
+    assert_eq!(
+        model_for_pair(Some(Language::Chinese), Language::English, &config),
+        "big-model"
+    );
+    // Keys match in any case
+    assert_eq!(
+        model_for_pair(Some(Language::Japanese), Language::English, &config),
+        "japanese-model"
+    );
+
+    // The pair's model is what the request uses
+    let pair = config_for_pair(Some(Language::Chinese), Language::English, &config);
+    assert_eq!(pair.model_version, "big-model");
+    let request =
+        build_chat_request("你好", "Translate", 100, &pair).expect("Request should build");
+    assert_eq!(request.model, "big-model");
+}
+
+#[test]
+fn test_model_for_missing_pair() {
+    let config = pair_config();
+    // Pairs are directed: EN-ZH is not ZH-EN
+    assert_eq!(
+        model_for_pair(Some(Language::English), Language::Chinese, &config),
+        "small-model"
+    );
+    assert_eq!(
+        model_for_pair(Some(Language::French), Language::English, &config),
+        "small-model"
+    );
+    assert_eq!(
+        config_for_pair(Some(Language::French), Language::English, &config),
+        config
+    );
+}
+
+#[test]
+fn test_model_for_unknown_source() {
+    let config = pair_config();
+    // No detected source, so no pair can match
+    assert_eq!(
+        model_for_pair(None, Language::English, &config),
+        "small-model"
+    );
+}
+
+#[tokio::test]
+async fn test_retry_on_empty_content() {
+    let mock_server = MockServer::start().await;
+    // The first reply is empty, the next one has the translation
+    Mock::given(method("POST"))
+        .respond_with(chat_completion_response("", "stop"))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(chat_completion_response("Bonjour", "stop"))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let result = translate_text(
+        "Hello",
+        None,
+        Language::French,
+        "test-key".to_string(),
+        &Config {
+            retry_on_empty: true,
+            ..api_config(&mock_server.uri(), "test-model")
+        },
+        None,
+    )
+    .await;
+
+    assert_eq!(result, Ok("Bonjour".to_string()));
+}
+
+#[tokio::test]
+async fn test_empty_content_without_retry() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(chat_completion_response("", "stop"))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let result = translate_text(
+        "Hello",
+        None,
+        Language::French,
+        "test-key".to_string(),
+        &api_config(&mock_server.uri(), "test-model"),
+        None,
+    )
+    .await;
+
+    assert_eq!(result, Err(TranslationError::NoContent.to_string()));
+}
+
+#[tokio::test]
+async fn test_whitespace_content_retried_up_to_max_retries() {
+    let mock_server = MockServer::start().await;
+    // Every reply is whitespace: the first request and two retries
+    Mock::given(method("POST"))
+        .respond_with(chat_completion_response(" \n ", "stop"))
+        .expect(3)
+        .mount(&mock_server)
+        .await;
+
+    let result = translate_text(
+        "Hello",
+        None,
+        Language::French,
+        "test-key".to_string(),
+        &Config {
+            retry_on_empty: true,
+            max_retries: 2,
+            ..api_config(&mock_server.uri(), "test-model")
+        },
+        None,
+    )
+    .await;
+
+    // Told apart from a reply without any content
+    assert_eq!(result, Err(TranslationError::BlankContent.to_string()));
+    assert_ne!(
+        TranslationError::BlankContent.to_string(),
+        TranslationError::NoContent.to_string()
+    );
+}
+
+#[test]
+fn test_seed_in_request_only_when_set() {
+    let config = Config {
+        seed: Some(42),
+        ..Config::default()
+    };
+    let request =
+        build_chat_request("Hello", "Translate", 100, &config).expect("Request should build");
+    let body = serde_json::to_value(&request).expect("Request should serialize");
+    assert_eq!(body["seed"], serde_json::json!(42));
+
+    let request = build_chat_request("Hello", "Translate", 100, &Config::default())
+        .expect("Request should build");
+    let body = serde_json::to_value(&request).expect("Request should serialize");
+    assert!(body.get("seed").is_none(), "{}", body);
+}
+
+#[test]
+fn test_truncate_input() {
+    // Text that fits is not cut
+    assert_eq!(truncate_input("Hello world", 11), None);
+    assert_eq!(truncate_input("Hello world", 100), None);
+    assert_eq!(truncate_input("", 0), None);
+
+    // Cut at the last word boundary before the limit
+    assert_eq!(truncate_input("Hello wonderful world", 10), Some("Hello"));
+    // A limit right before a space keeps the whole word
+    assert_eq!(
+        truncate_input("Hello wonderful world", 15),
+        Some("Hello wonderful")
+    );
+    assert_eq!(truncate_input("Hello  \n world", 6), Some("Hello"));
+    // Without a word boundary, at the limit
+    assert_eq!(truncate_input("Internationalization", 5), Some("Inter"));
+    assert_eq!(truncate_input("  Internationalization", 7), Some("  Inter"));
+    // Counted in characters, never cut inside one
+    assert_eq!(truncate_input("ééé ééé", 5), Some("ééé"));
+    assert_eq!(truncate_input("日本語のテキスト", 3), Some("日本語"));
+
+    assert_eq!(
+        with_input_truncated_note("Bonjour "),
+        format!("Bonjour {}", INPUT_TRUNCATED_NOTE)
+    );
+}
+
+#[tokio::test]
+async fn test_translation_max_chars_sends_only_the_start() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(body_string_contains("Hello wonderful"))
+        .respond_with(chat_completion_response("Bonjour merveilleux", "stop"))
+        .expect(2)
+        .mount(&mock_server)
+        .await;
+
+    let config = Config {
+        translation_max_chars: Some(18),
+        ..api_config(&mock_server.uri(), "test-model")
+    };
+    let result = translate_text(
+        "Hello wonderful world of text",
+        None,
+        Language::French,
+        "test-key".to_string(),
+        &config,
+        None,
+    )
+    .await;
+    assert_eq!(
+        result,
+        Ok(format!("Bonjour merveilleux {}", INPUT_TRUNCATED_NOTE))
+    );
+
+    let requests = mock_server.received_requests().await.unwrap_or_default();
+    let body = String::from_utf8_lossy(&requests[0].body).into_owned();
+    assert!(!body.contains("world"), "{}", body);
+
+    // Text within the limit is translated whole, without the note
+    let result = translate_long_text(
+        "Hello wonderful",
+        None,
+        Language::French,
+        "test-key".to_string(),
+        &config,
+        None,
+    )
+    .await;
+    assert_eq!(result, Ok("Bonjour merveilleux".to_string()));
+}
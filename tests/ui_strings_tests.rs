@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+
+// Import the crate to test
+use translator::config::{Config, ConfigWarning};
+use translator::ui_strings::{
+    fill, resolve, DEFAULTS, RATE_LIMITED, READING_CLIPBOARD, TRANSLATING, TRANSLATING_IN_PARTS,
+};
+
+#[test]
+fn test_resolve_with_partial_overrides() {
+    let mut overrides = HashMap::new();
+    overrides.insert(
+        READING_CLIPBOARD.to_string(),
+        "Lese Zwischenablage...".to_string(),
+    );
+    overrides.insert(
+        TRANSLATING.to_string(),
+        "Übersetze ins {language}...".to_string(),
+    );
+
+    // Overridden keys use the configured text
+    assert_eq!(
+        resolve(&overrides, READING_CLIPBOARD),
+        "Lese Zwischenablage..."
+    );
+    assert_eq!(
+        resolve(&overrides, TRANSLATING),
+        "Übersetze ins {language}..."
+    );
+    // The others keep their English defaults
+    assert_eq!(
+        resolve(&overrides, RATE_LIMITED),
+        "Rate limited, waiting..."
+    );
+    assert_eq!(
+        resolve(&overrides, TRANSLATING_IN_PARTS),
+        "Text is longer than {max_chars} characters, translating to {language} in parts..."
+    );
+    // Unknown keys show up as themselves
+    assert_eq!(resolve(&overrides, "no_such_key"), "no_such_key");
+
+    // Without overrides every key has a default
+    for (key, text) in DEFAULTS {
+        assert_eq!(resolve(&HashMap::new(), key), *text);
+    }
+}
+
+#[test]
+fn test_fill_placeholders() {
+    assert_eq!(
+        fill(
+            "Text is longer than {max_chars} characters, translating to {language} in parts...",
+            &[
+                ("language", "German".to_string()),
+                ("max_chars", "8000".to_string())
+            ]
+        ),
+        "Text is longer than 8000 characters, translating to German in parts..."
+    );
+    // Placeholders without a value are left alone
+    assert_eq!(fill("{language} {count}", &[]), "{language} {count}");
+}
+
+#[test]
+fn test_config_ui_text_and_validation() {
+    let mut config = Config::default();
+    assert_eq!(
+        config.ui_text(TRANSLATING, &[("language", "French".to_string())]),
+        "Translating to French..."
+    );
+
+    config
+        .ui_strings
+        .insert(TRANSLATING.to_string(), "Vers le {language}…".to_string());
+    config
+        .ui_strings
+        .insert("translatng".to_string(), "typo".to_string());
+    assert_eq!(
+        config.ui_text(TRANSLATING, &[("language", "French".to_string())]),
+        "Vers le French…"
+    );
+    assert_eq!(
+        config.validate(),
+        vec![ConfigWarning::UnknownUiString("translatng".to_string())]
+    );
+}
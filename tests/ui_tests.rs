@@ -1,5 +1,28 @@
 use lingua::Language;
-use translator::ui::choose_target_language;
+use tokio::time::{sleep, Duration, Instant};
+use translator::config::Config;
+use translator::detection::{choose_target_language, startup_target_language, Detection};
+use translator::settings::{LanguageUsage, Settings};
+use translator::translation::{check_clipboard_size, TranslationDetails};
+use translator::ui::{
+    activation_action, after_copy, count_words, cycle_language_index, format_button_label,
+    format_multi_translations, is_already_in_target, label_max_width, locked_target_language,
+    loggable_text, notification_preview, refined_target_language, remember_startup_target,
+    run_with_budget, should_notify, sort_languages_by_usage, source_button_to_disable, text_counts,
+    text_to_copy, Activation, AfterCopy,
+};
+
+// Target languages configured in the UI
+const TARGETS: &[Language] = &[
+    Language::English,
+    Language::French,
+    Language::German,
+    Language::Spanish,
+    Language::Italian,
+    Language::Polish,
+    Language::Russian,
+    Language::Ukrainian,
+];
 
 #[cfg(test)]
 mod tests {
@@ -13,6 +36,8 @@ mod tests {
             Language::English,
             Language::French,
             Language::German,
+            Language::English,
+            TARGETS,
         );
         assert_eq!(result, Language::English);
     }
@@ -25,6 +50,8 @@ mod tests {
             Language::English,
             Language::French,
             Language::German,
+            Language::English,
+            TARGETS,
         );
         assert_eq!(result, Language::German);
     }
@@ -37,6 +64,8 @@ mod tests {
             Language::English,
             Language::French,
             Language::English,
+            Language::English,
+            TARGETS,
         );
         assert_eq!(result, Language::French);
     }
@@ -44,8 +73,14 @@ mod tests {
     #[test]
     fn test_choose_target_language_no_source_detected() {
         // Test case 4: No source language detected
-        let result =
-            choose_target_language(None, Language::English, Language::French, Language::German);
+        let result = choose_target_language(
+            None,
+            Language::English,
+            Language::French,
+            Language::German,
+            Language::English,
+            TARGETS,
+        );
         // When source is None, it's not primary, so should return primary
         assert_eq!(result, Language::English);
     }
@@ -58,6 +93,8 @@ mod tests {
             Language::English,
             Language::English,
             Language::English,
+            Language::English,
+            TARGETS,
         );
         // Should return secondary (even though it's the same)
         assert_eq!(result, Language::English);
@@ -99,8 +136,533 @@ mod tests {
         ];
 
         for (source, primary, secondary, last, expected) in test_cases {
-            let result = choose_target_language(source, primary, secondary, last);
+            let result = choose_target_language(source, primary, secondary, last, primary, TARGETS);
             assert_eq!(result, expected);
         }
     }
+
+    #[test]
+    fn test_cycle_language_index_wraps_around() {
+        // Forward from the last button wraps to the first
+        assert_eq!(cycle_language_index(Some(0), 4, true), Some(1));
+        assert_eq!(cycle_language_index(Some(3), 4, true), Some(0));
+
+        // Backward from the first button wraps to the last
+        assert_eq!(cycle_language_index(Some(2), 4, false), Some(1));
+        assert_eq!(cycle_language_index(Some(0), 4, false), Some(3));
+    }
+
+    #[test]
+    fn test_cycle_language_index_without_active_button() {
+        assert_eq!(cycle_language_index(None, 3, true), Some(0));
+        assert_eq!(cycle_language_index(None, 3, false), Some(2));
+    }
+
+    #[test]
+    fn test_cycle_language_index_too_few_languages() {
+        assert_eq!(cycle_language_index(Some(0), 1, true), None);
+        assert_eq!(cycle_language_index(None, 1, false), None);
+        assert_eq!(cycle_language_index(None, 0, true), None);
+    }
+
+    #[test]
+    fn test_startup_target_language_keeps_manual_choice() {
+        let config = Config::default(); // EN primary, FR secondary, targets EN/FR/IT/PL
+        let settings = Settings {
+            last_language: Language::Polish,
+            prefer_manual_target: true,
+            ..Settings::default()
+        };
+
+        // Auto-selection would pick the primary language for a French source
+        assert_eq!(
+            startup_target_language(Some(Language::French), &config, &settings),
+            Language::Polish
+        );
+        assert_eq!(
+            startup_target_language(None, &config, &settings),
+            Language::Polish
+        );
+    }
+
+    #[test]
+    fn test_startup_target_language_auto_mode() {
+        let config = Config::default();
+        let settings = Settings {
+            last_language: Language::Polish,
+            prefer_manual_target: false,
+            ..Settings::default()
+        };
+
+        // Without the manual preference the regular selection runs
+        assert_eq!(
+            startup_target_language(Some(Language::French), &config, &settings),
+            Language::English
+        );
+        assert_eq!(
+            startup_target_language(Some(Language::English), &config, &settings),
+            Language::Polish
+        );
+    }
+
+    #[test]
+    fn test_pinned_target_language_wins_over_every_rule() {
+        let pinned = Config {
+            pinned_target_language: Some(Language::Polish),
+            ..Config::default() // EN primary, FR secondary, targets EN/FR/IT/PL
+        };
+        let auto = Settings {
+            last_language: Language::Italian,
+            prefer_manual_target: false,
+            ..Settings::default()
+        };
+        let manual = Settings {
+            prefer_manual_target: true,
+            ..auto.clone()
+        };
+
+        for source in [
+            None,                    // Undetected: would be the fallback
+            Some(Language::Russian), // Not configured: would be the fallback
+            Some(Language::French),  // Not primary: would be the primary
+            Some(Language::English), // Primary: would be the last language
+            Some(Language::Polish),  // Already in the pinned language
+        ] {
+            for settings in [&auto, &manual] {
+                assert_eq!(
+                    startup_target_language(source, &pinned, settings),
+                    Language::Polish,
+                    "{:?}",
+                    source
+                );
+            }
+            // Also when automatic selection is off
+            let no_auto = Config {
+                auto_select_target: false,
+                ..pinned.clone()
+            };
+            assert_eq!(
+                startup_target_language(source, &no_auto, &auto),
+                Language::Polish
+            );
+        }
+
+        // The second detection phase never corrects a pinned target
+        let detection = Detection {
+            language: Language::French,
+            confidence: 0.9,
+            runner_up: None,
+        };
+        assert_eq!(
+            refined_target_language(Some(&detection), Language::Polish, &pinned, &auto),
+            None
+        );
+        // Nor is it saved as the last language
+        assert!(!remember_startup_target(
+            Language::Polish,
+            &pinned,
+            Language::Italian
+        ));
+    }
+
+    #[test]
+    fn test_locked_target_language() {
+        assert_eq!(locked_target_language(&Config::default()), None);
+
+        let pinned = Config {
+            pinned_target_language: Some(Language::Polish),
+            ..Config::default()
+        };
+        // Pinning alone leaves the other buttons clickable
+        assert_eq!(locked_target_language(&pinned), None);
+        let locked = Config {
+            lock_pinned_target: true,
+            ..pinned
+        };
+        assert_eq!(locked_target_language(&locked), Some(Language::Polish));
+
+        // Locking without a pin locks nothing
+        let lock_only = Config {
+            lock_pinned_target: true,
+            ..Config::default()
+        };
+        assert_eq!(locked_target_language(&lock_only), None);
+    }
+
+    #[test]
+    fn test_startup_target_language_manual_choice_no_longer_a_target() {
+        let config = Config::default();
+        let settings = Settings {
+            last_language: Language::German, // No longer in all_target_languages
+            prefer_manual_target: true,
+            ..Settings::default()
+        };
+
+        // Falls back to automatic selection
+        assert_eq!(
+            startup_target_language(Some(Language::French), &config, &settings),
+            Language::English
+        );
+    }
+
+    #[test]
+    fn test_startup_target_language_auto_select_disabled() {
+        let config = Config {
+            auto_select_target: false,
+            ..Config::default()
+        };
+        let settings = Settings {
+            last_language: Language::Polish,
+            prefer_manual_target: false,
+            ..Settings::default()
+        };
+
+        // The last language is used whatever the source language is
+        assert_eq!(
+            startup_target_language(Some(Language::French), &config, &settings),
+            Language::Polish
+        );
+        assert_eq!(
+            startup_target_language(None, &config, &settings),
+            Language::Polish
+        );
+    }
+
+    #[test]
+    fn test_remember_startup_target_skips_write_when_disabled() {
+        let temp_dir = tempfile::tempdir().expect("Failed to create temp directory");
+        let original_config_home = std::env::var("XDG_CONFIG_HOME").ok();
+        std::env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+        let settings_path = temp_dir.path().join("translator").join("settings.toml");
+
+        let disabled = Config {
+            auto_select_target: false,
+            ..Config::default()
+        };
+        assert!(!remember_startup_target(
+            Language::English,
+            &disabled,
+            Language::Polish
+        ));
+        assert!(!settings_path.exists(), "No settings should be written");
+
+        // With automatic selection a changed target is saved
+        assert!(remember_startup_target(
+            Language::English,
+            &Config::default(),
+            Language::Polish
+        ));
+        assert!(settings_path.exists());
+
+        // A pinned target is never saved, so the last language is back once the pin is removed
+        let pinned = Config {
+            pinned_target_language: Some(Language::Polish),
+            ..Config::default()
+        };
+        assert!(!remember_startup_target(
+            Language::Polish,
+            &pinned,
+            Language::English
+        ));
+
+        if let Some(original) = original_config_home {
+            std::env::set_var("XDG_CONFIG_HOME", original);
+        } else {
+            std::env::remove_var("XDG_CONFIG_HOME");
+        }
+    }
+
+    // Stand-in for the startup flow: several steps that each take a while
+    async fn slow_startup_flow(steps: &mut Vec<&'static str>) {
+        for step in ["api key", "clipboard", "detection", "translation"] {
+            sleep(Duration::from_secs(10)).await;
+            steps.push(step);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run_with_budget_cuts_off_long_flow() {
+        let start = Instant::now();
+        let mut steps = Vec::new();
+
+        let completed =
+            run_with_budget(Duration::from_secs(25), slow_startup_flow(&mut steps)).await;
+
+        assert!(!completed, "Flow should be cut off by the budget");
+        assert_eq!(start.elapsed(), Duration::from_secs(25));
+        assert_eq!(steps, vec!["api key", "clipboard"], "Later steps never run");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run_with_budget_completes_within_budget() {
+        let mut steps = Vec::new();
+
+        let completed =
+            run_with_budget(Duration::from_secs(60), slow_startup_flow(&mut steps)).await;
+
+        assert!(completed);
+        assert_eq!(steps.len(), 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run_with_budget_zero_disables_limit() {
+        let mut steps = Vec::new();
+
+        let completed = run_with_budget(Duration::ZERO, slow_startup_flow(&mut steps)).await;
+
+        assert!(completed);
+        assert_eq!(steps.len(), 4);
+    }
+
+    #[test]
+    fn test_check_clipboard_size() {
+        // Up to and including the limit is fine
+        assert_eq!(check_clipboard_size(0, 1_000_000), Ok(()));
+        assert_eq!(check_clipboard_size(1_000_000, 1_000_000), Ok(()));
+
+        // One byte over is refused with a message naming both sizes
+        let message = check_clipboard_size(1_000_001, 1_000_000).unwrap_err();
+        assert!(message.contains("1000001 bytes"), "{}", message);
+        assert!(message.contains("limit is 1000000 bytes"), "{}", message);
+        assert!(message.contains("max_clipboard_bytes"), "{}", message);
+
+        // 0 disables the check
+        assert_eq!(check_clipboard_size(50_000_000, 0), Ok(()));
+    }
+
+    #[test]
+    fn test_sort_languages_by_usage() {
+        let mut usage = LanguageUsage::default();
+        usage.counts.insert("DE".to_string(), 5);
+        usage.counts.insert("ES".to_string(), 2);
+        usage.counts.insert("FR".to_string(), 2);
+
+        // Most used first; ties (ES/FR, and the unused ones) keep config order
+        let sorted = sort_languages_by_usage(
+            &[
+                Language::English,
+                Language::French,
+                Language::German,
+                Language::Spanish,
+                Language::Italian,
+            ],
+            &usage,
+        );
+        assert_eq!(
+            sorted,
+            vec![
+                Language::German,
+                Language::French,
+                Language::Spanish,
+                Language::English,
+                Language::Italian,
+            ]
+        );
+
+        // Without any usage the config order is kept
+        assert_eq!(
+            sort_languages_by_usage(TARGETS, &LanguageUsage::default()),
+            TARGETS.to_vec()
+        );
+    }
+
+    #[test]
+    fn test_should_notify() {
+        let details = TranslationDetails {
+            text: "Bonjour".to_string(),
+            finish_reason: Some("stop".to_string()),
+            usage: None,
+        };
+
+        // Finished in the background with notifications enabled
+        assert!(should_notify(true, false, Some(&details)));
+
+        // Disabled, window in front, or failed translation
+        assert!(!should_notify(false, false, Some(&details)));
+        assert!(!should_notify(true, true, Some(&details)));
+        assert!(!should_notify(true, false, None));
+    }
+
+    #[test]
+    fn test_notification_preview() {
+        assert_eq!(notification_preview("  Short text\n", 100), "Short text");
+
+        // Cut on characters, not bytes
+        assert_eq!(notification_preview("Привет, мир", 6), "Привет…");
+        assert_eq!(notification_preview("Привет", 6), "Привет");
+    }
+
+    #[test]
+    fn test_format_multi_translations() {
+        let results = vec![
+            (Language::German, Ok("Hallo".to_string())),
+            (Language::French, Err("API error (status 500)".to_string())),
+            (Language::Spanish, Ok("Hola".to_string())),
+        ];
+
+        // Failed translations are left out of the copied text
+        assert_eq!(
+            format_multi_translations(&results),
+            "German:\nHallo\n\nSpanish:\nHola"
+        );
+        assert_eq!(format_multi_translations(&[]), "");
+    }
+
+    #[test]
+    fn test_source_button_to_disable() {
+        // Source has a button and isn't the target
+        assert_eq!(
+            source_button_to_disable(Some(Language::German), TARGETS, Language::English),
+            Some(Language::German)
+        );
+
+        // Source without a button, undetected source, or source that is the active target
+        assert_eq!(
+            source_button_to_disable(Some(Language::Japanese), TARGETS, Language::English),
+            None
+        );
+        assert_eq!(
+            source_button_to_disable(None, TARGETS, Language::English),
+            None
+        );
+        assert_eq!(
+            source_button_to_disable(Some(Language::German), TARGETS, Language::German),
+            None
+        );
+    }
+
+    #[test]
+    fn test_is_already_in_target() {
+        // Confidently detected (or forced) as the target language
+        let german = Detection::certain(Language::German);
+        assert!(is_already_in_target(Some(&german), Language::German, true));
+        assert!(!is_already_in_target(
+            Some(&german),
+            Language::English,
+            true
+        ));
+
+        // Not sure enough, or too close to another language
+        let unsure = Detection::from_confidence_values(&[
+            (Language::German, 0.6),
+            (Language::Dutch, 0.2),
+            (Language::English, 0.2),
+        ])
+        .unwrap();
+        assert!(!is_already_in_target(Some(&unsure), Language::German, true));
+        let ambiguous = Detection {
+            language: Language::German,
+            confidence: 0.95,
+            runner_up: Some((Language::Dutch, 0.9)),
+        };
+        assert!(!is_already_in_target(
+            Some(&ambiguous),
+            Language::German,
+            true
+        ));
+
+        // Undetected source is always translated
+        assert!(!is_already_in_target(None, Language::German, true));
+
+        // A one-language detector can't tell, so a certain guess isn't trusted
+        assert!(!is_already_in_target(
+            Some(&german),
+            Language::German,
+            false
+        ));
+    }
+
+    #[test]
+    fn test_text_to_copy() {
+        let full = "Bonjour le monde";
+
+        // No selection, or an empty one, copies everything
+        assert_eq!(text_to_copy(full, None), full);
+        assert_eq!(text_to_copy(full, Some((3, 3))), full);
+
+        // Selection bounds are character offsets, in either order
+        assert_eq!(text_to_copy(full, Some((8, 10))), "le");
+        assert_eq!(text_to_copy(full, Some((10, 8))), "le");
+        assert_eq!(text_to_copy("Привет мир", Some((7, 10))), "мир");
+
+        // A selection running past the end stops at the end of the text
+        assert_eq!(text_to_copy(full, Some((11, 99))), "monde");
+    }
+
+    #[test]
+    fn test_count_words_across_scripts() {
+        assert_eq!(count_words("Hello, world! How are you?"), 5);
+        assert_eq!(count_words("Привет, как дела?"), 3);
+        assert_eq!(count_words("مرحبا بالعالم"), 2);
+
+        // Any Unicode whitespace separates words, including no-break and ideographic spaces
+        assert_eq!(count_words("one\u{00A0}two\tthree\n\nfour\u{3000}five"), 5);
+
+        // Without spaces a whole run of CJK text is a single "word"
+        assert_eq!(count_words("你好世界"), 1);
+        assert_eq!(count_words("こんにちは 世界"), 2);
+
+        assert_eq!(count_words(""), 0);
+        assert_eq!(count_words("   \n\t "), 0);
+    }
+
+    #[test]
+    fn test_text_counts() {
+        assert_eq!(text_counts("Привет, мир"), "2 words, 11 characters");
+        assert_eq!(text_counts("A"), "1 word, 1 character");
+        assert_eq!(text_counts(""), "0 words, 0 characters");
+    }
+
+    #[test]
+    fn test_activation_dispatch() {
+        // First launch: no window yet
+        assert_eq!(activation_action(0), Activation::Open);
+        // A later launch or --trigger reaches the running instance with its window open
+        assert_eq!(activation_action(1), Activation::Refresh);
+        assert_eq!(activation_action(2), Activation::Refresh);
+    }
+
+    #[test]
+    fn test_copy_closes_window_unless_disabled() {
+        // Default: Copy & Close
+        let action = after_copy(&Config::default());
+        assert_eq!(action, AfterCopy::Close);
+        assert_eq!(action.button_label(), "Copy & Close");
+
+        let keep_open = Config {
+            close_after_copy: false,
+            ..Config::default()
+        };
+        let action = after_copy(&keep_open);
+        assert_eq!(action, AfterCopy::KeepOpen);
+        assert_eq!(action.button_label(), "Copy");
+    }
+
+    #[test]
+    fn test_label_max_width() {
+        assert_eq!(label_max_width(60), 60);
+        // 0 leaves the width unset
+        assert_eq!(label_max_width(0), -1);
+        assert_eq!(label_max_width(u32::MAX), i32::MAX);
+    }
+
+    #[test]
+    fn test_loggable_text_hides_private_text() {
+        assert_eq!(loggable_text("Bonjour le monde", false), "Bonjour le monde");
+        // Only the length is logged, counted in characters
+        assert_eq!(loggable_text("Привет мир", true), "<10 characters hidden>");
+        assert_eq!(loggable_text("", true), "<0 characters hidden>");
+    }
+
+    #[test]
+    fn test_format_button_label() {
+        assert_eq!(format_button_label(Language::English, "upper"), "EN");
+        assert_eq!(format_button_label(Language::English, "lower"), "en");
+        assert_eq!(format_button_label(Language::English, "title"), "En");
+        assert_eq!(format_button_label(Language::Polish, " Title "), "Pl");
+        assert_eq!(format_button_label(Language::German, "LOWER"), "de");
+        // Unknown values keep the default
+        assert_eq!(format_button_label(Language::French, "camel"), "FR");
+        assert_eq!(format_button_label(Language::French, ""), "FR");
+    }
 }